@@ -11,9 +11,11 @@ import {
   Transaction,
   sendAndConfirmTransaction,
   SYSVAR_RENT_PUBKEY,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
 } from '@solana/web3.js';
-import {TOKEN_PROGRAM_ID} from "@solana/spl-token";
+import {createHash} from 'crypto';
 import fs from 'mz/fs';
+import nacl from 'tweetnacl';
 import path from 'path';
 
 import {
@@ -22,11 +24,37 @@ import {
   longToByteArray,
   newAccountWithLamports,
   readAccountFromFile,
-  findAssociatedTokenAddress
+  findAssociatedTokenAddress,
+  getTokenProgramId
 } from './utils';
 
 const tokenMintPubKey = new PublicKey('A2drjwVLywomUqEHcGo1piez3GWw5cfZN3d5LuD3aFFN');
 
+/**
+ * Metaplex token metadata program, the marketplace reads a mint's verified collection from it to pick a fee override
+ */
+const METADATA_PROGRAM_ID = new PublicKey('metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s');
+
+/**
+ * Id of the test bidder's bid, a bidder can hold several bids on the same mint under different ids
+ */
+const bidId = 0;
+
+/**
+ * Id of the test lister's listing, a fresh id lets the same mint be listed again after a sale
+ */
+const listId = 0;
+
+/**
+ * Most lamports of platform fee the taker of a test trade agrees to bear, the whole fee on a 1 SOL trade
+ */
+const maxFee = 10000000;
+
+/**
+ * Nonce of the test lister's signed order, burned when the order is executed
+ */
+const orderNonce = 0;
+
 /**
  * Connection to the network
  */
@@ -205,27 +233,46 @@ export async function list(): Promise<void> {
 
   const byteArray = [3];
   const amount = longToByteArray(1000000000);
-  const list = [Buffer.from(byteArray), Buffer.from(amount)];
+  // A zero minimum bid lets the lister accept any bid
+  const minBid = longToByteArray(0);
+  const list = [Buffer.from(byteArray), Buffer.from(amount), Buffer.from(longToByteArray(listId)), Buffer.from(minBid)];
   const buffer = Buffer.concat(list);
 
   const keyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
   const listerAccount = await readAccountFromFile(keyPairPath);
   const listerPubKey = listerAccount.publicKey;
 
-  const tokenAccountPubKey = await findAssociatedTokenAddress(listerPubKey, tokenMintPubKey);
+  const tokenProgramId = await getTokenProgramId(connection, tokenMintPubKey);
+  const tokenAccountPubKey = await findAssociatedTokenAddress(listerPubKey, tokenMintPubKey, tokenProgramId);
   const escrowStatePubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
     Buffer.from("List"),
     Buffer.from("State")
   ], programId))[0];
   const escrowVaultPubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
     Buffer.from("List"),
     Buffer.from("Vault")
   ], programId))[0];
 
+  const mintStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("Mint"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
   const instruction = new TransactionInstruction({
     keys: [
       {pubkey: listerPubKey, isSigner: true, isWritable: false},
@@ -234,9 +281,12 @@ export async function list(): Promise<void> {
       {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
       {pubkey: escrowVaultPubkey, isSigner: false, isWritable: true},
       {pubkey: programId, isSigner: false, isWritable: false},
-      {pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false},
+      {pubkey: tokenProgramId, isSigner: false, isWritable: false},
       {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
-      {pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false}
+      {pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false},
+      {pubkey: mintStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: false},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
     ],
     programId,
     data: buffer,
@@ -251,27 +301,36 @@ export async function list(): Promise<void> {
 
 export async function deList(): Promise<void> {
   const byteArray = [4];
-  const list = [Buffer.from(byteArray)];
+  const list = [Buffer.from(byteArray), Buffer.from(longToByteArray(listId))];
   const buffer = Buffer.concat(list);
 
   const keyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
   const listerAccount = await readAccountFromFile(keyPairPath);
   const listerPubKey = listerAccount.publicKey;
 
-  const tokenAccountPubKey = await findAssociatedTokenAddress(listerPubKey, tokenMintPubKey)
+  const tokenProgramId = await getTokenProgramId(connection, tokenMintPubKey);
+  const tokenAccountPubKey = await findAssociatedTokenAddress(listerPubKey, tokenMintPubKey, tokenProgramId);
   const escrowStatePubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
     Buffer.from("List"),
     Buffer.from("State")
   ], programId))[0];
   const escrowVaultPubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
     Buffer.from("List"),
     Buffer.from("Vault")
   ], programId))[0];
 
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
   const instruction = new TransactionInstruction({
     keys: [
       {pubkey: listerPubKey, isSigner: true, isWritable: false},
@@ -280,7 +339,8 @@ export async function deList(): Promise<void> {
       {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
       {pubkey: escrowVaultPubkey, isSigner: false, isWritable: true},
       {pubkey: programId, isSigner: false, isWritable: false},
-      {pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false}
+      {pubkey: tokenProgramId, isSigner: false, isWritable: false},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
     ],
     programId,
     data: buffer,
@@ -296,7 +356,9 @@ export async function deList(): Promise<void> {
 export async function bid(): Promise<void> {
   const byteArray = [5];
   const amount = longToByteArray(1000000000);
-  const list = [Buffer.from(byteArray), Buffer.from(amount)];
+  // Unix timestamp after which the bid can no longer be accepted, zero keeps it open until withdrawn
+  const expiry = longToByteArray(0);
+  const list = [Buffer.from(byteArray), Buffer.from(amount), Buffer.from(longToByteArray(bidId)), Buffer.from(expiry)];
   const buffer = Buffer.concat(list);
 
   const keyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
@@ -306,16 +368,26 @@ export async function bid(): Promise<void> {
   const escrowStatePubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
     Buffer.from("Bid"),
     Buffer.from("State")
   ], programId))[0];
   const escrowVaultPubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
     Buffer.from("Bid"),
     Buffer.from("Vault")
   ], programId))[0];
 
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
   const instruction = new TransactionInstruction({
     keys: [
       {pubkey: bidderPubKey, isSigner: true, isWritable: false},
@@ -324,7 +396,9 @@ export async function bid(): Promise<void> {
       {pubkey: escrowVaultPubkey, isSigner: false, isWritable: true},
       {pubkey: programId, isSigner: false, isWritable: false},
       {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
-      {pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false}
+      {pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: false},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
     ],
     programId,
     data: buffer,
@@ -339,7 +413,7 @@ export async function bid(): Promise<void> {
 
 export async function withdrawBid(): Promise<void> {
   const byteArray = [6];
-  const list = [Buffer.from(byteArray)];
+  const list = [Buffer.from(byteArray), Buffer.from(longToByteArray(bidId))];
   const buffer = Buffer.concat(list);
 
   const keyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
@@ -349,23 +423,32 @@ export async function withdrawBid(): Promise<void> {
   const escrowStatePubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
     Buffer.from("Bid"),
     Buffer.from("State")
   ], programId))[0];
   const escrowVaultPubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
     Buffer.from("Bid"),
     Buffer.from("Vault")
   ], programId))[0];
 
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
   const instruction = new TransactionInstruction({
     keys: [
       {pubkey: bidderPubKey, isSigner: true, isWritable: false},
       {pubkey: tokenMintPubKey, isSigner: false, isWritable: true},
       {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
       {pubkey: escrowVaultPubkey, isSigner: false, isWritable: true},
-      {pubkey: programId, isSigner: false, isWritable: false}
+      {pubkey: programId, isSigner: false, isWritable: false},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
     ],
     programId,
     data: buffer,
@@ -380,7 +463,8 @@ export async function withdrawBid(): Promise<void> {
 
 export async function acceptBid(): Promise<void> {
   const byteArray = [7];
-  const list = [Buffer.from(byteArray)];
+  const expectedPrice = longToByteArray(1000000000);
+  const list = [Buffer.from(byteArray), Buffer.from(longToByteArray(bidId)), Buffer.from(longToByteArray(listId)), Buffer.from(expectedPrice), Buffer.from(longToByteArray(maxFee))];
   const buffer = Buffer.concat(list);
 
   const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
@@ -394,6 +478,7 @@ export async function acceptBid(): Promise<void> {
   const listEscrowStatePubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
     Buffer.from("List"),
     Buffer.from("State")
   ], programId))[0];
@@ -404,22 +489,49 @@ export async function acceptBid(): Promise<void> {
   const listEscrowVaultPubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
     Buffer.from("List"),
     Buffer.from("Vault")
   ], programId))[0];
   const bidEscrowStatePubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
     Buffer.from("Bid"),
     Buffer.from("State")
   ], programId))[0];
   const bidEscrowVaultPubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
     Buffer.from("Bid"),
     Buffer.from("Vault")
   ], programId))[0];
 
+  const mintStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("Mint"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const feeSchedulePubkey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("FeeSchedule")], programId))[0];
+  const metadataPubkey = (await PublicKey.findProgramAddress([
+    Buffer.from("metadata"),
+    METADATA_PROGRAM_ID.toBuffer(),
+    tokenMintPubKey.toBuffer()
+  ], METADATA_PROGRAM_ID))[0];
+  const traderStatePubkey = (await PublicKey.findProgramAddress([
+    listerPubKey.toBuffer(),
+    Buffer.from("Trader"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
   const instruction = new TransactionInstruction({
     keys: [
       {pubkey: listerPubKey, isSigner: true, isWritable: false},
@@ -430,7 +542,12 @@ export async function acceptBid(): Promise<void> {
       {pubkey: bidEscrowStatePubkey, isSigner: false, isWritable: true},
       {pubkey: bidEscrowVaultPubkey, isSigner: false, isWritable: true},
       {pubkey: listEscrowStatePubkey, isSigner: false, isWritable: true},
-      {pubkey: listEscrowVaultPubkey, isSigner: false, isWritable: true}
+      {pubkey: listEscrowVaultPubkey, isSigner: false, isWritable: true},
+      {pubkey: mintStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: feeSchedulePubkey, isSigner: false, isWritable: false},
+      {pubkey: metadataPubkey, isSigner: false, isWritable: false},
+      {pubkey: traderStatePubkey, isSigner: false, isWritable: false},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
     ],
     programId,
     data: buffer,
@@ -445,7 +562,7 @@ export async function acceptBid(): Promise<void> {
 
 export async function withdrawOnSuccess(): Promise<void> {
   const byteArray = [8];
-  const list = [Buffer.from(byteArray)];
+  const list = [Buffer.from(byteArray), Buffer.from(longToByteArray(listId))];
   const buffer = Buffer.concat(list);
 
   const bidderKeyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
@@ -456,16 +573,19 @@ export async function withdrawOnSuccess(): Promise<void> {
   const listerAccount = await readAccountFromFile(listerKeyPairPath);
   const listerPubKey = listerAccount.publicKey;
 
-  const tokenAccountPubKey = await findAssociatedTokenAddress(bidderPubKey, tokenMintPubKey);
+  const tokenProgramId = await getTokenProgramId(connection, tokenMintPubKey);
+  const tokenAccountPubKey = await findAssociatedTokenAddress(bidderPubKey, tokenMintPubKey, tokenProgramId);
   const listEscrowStatePubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
     Buffer.from("List"),
     Buffer.from("State")
   ], programId))[0];
   const listEscrowVaultPubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
     Buffer.from("List"),
     Buffer.from("Vault")
   ], programId))[0];
@@ -478,7 +598,7 @@ export async function withdrawOnSuccess(): Promise<void> {
       {pubkey: listerPubKey, isSigner: false, isWritable: true},
       {pubkey: listEscrowStatePubkey, isSigner: false, isWritable: true},
       {pubkey: listEscrowVaultPubkey, isSigner: false, isWritable: true},
-      {pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false}
+      {pubkey: tokenProgramId, isSigner: false, isWritable: false}
     ],
     programId,
     data: buffer,
@@ -494,7 +614,8 @@ export async function withdrawOnSuccess(): Promise<void> {
 export async function acceptListing(): Promise<void> {
 
   const byteArray = [9];
-  const list = [Buffer.from(byteArray)];
+  const expectedPrice = longToByteArray(1000000000);
+  const list = [Buffer.from(byteArray), Buffer.from(longToByteArray(listId)), Buffer.from(expectedPrice), Buffer.from(longToByteArray(maxFee))];
   const buffer = Buffer.concat(list);
 
   const bidderKeyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
@@ -505,23 +626,50 @@ export async function acceptListing(): Promise<void> {
   const listerAccount = await readAccountFromFile(listerKeyPairPath);
   const listerPubKey = listerAccount.publicKey;
 
-  const tokenAccountPubKey = await findAssociatedTokenAddress(bidderPubKey, tokenMintPubKey);
+  const tokenProgramId = await getTokenProgramId(connection, tokenMintPubKey);
+  const tokenAccountPubKey = await findAssociatedTokenAddress(bidderPubKey, tokenMintPubKey, tokenProgramId);
 
   const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
   const vaultccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("Vault")], programId))[0];
   const listEscrowStatePubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
     Buffer.from("List"),
     Buffer.from("State")
   ], programId))[0];
   const listEscrowVaultPubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
     Buffer.from("List"),
     Buffer.from("Vault")
   ], programId))[0];
 
+  const mintStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("Mint"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const feeSchedulePubkey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("FeeSchedule")], programId))[0];
+  const metadataPubkey = (await PublicKey.findProgramAddress([
+    Buffer.from("metadata"),
+    METADATA_PROGRAM_ID.toBuffer(),
+    tokenMintPubKey.toBuffer()
+  ], METADATA_PROGRAM_ID))[0];
+  const traderStatePubkey = (await PublicKey.findProgramAddress([
+    listerPubKey.toBuffer(),
+    Buffer.from("Trader"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
   const instruction = new TransactionInstruction({
     keys: [
       {pubkey: bidderPubKey, isSigner: true, isWritable: false},
@@ -532,8 +680,13 @@ export async function acceptListing(): Promise<void> {
       {pubkey: vaultccountPubKey, isSigner: false, isWritable: true},
       {pubkey: listEscrowStatePubkey, isSigner: false, isWritable: true},
       {pubkey: listEscrowVaultPubkey, isSigner: false, isWritable: true},
-      {pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false},
-      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}
+      {pubkey: tokenProgramId, isSigner: false, isWritable: false},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
+      {pubkey: mintStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: feeSchedulePubkey, isSigner: false, isWritable: false},
+      {pubkey: metadataPubkey, isSigner: false, isWritable: false},
+      {pubkey: traderStatePubkey, isSigner: false, isWritable: false},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
     ],
     programId,
     data: buffer,
@@ -548,7 +701,7 @@ export async function acceptListing(): Promise<void> {
 
 export async function refund(): Promise<void> {
   const byteArray = [10];
-  const list = [Buffer.from(byteArray)];
+  const list = [Buffer.from(byteArray), Buffer.from(longToByteArray(bidId))];
   const buffer = Buffer.concat(list);
 
   const keyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
@@ -563,16 +716,24 @@ export async function refund(): Promise<void> {
   const escrowStatePubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
     Buffer.from("Bid"),
     Buffer.from("State")
   ], programId))[0];
   const escrowVaultPubkey = (await PublicKey.findProgramAddress([
     tokenMintPubKey.toBuffer(), 
     bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
     Buffer.from("Bid"),
     Buffer.from("Vault")
   ], programId))[0];
 
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
   const instruction = new TransactionInstruction({
     keys: [
       {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: false},
@@ -580,7 +741,218 @@ export async function refund(): Promise<void> {
       {pubkey: bidderPubKey, isSigner: false, isWritable: true},
       {pubkey: programStatePubkey, isSigner: false, isWritable: false},
       {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
-      {pubkey: escrowVaultPubkey, isSigner: false, isWritable: true}
+      {pubkey: escrowVaultPubkey, isSigner: false, isWritable: true},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [deployerAccount],
+  );
+}
+
+export async function refundListing(): Promise<void> {
+  const byteArray = [11];
+  const list = [Buffer.from(byteArray), Buffer.from(longToByteArray(listId))];
+  const buffer = Buffer.concat(list);
+
+  const keyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(keyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const tokenProgramId = await getTokenProgramId(connection, tokenMintPubKey);
+  const tokenAccountPubKey = await findAssociatedTokenAddress(listerPubKey, tokenMintPubKey, tokenProgramId);
+  const programStatePubkey = (await PublicKey.findProgramAddress([
+    Buffer.from("Platform"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const escrowStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(), 
+    listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
+    Buffer.from("List"),
+    Buffer.from("State")
+  ], programId))[0];
+  const escrowVaultPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(), 
+    listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
+    Buffer.from("List"),
+    Buffer.from("Vault")
+  ], programId))[0];
+
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: true},
+      {pubkey: tokenAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: tokenMintPubKey, isSigner: false, isWritable: true},
+      {pubkey: listerPubKey, isSigner: false, isWritable: true},
+      {pubkey: programStatePubkey, isSigner: false, isWritable: false},
+      {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: escrowVaultPubkey, isSigner: false, isWritable: true},
+      {pubkey: tokenProgramId, isSigner: false, isWritable: false},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [deployerAccount],
+  );
+}
+
+export async function bulkRefund(): Promise<void> {
+  const byteArray = [12];
+  const computeBudget = longToByteArray(0);
+  const list = [Buffer.from(byteArray), Buffer.from(computeBudget)];
+  const buffer = Buffer.concat(list);
+
+  const bidderKeyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
+  const bidderAccount = await readAccountFromFile(bidderKeyPairPath);
+  const bidderPubKey = bidderAccount.publicKey;
+
+  const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(listerKeyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const tokenProgramId = await getTokenProgramId(connection, tokenMintPubKey);
+  const tokenAccountPubKey = await findAssociatedTokenAddress(listerPubKey, tokenMintPubKey, tokenProgramId);
+  const programStatePubkey = (await PublicKey.findProgramAddress([
+    Buffer.from("Platform"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const bidEscrowStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(), 
+    bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
+    Buffer.from("Bid"),
+    Buffer.from("State")
+  ], programId))[0];
+  const bidEscrowVaultPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(), 
+    bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
+    Buffer.from("Bid"),
+    Buffer.from("Vault")
+  ], programId))[0];
+  const listEscrowStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(), 
+    listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
+    Buffer.from("List"),
+    Buffer.from("State")
+  ], programId))[0];
+  const listEscrowVaultPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(), 
+    listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
+    Buffer.from("List"),
+    Buffer.from("Vault")
+  ], programId))[0];
+
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: programStatePubkey, isSigner: false, isWritable: false},
+      {pubkey: tokenProgramId, isSigner: false, isWritable: false},
+      // Bid escrow: state, vault, bidder, order book
+      {pubkey: bidEscrowStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: bidEscrowVaultPubkey, isSigner: false, isWritable: true},
+      {pubkey: bidderPubKey, isSigner: false, isWritable: true},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true},
+      // List escrow: state, vault, lister, lister token account, mint, order book
+      {pubkey: listEscrowStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: listEscrowVaultPubkey, isSigner: false, isWritable: true},
+      {pubkey: listerPubKey, isSigner: false, isWritable: true},
+      {pubkey: tokenAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: tokenMintPubKey, isSigner: false, isWritable: false},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [deployerAccount],
+  );
+}
+
+export async function setFeeDistribution(): Promise<void> {
+  // The deployer keeps 80% of the fees and the test lister stands in for a partner taking 20%
+  const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(listerKeyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const recipients: [PublicKey, number][] = [[deployerAccount.publicKey, 8000], [listerPubKey, 2000]];
+  const list = [Buffer.from([22, recipients.length])];
+  for (const [recipient, bps] of recipients) {
+    list.push(recipient.toBuffer(), Buffer.from([bps >> 8, bps & 0xff]));
+  }
+  const buffer = Buffer.concat(list);
+
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+  const distributionPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("Distribution")], programId))[0];
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: true},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: false},
+      {pubkey: distributionPubKey, isSigner: false, isWritable: true},
+      {pubkey: programId, isSigner: false, isWritable: false},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
+      {pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [deployerAccount],
+  );
+}
+
+export async function distributeFees(): Promise<void> {
+  const buffer = Buffer.from([23]);
+
+  const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(listerKeyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const vaultccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("Vault")], programId))[0];
+  const distributionPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("Distribution")], programId))[0];
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: vaultccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: distributionPubKey, isSigner: false, isWritable: false},
+      {pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false},
+      // Recipients, in the order of the fee distribution table
+      {pubkey: deployerAccount.publicKey, isSigner: false, isWritable: true},
+      {pubkey: listerPubKey, isSigner: false, isWritable: true}
     ],
     programId,
     data: buffer,
@@ -591,4 +963,872 @@ export async function refund(): Promise<void> {
     new Transaction().add(instruction),
     [deployerAccount],
   );
-}
\ No newline at end of file
+}
+
+export async function changeReferralFee(): Promise<void> {
+  // Referrers get a fifth of the platform fee, in the same 1e9 scale as the platform fee
+  const list = [Buffer.from([24]), Buffer.from(longToByteArray(200000000))];
+  const buffer = Buffer.concat(list);
+
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: true}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [deployerAccount],
+  );
+}
+
+export async function changeFeeBearer(): Promise<void> {
+  // 0 charges the seller, 1 the buyer and 2 splits the fee between them
+  const buffer = Buffer.from([25, 2]);
+
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: true}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [deployerAccount],
+  );
+}
+
+export async function changeMinimums(): Promise<void> {
+  const minBidAmount = longToByteArray(10000000);
+  const minListAmount = longToByteArray(10000000);
+  const list = [Buffer.from([26]), Buffer.from(minBidAmount), Buffer.from(minListAmount)];
+  const buffer = Buffer.concat(list);
+
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: true}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [deployerAccount],
+  );
+}
+
+export async function slashBid(): Promise<void> {
+  const byteArray = [28];
+  const list = [Buffer.from(byteArray), Buffer.from(longToByteArray(bidId))];
+  const buffer = Buffer.concat(list);
+
+  const keyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
+  const bidderAccount = await readAccountFromFile(keyPairPath);
+  const bidderPubKey = bidderAccount.publicKey;
+
+  const programStatePubkey = (await PublicKey.findProgramAddress([
+    Buffer.from("Platform"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const vaultccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("Vault")], programId))[0];
+
+  const escrowStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(), 
+    bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
+    Buffer.from("Bid"),
+    Buffer.from("State")
+  ], programId))[0];
+  const escrowVaultPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(), 
+    bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
+    Buffer.from("Bid"),
+    Buffer.from("Vault")
+  ], programId))[0];
+
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: tokenMintPubKey, isSigner: false, isWritable: true},
+      {pubkey: bidderPubKey, isSigner: false, isWritable: true},
+      {pubkey: programStatePubkey, isSigner: false, isWritable: false},
+      {pubkey: vaultccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: escrowVaultPubkey, isSigner: false, isWritable: true},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [deployerAccount],
+  );
+}
+
+export async function changeBidBond(): Promise<void> {
+  const list = [Buffer.from([27]), Buffer.from(longToByteArray(1000000))];
+  const buffer = Buffer.concat(list);
+
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: true}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [deployerAccount],
+  );
+}
+/**
+ * Bubblegum, account compression and noop programs moving compressed NFTs
+ */
+const BUBBLEGUM_PROGRAM_ID = new PublicKey('BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY');
+const ACCOUNT_COMPRESSION_PROGRAM_ID = new PublicKey('cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK');
+const NOOP_PROGRAM_ID = new PublicKey('noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV');
+
+/**
+ * A compressed NFT as returned by a DAS provider's `getAsset` and `getAssetProof`, saved to
+ * dist/compressed/asset.json. The root and proof change whenever the tree does, so refresh the file
+ * after listing and before delisting or buying
+ */
+interface CompressedAsset {
+  tree: string;
+  root: string;
+  dataHash: string;
+  creatorHash: string;
+  nonce: number;
+  index: number;
+  proof: string[];
+}
+
+async function readCompressedAsset(): Promise<CompressedAsset> {
+  const assetPath = path.resolve(__dirname, '../../dist/compressed/asset.json');
+  return JSON.parse(await fs.readFile(assetPath, {encoding: 'utf8'}));
+}
+
+async function compressedListingAccounts(asset: CompressedAsset, listerPubKey: PublicKey) {
+  const treePubkey = new PublicKey(asset.tree);
+  const assetId = (await PublicKey.findProgramAddress([
+    Buffer.from("asset"),
+    treePubkey.toBuffer(),
+    Buffer.from(longToByteArray(asset.nonce).reverse())
+  ], BUBBLEGUM_PROGRAM_ID))[0];
+  const escrowStatePubkey = (await PublicKey.findProgramAddress([
+    assetId.toBuffer(),
+    listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
+    Buffer.from("CompressedList"),
+    Buffer.from("State")
+  ], programId))[0];
+  const treeAuthorityPubkey = (await PublicKey.findProgramAddress([treePubkey.toBuffer()], BUBBLEGUM_PROGRAM_ID))[0];
+
+  const treeKeys = [
+    {pubkey: treeAuthorityPubkey, isSigner: false, isWritable: false},
+    {pubkey: treePubkey, isSigner: false, isWritable: true},
+    {pubkey: NOOP_PROGRAM_ID, isSigner: false, isWritable: false},
+    {pubkey: ACCOUNT_COMPRESSION_PROGRAM_ID, isSigner: false, isWritable: false},
+    {pubkey: BUBBLEGUM_PROGRAM_ID, isSigner: false, isWritable: false},
+    {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}
+  ];
+  const mintStatePubkey = (await PublicKey.findProgramAddress([
+    assetId.toBuffer(),
+    Buffer.from("Mint"),
+    Buffer.from("State")
+  ], programId))[0];
+  const proofKeys = asset.proof.map(node => ({pubkey: new PublicKey(node), isSigner: false, isWritable: false}));
+  return {escrowStatePubkey, mintStatePubkey, treeKeys, proofKeys};
+}
+
+export async function listCompressed(): Promise<void> {
+  const asset = await readCompressedAsset();
+  const list = [
+    Buffer.from([29]),
+    Buffer.from(longToByteArray(LAMPORTS_PER_SOL)),
+    Buffer.from(longToByteArray(listId)),
+    new PublicKey(asset.root).toBuffer(),
+    new PublicKey(asset.dataHash).toBuffer(),
+    new PublicKey(asset.creatorHash).toBuffer(),
+    Buffer.from(longToByteArray(asset.nonce)),
+    Buffer.from(longToByteArray(asset.index).slice(4))
+  ];
+  const buffer = Buffer.concat(list);
+
+  const keyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(keyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const {escrowStatePubkey, mintStatePubkey, treeKeys, proofKeys} = await compressedListingAccounts(asset, listerPubKey);
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: listerPubKey, isSigner: true, isWritable: true},
+      {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
+      ...treeKeys,
+      {pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: false},
+      {pubkey: mintStatePubkey, isSigner: false, isWritable: true},
+      ...proofKeys
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [listerAccount],
+  );
+}
+
+export async function deListCompressed(): Promise<void> {
+  const asset = await readCompressedAsset();
+  const list = [Buffer.from([30]), Buffer.from(longToByteArray(listId)), new PublicKey(asset.root).toBuffer()];
+  const buffer = Buffer.concat(list);
+
+  const keyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(keyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const {escrowStatePubkey, treeKeys, proofKeys} = await compressedListingAccounts(asset, listerPubKey);
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: listerPubKey, isSigner: true, isWritable: true},
+      {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
+      ...treeKeys,
+      ...proofKeys
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [listerAccount],
+  );
+}
+
+export async function acceptCompressedListing(): Promise<void> {
+  const asset = await readCompressedAsset();
+  const list = [Buffer.from([31]), Buffer.from(longToByteArray(listId)), new PublicKey(asset.root).toBuffer()];
+  const buffer = Buffer.concat(list);
+
+  const bidderKeyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
+  const bidderAccount = await readAccountFromFile(bidderKeyPairPath);
+  const bidderPubKey = bidderAccount.publicKey;
+
+  const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(listerKeyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const {escrowStatePubkey, mintStatePubkey, treeKeys, proofKeys} = await compressedListingAccounts(asset, listerPubKey);
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+  const vaultAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("Vault")], programId))[0];
+  const feeSchedulePubkey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("FeeSchedule")], programId))[0];
+  const traderStatePubkey = (await PublicKey.findProgramAddress([
+    listerPubKey.toBuffer(),
+    Buffer.from("Trader"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: bidderPubKey, isSigner: true, isWritable: true},
+      {pubkey: listerPubKey, isSigner: false, isWritable: true},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: false},
+      {pubkey: vaultAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: mintStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: feeSchedulePubkey, isSigner: false, isWritable: false},
+      {pubkey: traderStatePubkey, isSigner: false, isWritable: false},
+      ...treeKeys,
+      ...proofKeys
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [bidderAccount],
+  );
+}
+
+async function delegatedListingAccounts(listerPubKey: PublicKey) {
+  const tokenProgramId = await getTokenProgramId(connection, tokenMintPubKey);
+  const listerTokenAccountPubKey = await findAssociatedTokenAddress(listerPubKey, tokenMintPubKey, tokenProgramId);
+  const escrowStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
+    Buffer.from("DelegatedList"),
+    Buffer.from("State")
+  ], programId))[0];
+  const editionPubkey = (await PublicKey.findProgramAddress([
+    Buffer.from("metadata"),
+    METADATA_PROGRAM_ID.toBuffer(),
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("edition")
+  ], METADATA_PROGRAM_ID))[0];
+  const mintStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("Mint"),
+    Buffer.from("State")
+  ], programId))[0];
+  return {tokenProgramId, listerTokenAccountPubKey, escrowStatePubkey, editionPubkey, mintStatePubkey};
+}
+
+export async function listDelegated(): Promise<void> {
+  const list = [Buffer.from([32]), Buffer.from(longToByteArray(LAMPORTS_PER_SOL)), Buffer.from(longToByteArray(listId))];
+  const buffer = Buffer.concat(list);
+
+  const keyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(keyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const {tokenProgramId, listerTokenAccountPubKey, escrowStatePubkey, editionPubkey, mintStatePubkey} = await delegatedListingAccounts(listerPubKey);
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: listerPubKey, isSigner: true, isWritable: true},
+      {pubkey: listerTokenAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: tokenMintPubKey, isSigner: false, isWritable: false},
+      {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: editionPubkey, isSigner: false, isWritable: false},
+      {pubkey: METADATA_PROGRAM_ID, isSigner: false, isWritable: false},
+      {pubkey: tokenProgramId, isSigner: false, isWritable: false},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
+      {pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false},
+      {pubkey: mintStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: false}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [listerAccount],
+  );
+}
+
+export async function deListDelegated(): Promise<void> {
+  const list = [Buffer.from([33]), Buffer.from(longToByteArray(listId))];
+  const buffer = Buffer.concat(list);
+
+  const keyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(keyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const {tokenProgramId, listerTokenAccountPubKey, escrowStatePubkey, editionPubkey} = await delegatedListingAccounts(listerPubKey);
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: listerPubKey, isSigner: true, isWritable: true},
+      {pubkey: listerTokenAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: tokenMintPubKey, isSigner: false, isWritable: false},
+      {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: editionPubkey, isSigner: false, isWritable: false},
+      {pubkey: METADATA_PROGRAM_ID, isSigner: false, isWritable: false},
+      {pubkey: tokenProgramId, isSigner: false, isWritable: false}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [listerAccount],
+  );
+}
+
+export async function acceptDelegatedListing(): Promise<void> {
+  const list = [Buffer.from([34]), Buffer.from(longToByteArray(listId))];
+  const buffer = Buffer.concat(list);
+
+  const bidderKeyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
+  const bidderAccount = await readAccountFromFile(bidderKeyPairPath);
+  const bidderPubKey = bidderAccount.publicKey;
+
+  const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(listerKeyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const {tokenProgramId, listerTokenAccountPubKey, escrowStatePubkey, editionPubkey, mintStatePubkey} = await delegatedListingAccounts(listerPubKey);
+  const tokenAccountPubKey = await findAssociatedTokenAddress(bidderPubKey, tokenMintPubKey, tokenProgramId);
+
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+  const vaultAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("Vault")], programId))[0];
+  const feeSchedulePubkey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("FeeSchedule")], programId))[0];
+  const metadataPubkey = (await PublicKey.findProgramAddress([
+    Buffer.from("metadata"),
+    METADATA_PROGRAM_ID.toBuffer(),
+    tokenMintPubKey.toBuffer()
+  ], METADATA_PROGRAM_ID))[0];
+  const traderStatePubkey = (await PublicKey.findProgramAddress([
+    listerPubKey.toBuffer(),
+    Buffer.from("Trader"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: bidderPubKey, isSigner: true, isWritable: true},
+      {pubkey: tokenAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: tokenMintPubKey, isSigner: false, isWritable: false},
+      {pubkey: listerPubKey, isSigner: false, isWritable: true},
+      {pubkey: listerTokenAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: false},
+      {pubkey: vaultAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: escrowStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: editionPubkey, isSigner: false, isWritable: false},
+      {pubkey: METADATA_PROGRAM_ID, isSigner: false, isWritable: false},
+      {pubkey: tokenProgramId, isSigner: false, isWritable: false},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
+      {pubkey: mintStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: feeSchedulePubkey, isSigner: false, isWritable: false},
+      {pubkey: metadataPubkey, isSigner: false, isWritable: false},
+      {pubkey: traderStatePubkey, isSigner: false, isWritable: false},
+      {pubkey: await orderNonceAddress(listerPubKey), isSigner: false, isWritable: false}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [bidderAccount],
+  );
+}
+
+export async function matchOrders(): Promise<void> {
+  const byteArray = [35];
+  const list = [Buffer.from(byteArray), Buffer.from(longToByteArray(bidId)), Buffer.from(longToByteArray(listId))];
+  const buffer = Buffer.concat(list);
+
+  const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerPubKey = (await readAccountFromFile(listerKeyPairPath)).publicKey;
+
+  const bidderKeyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
+  const bidderPubKey = (await readAccountFromFile(bidderKeyPairPath)).publicKey;
+
+  const listEscrowStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(), 
+    listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
+    Buffer.from("List"),
+    Buffer.from("State")
+  ], programId))[0];
+
+
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+  const vaultccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("Vault")], programId))[0];
+  const bidEscrowStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(), 
+    bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
+    Buffer.from("Bid"),
+    Buffer.from("State")
+  ], programId))[0];
+  const bidEscrowVaultPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(), 
+    bidderPubKey.toBuffer(),
+    Buffer.from(longToByteArray(bidId)),
+    Buffer.from("Bid"),
+    Buffer.from("Vault")
+  ], programId))[0];
+
+  const mintStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("Mint"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const feeSchedulePubkey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("FeeSchedule")], programId))[0];
+  const metadataPubkey = (await PublicKey.findProgramAddress([
+    Buffer.from("metadata"),
+    METADATA_PROGRAM_ID.toBuffer(),
+    tokenMintPubKey.toBuffer()
+  ], METADATA_PROGRAM_ID))[0];
+  const traderStatePubkey = (await PublicKey.findProgramAddress([
+    listerPubKey.toBuffer(),
+    Buffer.from("Trader"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: true},
+      {pubkey: tokenMintPubKey, isSigner: false, isWritable: false},
+      {pubkey: listerPubKey, isSigner: false, isWritable: true},
+      {pubkey: bidderPubKey, isSigner: false, isWritable: true},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: vaultccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: bidEscrowStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: bidEscrowVaultPubkey, isSigner: false, isWritable: true},
+      {pubkey: listEscrowStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: mintStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: feeSchedulePubkey, isSigner: false, isWritable: false},
+      {pubkey: metadataPubkey, isSigner: false, isWritable: false},
+      {pubkey: traderStatePubkey, isSigner: false, isWritable: false},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [deployerAccount],
+  );
+}
+
+export async function sweepListings(): Promise<void> {
+  // Buys the test listing, skipping it instead of failing if it is gone or above the maximum
+  const maxTotal = longToByteArray(2 * LAMPORTS_PER_SOL);
+  const list = [Buffer.from([36]), Buffer.from(maxTotal), Buffer.from([1])];
+  const buffer = Buffer.concat(list);
+
+  const bidderKeyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
+  const bidderAccount = await readAccountFromFile(bidderKeyPairPath);
+  const bidderPubKey = bidderAccount.publicKey;
+
+  const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerPubKey = (await readAccountFromFile(listerKeyPairPath)).publicKey;
+
+  const tokenProgramId = await getTokenProgramId(connection, tokenMintPubKey);
+  const tokenAccountPubKey = await findAssociatedTokenAddress(bidderPubKey, tokenMintPubKey, tokenProgramId);
+
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+  const vaultAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("Vault")], programId))[0];
+  const feeSchedulePubkey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("FeeSchedule")], programId))[0];
+
+  const listEscrowStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
+    Buffer.from("List"),
+    Buffer.from("State")
+  ], programId))[0];
+  const listEscrowVaultPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    listerPubKey.toBuffer(),
+    Buffer.from(longToByteArray(listId)),
+    Buffer.from("List"),
+    Buffer.from("Vault")
+  ], programId))[0];
+  const mintStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("Mint"),
+    Buffer.from("State")
+  ], programId))[0];
+  const metadataPubkey = (await PublicKey.findProgramAddress([
+    Buffer.from("metadata"),
+    METADATA_PROGRAM_ID.toBuffer(),
+    tokenMintPubKey.toBuffer()
+  ], METADATA_PROGRAM_ID))[0];
+  const traderStatePubkey = (await PublicKey.findProgramAddress([
+    listerPubKey.toBuffer(),
+    Buffer.from("Trader"),
+    Buffer.from("State")
+  ], programId))[0];
+  const orderBookPubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("OrderBook"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: bidderPubKey, isSigner: true, isWritable: true},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: false},
+      {pubkey: vaultAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: tokenProgramId, isSigner: false, isWritable: false},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
+      {pubkey: feeSchedulePubkey, isSigner: false, isWritable: false},
+      // One group of accounts per listing, in this order
+      {pubkey: tokenAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: tokenMintPubKey, isSigner: false, isWritable: false},
+      {pubkey: listerPubKey, isSigner: false, isWritable: true},
+      {pubkey: listEscrowStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: listEscrowVaultPubkey, isSigner: false, isWritable: true},
+      {pubkey: mintStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: metadataPubkey, isSigner: false, isWritable: false},
+      {pubkey: traderStatePubkey, isSigner: false, isWritable: false},
+      {pubkey: orderBookPubkey, isSigner: false, isWritable: true}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [bidderAccount],
+  );
+}
+
+const ED25519_PROGRAM_ID = new PublicKey('Ed25519SigVerify111111111111111111111111111');
+
+async function orderDelegateAddress(): Promise<PublicKey> {
+  return (await PublicKey.findProgramAddress([Buffer.from("Order"), Buffer.from("Delegate")], programId))[0];
+}
+
+/**
+ * Order nonce state of `owner`, holding the orders they cancelled
+ */
+async function orderNonceAddress(owner: PublicKey): Promise<PublicKey> {
+  return (await PublicKey.findProgramAddress([owner.toBuffer(), Buffer.from("OrderNonce"), Buffer.from("State")], programId))[0];
+}
+
+/**
+ * Message the test lister signs for the test order: one SOL, never expiring
+ */
+function signedOrderMessage(listerPubKey: PublicKey): Buffer {
+  return Buffer.concat([
+    Buffer.from("NFTMarketplaceOrder"),
+    programId.toBuffer(),
+    listerPubKey.toBuffer(),
+    tokenMintPubKey.toBuffer(),
+    Buffer.from(longToByteArray(LAMPORTS_PER_SOL)),
+    Buffer.from(longToByteArray(0)),
+    Buffer.from(longToByteArray(orderNonce))
+  ]);
+}
+
+/**
+ * Approves the program's order delegate for the lister's NFT, after which the lister can sell it through signed orders
+ */
+export async function approveOrderDelegate(): Promise<void> {
+  const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(listerKeyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const tokenProgramId = await getTokenProgramId(connection, tokenMintPubKey);
+  const listerTokenAccountPubKey = await findAssociatedTokenAddress(listerPubKey, tokenMintPubKey, tokenProgramId);
+
+  // The token program's Approve for a single token
+  const amount = Buffer.from(longToByteArray(1)).reverse();
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: listerTokenAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: await orderDelegateAddress(), isSigner: false, isWritable: false},
+      {pubkey: listerPubKey, isSigner: true, isWritable: false}
+    ],
+    programId: tokenProgramId,
+    data: Buffer.concat([Buffer.from([4]), amount]),
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [listerAccount],
+  );
+}
+
+/**
+ * Ed25519 program instruction verifying `signature` of `message` by `publicKey`, all three inlined in its data
+ */
+function ed25519Instruction(publicKey: PublicKey, signature: Uint8Array, message: Buffer): TransactionInstruction {
+  const publicKeyOffset = 16;
+  const signatureOffset = publicKeyOffset + 32;
+  const messageOffset = signatureOffset + 64;
+  const offsets = Buffer.alloc(16);
+  offsets.writeUInt8(1, 0);
+  offsets.writeUInt16LE(signatureOffset, 2);
+  offsets.writeUInt16LE(0xffff, 4);
+  offsets.writeUInt16LE(publicKeyOffset, 6);
+  offsets.writeUInt16LE(0xffff, 8);
+  offsets.writeUInt16LE(messageOffset, 10);
+  offsets.writeUInt16LE(message.length, 12);
+  offsets.writeUInt16LE(0xffff, 14);
+  return new TransactionInstruction({
+    keys: [],
+    programId: ED25519_PROGRAM_ID,
+    data: Buffer.concat([offsets, publicKey.toBuffer(), Buffer.from(signature), message]),
+  });
+}
+
+export async function executeSignedOrder(): Promise<void> {
+  const nonce = longToByteArray(orderNonce);
+  const list = [Buffer.from([37]), Buffer.from(longToByteArray(LAMPORTS_PER_SOL)), Buffer.from(longToByteArray(0)), Buffer.from(nonce)];
+  const buffer = Buffer.concat(list);
+
+  const bidderKeyPairPath = path.resolve(__dirname, '../../dist/bidder/bidder-keypair.json');
+  const bidderAccount = await readAccountFromFile(bidderKeyPairPath);
+  const bidderPubKey = bidderAccount.publicKey;
+
+  // The lister signs the order off-chain, the buyer submits it
+  const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(listerKeyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+  const message = signedOrderMessage(listerPubKey);
+  const signature = nacl.sign.detached(message, listerAccount.secretKey);
+
+  const tokenProgramId = await getTokenProgramId(connection, tokenMintPubKey);
+  const tokenAccountPubKey = await findAssociatedTokenAddress(bidderPubKey, tokenMintPubKey, tokenProgramId);
+  const listerTokenAccountPubKey = await findAssociatedTokenAddress(listerPubKey, tokenMintPubKey, tokenProgramId);
+
+  const noncePubkey = (await PublicKey.findProgramAddress([
+    listerPubKey.toBuffer(),
+    Buffer.from(nonce),
+    Buffer.from("Order"),
+    Buffer.from("Nonce")
+  ], programId))[0];
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+  const vaultAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("Vault")], programId))[0];
+  const feeSchedulePubkey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("FeeSchedule")], programId))[0];
+  const mintStatePubkey = (await PublicKey.findProgramAddress([
+    tokenMintPubKey.toBuffer(),
+    Buffer.from("Mint"),
+    Buffer.from("State")
+  ], programId))[0];
+  const metadataPubkey = (await PublicKey.findProgramAddress([
+    Buffer.from("metadata"),
+    METADATA_PROGRAM_ID.toBuffer(),
+    tokenMintPubKey.toBuffer()
+  ], METADATA_PROGRAM_ID))[0];
+  const traderStatePubkey = (await PublicKey.findProgramAddress([
+    listerPubKey.toBuffer(),
+    Buffer.from("Trader"),
+    Buffer.from("State")
+  ], programId))[0];
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: bidderPubKey, isSigner: true, isWritable: true},
+      {pubkey: tokenAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: tokenMintPubKey, isSigner: false, isWritable: false},
+      {pubkey: listerPubKey, isSigner: false, isWritable: true},
+      {pubkey: listerTokenAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: await orderDelegateAddress(), isSigner: false, isWritable: false},
+      {pubkey: noncePubkey, isSigner: false, isWritable: true},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: false},
+      {pubkey: vaultAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: tokenProgramId, isSigner: false, isWritable: false},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false},
+      {pubkey: SYSVAR_INSTRUCTIONS_PUBKEY, isSigner: false, isWritable: false},
+      {pubkey: mintStatePubkey, isSigner: false, isWritable: true},
+      {pubkey: feeSchedulePubkey, isSigner: false, isWritable: false},
+      {pubkey: metadataPubkey, isSigner: false, isWritable: false},
+      {pubkey: traderStatePubkey, isSigner: false, isWritable: false},
+      {pubkey: await orderNonceAddress(listerPubKey), isSigner: false, isWritable: false}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(ed25519Instruction(listerPubKey, signature, message), instruction),
+    [bidderAccount],
+  );
+}
+
+async function sendOrderNonceInstruction(buffer: Buffer): Promise<void> {
+  const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerAccount = await readAccountFromFile(listerKeyPairPath);
+  const listerPubKey = listerAccount.publicKey;
+
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: listerPubKey, isSigner: true, isWritable: true},
+      {pubkey: await orderNonceAddress(listerPubKey), isSigner: false, isWritable: true},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}
+    ],
+    programId,
+    data: buffer,
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [listerAccount],
+  );
+}
+
+/**
+ * Cancels every signed order and delegated listing of the test lister with a nonce or list id below the next one
+ */
+export async function incrementNonce(): Promise<void> {
+  const list = [Buffer.from([38]), Buffer.from(longToByteArray(orderNonce + 1))];
+  await sendOrderNonceInstruction(Buffer.concat(list));
+}
+
+/**
+ * Cancels the test lister's signed order by the hash of its message
+ */
+export async function cancelOrder(): Promise<void> {
+  const listerKeyPairPath = path.resolve(__dirname, '../../dist/lister/lister-keypair.json');
+  const listerPubKey = (await readAccountFromFile(listerKeyPairPath)).publicKey;
+
+  const orderHash = createHash('sha256').update(signedOrderMessage(listerPubKey)).digest();
+  await sendOrderNonceInstruction(Buffer.concat([Buffer.from([39]), orderHash]));
+}
+
+/**
+ * Upgrades the platform state written by an earlier version of the program to the current layout
+ */
+export async function migrateState(): Promise<void> {
+  const stateAccountPubKey = (await PublicKey.findProgramAddress([Buffer.from("Platform"), Buffer.from("State")], programId))[0];
+  const instruction = new TransactionInstruction({
+    keys: [
+      {pubkey: deployerAccount.publicKey, isSigner: true, isWritable: true},
+      {pubkey: stateAccountPubKey, isSigner: false, isWritable: true},
+      {pubkey: SystemProgram.programId, isSigner: false, isWritable: false}
+    ],
+    programId,
+    data: Buffer.from([40]),
+  });
+  console.log('Sending transaction for update')
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [deployerAccount],
+  );
+}
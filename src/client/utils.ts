@@ -101,12 +101,13 @@ export function longToByteArray (num: number): number[] {
 
 export async function findAssociatedTokenAddress(
   walletAddress: PublicKey,
-  tokenMintAddress: PublicKey
+  tokenMintAddress: PublicKey,
+  tokenProgramId: PublicKey = TOKEN_PROGRAM_ID
 ): Promise<PublicKey> {
   const assocAccounts = (await PublicKey.findProgramAddress(
       [
           walletAddress.toBuffer(),
-          TOKEN_PROGRAM_ID.toBuffer(),
+          tokenProgramId.toBuffer(),
           tokenMintAddress.toBuffer(),
       ],
       ASSOCIATED_TOKEN_PROGRAM_ID
@@ -116,3 +117,17 @@ export async function findAssociatedTokenAddress(
   }
   return assocAccounts[0]
 }
+
+/**
+ * Token program owning the mint, either the original token program or Token-2022
+ */
+export async function getTokenProgramId(
+  connection: Connection,
+  tokenMintAddress: PublicKey
+): Promise<PublicKey> {
+  const mintAccount = await connection.getAccountInfo(tokenMintAddress);
+  if (mintAccount === null) {
+    return Promise.reject("Mint account not found");
+  }
+  return mintAccount.owner;
+}
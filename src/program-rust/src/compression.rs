@@ -0,0 +1,122 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    instruction::{AccountMeta, Instruction},
+    keccak,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+};
+use std::slice::Iter;
+
+solana_program::declare_id!("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+
+pub mod account_compression {
+    solana_program::declare_id!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+}
+
+pub mod noop {
+    solana_program::declare_id!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+}
+
+// Anchor discriminator of Bubblegum's `transfer`, the first 8 bytes of sha256("global:transfer")
+const TRANSFER_DISCRIMINATOR: [u8; 8] = [163, 52, 200, 231, 140, 3, 69, 186];
+const LEAF_SCHEMA_V1: u8 = 1;
+
+pub fn find_asset_id(merkle_tree: &Pubkey, nonce: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"asset", merkle_tree.as_ref(), &nonce.to_le_bytes()], &id()).0
+}
+
+pub fn find_tree_authority(merkle_tree: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[merkle_tree.as_ref()], &id()).0
+}
+
+/// Accounts Bubblegum needs to move a leaf of `merkle_tree`, passed in this order
+pub struct TreeAccounts<'a, 'b> {
+    pub tree_authority: &'a AccountInfo<'b>,
+    pub merkle_tree: &'a AccountInfo<'b>,
+    pub log_wrapper: &'a AccountInfo<'b>,
+    pub compression_program: &'a AccountInfo<'b>,
+    pub bubblegum_program: &'a AccountInfo<'b>,
+    pub system_program: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> TreeAccounts<'a, 'b> {
+    pub fn next(account_info_iter: &mut Iter<'a, AccountInfo<'b>>) -> Result<Self, ProgramError> {
+        Ok(TreeAccounts {
+            tree_authority: next_account_info(account_info_iter)?,
+            merkle_tree: next_account_info(account_info_iter)?,
+            log_wrapper: next_account_info(account_info_iter)?,
+            compression_program: next_account_info(account_info_iter)?,
+            bubblegum_program: next_account_info(account_info_iter)?,
+            system_program: next_account_info(account_info_iter)?,
+        })
+    }
+
+    pub fn check(&self) -> Result<(), ProgramError> {
+        if !self.bubblegum_program.key.eq(&id()) || !self.compression_program.key.eq(&account_compression::id()) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !self.merkle_tree.owner.eq(&account_compression::id())
+            || !self.tree_authority.key.eq(&find_tree_authority(self.merkle_tree.key))
+            || !self.log_wrapper.key.eq(&noop::id())
+            || !self.system_program.key.eq(&system_program::id())
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+}
+
+/// Hash of a Bubblegum V1 leaf, the value stored in the Merkle tree for a compressed NFT
+pub fn hash_leaf(asset_id: &Pubkey, owner: &Pubkey, delegate: &Pubkey, nonce: u64, data_hash: &[u8; 32], creator_hash: &[u8; 32]) -> [u8; 32] {
+    keccak::hashv(&[
+        &[LEAF_SCHEMA_V1],
+        asset_id.as_ref(),
+        owner.as_ref(),
+        delegate.as_ref(),
+        &nonce.to_le_bytes(),
+        data_hash,
+        creator_hash,
+    ]).to_bytes()
+}
+
+/// Bubblegum `transfer` of the leaf at `index`, the proof nodes follow the fixed accounts
+#[allow(clippy::too_many_arguments)]
+pub fn transfer(
+    tree_authority: &Pubkey,
+    leaf_owner: &Pubkey,
+    leaf_delegate: &Pubkey,
+    new_leaf_owner: &Pubkey,
+    merkle_tree: &Pubkey,
+    root: &[u8; 32],
+    data_hash: &[u8; 32],
+    creator_hash: &[u8; 32],
+    nonce: u64,
+    index: u32,
+    proof: &[AccountInfo],
+) -> Instruction {
+    let mut data = TRANSFER_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(root);
+    data.extend_from_slice(data_hash);
+    data.extend_from_slice(creator_hash);
+    data.extend_from_slice(&nonce.to_le_bytes());
+    data.extend_from_slice(&index.to_le_bytes());
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*tree_authority, false),
+        AccountMeta::new_readonly(*leaf_owner, true),
+        AccountMeta::new_readonly(*leaf_delegate, false),
+        AccountMeta::new_readonly(*new_leaf_owner, false),
+        AccountMeta::new(*merkle_tree, false),
+        AccountMeta::new_readonly(noop::id(), false),
+        AccountMeta::new_readonly(account_compression::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(proof.iter().map(|node| AccountMeta::new_readonly(*node.key, false)));
+
+    Instruction {
+        program_id: id(),
+        accounts,
+        data,
+    }
+}
@@ -27,6 +27,83 @@ pub enum NFTError {
     /// Listing already fulfilled
     #[error("Listing already fulfilled")]
     ListingAlreadyFullfilled,
+
+    /// Listing reserved for another buyer
+    #[error("Listing reserved for another buyer")]
+    ListingReserved,
+
+    /// Invalid fee distribution
+    #[error("Fee distribution must name distinct recipients whose shares add up to 10000 bps")]
+    InvalidFeeDistribution,
+
+    /// Bid escrow does not cover the price plus the buyer fee
+    #[error("Bid escrow does not cover the price plus the buyer fee")]
+    BidDoesNotCoverFee,
+
+    /// Amount below the platform minimum
+    #[error("Amount below the platform minimum")]
+    AmountBelowMinimum,
+
+    /// Bid below the listing's minimum bid
+    #[error("Bid below the listing's minimum bid")]
+    BidBelowListingMinimum,
+
+    /// Mint uses a token extension that could move or lock the escrowed NFT
+    #[error("Mint uses an unsupported token extension")]
+    UnsupportedMintExtension,
+
+    /// Merkle proof does not lead from the escrowed leaf to the given root. No longer returned, Bubblegum checks
+    /// proofs against the tree itself; kept so the codes of the errors after it do not change
+    #[error("Invalid Merkle proof")]
+    InvalidMerkleProof,
+
+    /// Seller's token account no longer holds the NFT delegated to the listing
+    #[error("Listing delegation is stale")]
+    StaleDelegation,
+
+    /// Bid expired before it was accepted
+    #[error("Bid expired")]
+    BidExpired,
+
+    /// Bid amount is below the listing price
+    #[error("Bid does not cross the listing")]
+    OrdersDoNotCross,
+
+    /// Buying the next listing would take the sweep above its maximum total
+    #[error("Sweep total above its maximum")]
+    SweepLimitExceeded,
+
+    /// Listing or bid amount is not the price the taker expected
+    #[error("Price differs from the expected price")]
+    PriceMismatch,
+
+    /// Platform fee of the trade is above the taker's maximum
+    #[error("Platform fee above the maximum")]
+    FeeAboveMaximum,
+
+    /// No ed25519 instruction verifying the seller's signature of the order precedes the instruction
+    #[error("Invalid order signature")]
+    InvalidOrderSignature,
+
+    /// Signed order expired before it was executed
+    #[error("Order expired")]
+    OrderExpired,
+
+    /// Nonce of the signed order was already burned
+    #[error("Order nonce already used")]
+    NonceAlreadyUsed,
+
+    /// Owner cancelled the order, or raised their nonce floor above its nonce
+    #[error("Order cancelled")]
+    OrderCancelled,
+
+    /// New nonce floor is not above the current one
+    #[error("Nonce floor can only increase")]
+    NonceNotIncreased,
+
+    /// Referrer of a trade is one of its parties
+    #[error("Invalid referrer")]
+    InvalidReferrer,
 }
 
 impl From<NFTError> for ProgramError {
@@ -1,10 +1,11 @@
 use std::convert::{TryInto};
 use solana_program::{
+    hash::Hash,
     program_error::ProgramError,
     pubkey::Pubkey
 };
-use arrayref::{array_ref};
-use crate::error::NFTError;
+use arrayref::{array_ref, array_refs};
+use crate::{error::NFTError, state};
 
 #[repr(C)]
 #[derive(Debug, PartialEq)]
@@ -25,48 +26,261 @@ pub struct ChangeFee {
     pub platform_fee: u64
 }
 
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ChangeReferralFee {
+    pub referral_fee: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ChangeFeeBearer {
+    pub fee_bearer: state::FeeBearer
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ChangeMinimums {
+    pub min_bid_amount: u64,
+    pub min_list_amount: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ChangeBidBond {
+    pub bid_bond: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SlashBid {
+    pub bid_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ListCompressed {
+    pub amount: u64,
+    pub list_id: u64,
+    pub root: [u8; 32],
+    pub data_hash: [u8; 32],
+    pub creator_hash: [u8; 32],
+    pub nonce: u64,
+    pub index: u32
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct DeListCompressed {
+    pub list_id: u64,
+    pub root: [u8; 32]
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct AcceptCompressedListing {
+    pub list_id: u64,
+    pub root: [u8; 32]
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ListDelegated {
+    pub amount: u64,
+    pub list_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct DeListDelegated {
+    pub list_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct AcceptDelegatedListing {
+    pub list_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct MatchOrders {
+    pub bid_id: u64,
+    pub list_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SweepListings {
+    pub max_total: u64,
+    pub skip_unavailable: bool
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ExecuteSignedOrder {
+    pub price: u64,
+    pub expiry: i64,
+    pub nonce: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct IncrementNonce {
+    pub nonce: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct CancelOrder {
+    pub order_hash: Hash
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct MigrateState {}
+
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct List {
     pub amount: u64,
+    pub list_id: u64,
+    pub min_bid: u64,
+    pub reserved_buyer: Option<Pubkey>,
 }
 
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct DeList {
+    pub list_id: u64
 }
 
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct Bid {
-    pub amount: u64
+    pub amount: u64,
+    pub bid_id: u64,
+    pub expiry: i64
 }
 
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct WithdrawBid {
+    pub bid_id: u64
 }
 
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct AcceptBid {
+    pub bid_id: u64,
+    pub list_id: u64,
+    // Price the taker agreed to, and the most lamports of the platform fee they agreed to bear
+    pub slippage: Option<(u64, u64)>
 }
 
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct WithdrawNFTOnSuccess {
+    pub list_id: u64
 }
 
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct AcceptListing {
+    pub list_id: u64,
+    // Price the taker agreed to, and the most lamports of the platform fee they agreed to bear
+    pub slippage: Option<(u64, u64)>
 }
 
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub struct RefundUser {
+    pub bid_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct RefundListing {
+    pub list_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct BulkRefund {
+    pub compute_budget: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct CreateSwap {
+    pub swap_id: u64,
+    pub offered_lamports: u64,
+    pub requested_lamports: u64,
+    pub requested_mints: Vec<Pubkey>
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct AcceptSwap {
+    pub swap_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct CancelSwap {
+    pub swap_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ListBundle {
+    pub amount: u64,
+    pub bundle_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct DeListBundle {
+    pub bundle_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct AcceptBundleListing {
+    pub bundle_id: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetCollectionFee {
+    pub collection: Pubkey,
+    pub platform_fee: Option<u64>
 }
 
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetTierFee {
+    pub tier: u8,
+    pub platform_fee: u64
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetTraderTier {
+    pub trader: Pubkey,
+    pub tier: u8
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetFeeDistribution {
+    pub recipients: Vec<state::FeeRecipient>
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct DistributeFees {}
+
 #[repr(C)]
 #[derive(Debug, PartialEq)]
 pub enum NFTInstruction {
@@ -80,7 +294,37 @@ pub enum NFTInstruction {
     AcceptBid(AcceptBid),
     WithdrawNFTOnSuccess(WithdrawNFTOnSuccess),
     AcceptListing(AcceptListing),
-    RefundUser(RefundUser)
+    RefundUser(RefundUser),
+    RefundListing(RefundListing),
+    BulkRefund(BulkRefund),
+    CreateSwap(CreateSwap),
+    AcceptSwap(AcceptSwap),
+    CancelSwap(CancelSwap),
+    ListBundle(ListBundle),
+    DeListBundle(DeListBundle),
+    AcceptBundleListing(AcceptBundleListing),
+    SetCollectionFee(SetCollectionFee),
+    SetTierFee(SetTierFee),
+    SetTraderTier(SetTraderTier),
+    SetFeeDistribution(SetFeeDistribution),
+    DistributeFees(DistributeFees),
+    ChangeReferralFee(ChangeReferralFee),
+    ChangeFeeBearer(ChangeFeeBearer),
+    ChangeMinimums(ChangeMinimums),
+    ChangeBidBond(ChangeBidBond),
+    SlashBid(SlashBid),
+    ListCompressed(ListCompressed),
+    DeListCompressed(DeListCompressed),
+    AcceptCompressedListing(AcceptCompressedListing),
+    ListDelegated(ListDelegated),
+    DeListDelegated(DeListDelegated),
+    AcceptDelegatedListing(AcceptDelegatedListing),
+    MatchOrders(MatchOrders),
+    SweepListings(SweepListings),
+    ExecuteSignedOrder(ExecuteSignedOrder),
+    IncrementNonce(IncrementNonce),
+    CancelOrder(CancelOrder),
+    MigrateState(MigrateState)
 }
 
 impl NFTInstruction {
@@ -98,7 +342,7 @@ impl NFTInstruction {
                     let authority_bytes = array_ref![authority_bytes_slice, 0 ,32];
                     return Ok(Self::Initialize(Initialize{
                         authority: Pubkey::new_from_array(*authority_bytes),
-                        platform_fee: platform_fee,
+                        platform_fee,
                     }));
                 }
                 Err(NFTError::InvalidPlatformFee.into())
@@ -118,46 +362,429 @@ impl NFTInstruction {
                         platform_fee: Self::unpack_amount(rest)?,
                     }));
                 }
-                return Err(NFTError::InvalidInstructionData.into());
+                Err(NFTError::InvalidInstructionData.into())
             }
             3 => {
-                if rest.len() == 8usize {
+                if rest.len() == 24usize || rest.len() == 56usize {
+                    let (amount, rest) = rest.split_at(8);
+                    let (list_id, rest) = rest.split_at(8);
+                    let (min_bid, rest) = rest.split_at(8);
+                    let reserved_buyer = match rest.len() {
+                        32 => Some(Pubkey::new_from_array(*array_ref![rest, 0, 32])),
+                        _ => None,
+                    };
                     return Ok(Self::List(List{
-                        amount: Self::unpack_amount(rest)?,
+                        amount: Self::unpack_amount(amount)?,
+                        list_id: Self::unpack_amount(list_id)?,
+                        min_bid: Self::unpack_amount(min_bid)?,
+                        reserved_buyer,
                     }));
                 }
-                return Err(NFTError::InvalidInstructionData.into());
+                Err(NFTError::InvalidInstructionData.into())
             }
             4 => {
-                Ok(Self::DeList(DeList{}))
+                if rest.len() == 8usize {
+                    return Ok(Self::DeList(DeList{
+                        list_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
             }
             5 => {
-                if rest.len() == 8usize {
+                if rest.len() == 16usize || rest.len() == 24usize {
+                    let (amount, rest) = rest.split_at(8);
+                    let (bid_id, rest) = rest.split_at(8);
+                    let expiry = match rest.len() {
+                        8 => Self::unpack_amount(rest)? as i64,
+                        _ => 0,
+                    };
                     return Ok(Self::Bid(Bid{
-                        amount: Self::unpack_amount(rest)?,
+                        amount: Self::unpack_amount(amount)?,
+                        bid_id: Self::unpack_amount(bid_id)?,
+                        expiry,
                     }));
                 }
-                return Err(NFTError::InvalidInstructionData.into());
+                Err(NFTError::InvalidInstructionData.into())
             }
             6 => {
-                Ok(Self::WithdrawBid(WithdrawBid{}))
+                if rest.len() == 8usize {
+                    return Ok(Self::WithdrawBid(WithdrawBid{
+                        bid_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
             }
             7 => {
-                Ok(Self::AcceptBid(AcceptBid{}))
+                if rest.len() == 16usize || rest.len() == 32usize {
+                    let (bid_id, rest) = rest.split_at(8);
+                    let (list_id, rest) = rest.split_at(8);
+                    return Ok(Self::AcceptBid(AcceptBid{
+                        bid_id: Self::unpack_amount(bid_id)?,
+                        list_id: Self::unpack_amount(list_id)?,
+                        slippage: Self::unpack_slippage(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
             }
             8 => {
-                Ok(Self::WithdrawNFTOnSuccess(WithdrawNFTOnSuccess{}))
+                if rest.len() == 8usize {
+                    return Ok(Self::WithdrawNFTOnSuccess(WithdrawNFTOnSuccess{
+                        list_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
             }
             9 => {
-                Ok(Self::AcceptListing(AcceptListing{}))
+                if rest.len() == 8usize || rest.len() == 24usize {
+                    let (list_id, rest) = rest.split_at(8);
+                    return Ok(Self::AcceptListing(AcceptListing{
+                        list_id: Self::unpack_amount(list_id)?,
+                        slippage: Self::unpack_slippage(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
             }
             10 => {
-                Ok(Self::RefundUser(RefundUser{}))
+                if rest.len() == 8usize {
+                    return Ok(Self::RefundUser(RefundUser{
+                        bid_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            11 => {
+                if rest.len() == 8usize {
+                    return Ok(Self::RefundListing(RefundListing{
+                        list_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            12 => {
+                if rest.len() == 8usize {
+                    return Ok(Self::BulkRefund(BulkRefund{
+                        compute_budget: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            13 => {
+                if rest.len() < 25usize {
+                    return Err(NFTError::InvalidInstructionData.into());
+                }
+                let (swap_id, rest) = rest.split_at(8);
+                let (offered_lamports, rest) = rest.split_at(8);
+                let (requested_lamports, rest) = rest.split_at(8);
+                let (&requested_count, rest) = rest.split_first().ok_or(NFTError::InvalidInstructionData)?;
+                let requested_count = requested_count as usize;
+                if requested_count == 0 || requested_count > state::MAX_SWAP_MINTS || rest.len() != requested_count * 32 {
+                    return Err(NFTError::InvalidInstructionData.into());
+                }
+                Ok(Self::CreateSwap(CreateSwap{
+                    swap_id: Self::unpack_amount(swap_id)?,
+                    offered_lamports: Self::unpack_amount(offered_lamports)?,
+                    requested_lamports: Self::unpack_amount(requested_lamports)?,
+                    requested_mints: rest.chunks(32).map(|chunk| Pubkey::new_from_array(*array_ref![chunk, 0, 32])).collect(),
+                }))
+            }
+            14 => {
+                if rest.len() == 8usize {
+                    return Ok(Self::AcceptSwap(AcceptSwap{
+                        swap_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            15 => {
+                if rest.len() == 8usize {
+                    return Ok(Self::CancelSwap(CancelSwap{
+                        swap_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            16 => {
+                if rest.len() == 16usize {
+                    let (amount, bundle_id) = rest.split_at(8);
+                    return Ok(Self::ListBundle(ListBundle{
+                        amount: Self::unpack_amount(amount)?,
+                        bundle_id: Self::unpack_amount(bundle_id)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            17 => {
+                if rest.len() == 8usize {
+                    return Ok(Self::DeListBundle(DeListBundle{
+                        bundle_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            18 => {
+                if rest.len() == 8usize {
+                    return Ok(Self::AcceptBundleListing(AcceptBundleListing{
+                        bundle_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            19 => {
+                if rest.len() == 32usize || rest.len() == 40usize {
+                    let (collection, rest) = rest.split_at(32);
+                    let platform_fee = match rest.len() {
+                        8 => Some(Self::unpack_amount(rest)?),
+                        _ => None,
+                    };
+                    return Ok(Self::SetCollectionFee(SetCollectionFee{
+                        collection: Pubkey::new_from_array(*array_ref![collection, 0, 32]),
+                        platform_fee,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            20 => {
+                if rest.len() == 9usize {
+                    let (&tier, rest) = rest.split_first().ok_or(NFTError::InvalidInstructionData)?;
+                    if tier == 0 || tier as usize > state::MAX_FEE_TIERS {
+                        return Err(NFTError::InvalidInstructionData.into());
+                    }
+                    return Ok(Self::SetTierFee(SetTierFee{
+                        tier,
+                        platform_fee: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            21 => {
+                if rest.len() == 33usize {
+                    let (trader, tier) = rest.split_at(32);
+                    if tier[0] as usize > state::MAX_FEE_TIERS {
+                        return Err(NFTError::InvalidInstructionData.into());
+                    }
+                    return Ok(Self::SetTraderTier(SetTraderTier{
+                        trader: Pubkey::new_from_array(*array_ref![trader, 0, 32]),
+                        tier: tier[0],
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            22 => {
+                let (&recipient_count, rest) = rest.split_first().ok_or(NFTError::InvalidInstructionData)?;
+                let recipient_count = recipient_count as usize;
+                if recipient_count == 0 || recipient_count > state::MAX_FEE_RECIPIENTS || rest.len() != recipient_count * 34 {
+                    return Err(NFTError::InvalidInstructionData.into());
+                }
+                let recipients: Vec<state::FeeRecipient> = rest
+                    .chunks(34)
+                    .map(|chunk| state::FeeRecipient{
+                        recipient: Pubkey::new_from_array(*array_ref![chunk, 0, 32]),
+                        bps: u16::from_be_bytes(*array_ref![chunk, 32, 2]),
+                    })
+                    .collect();
+                let total_bps: u32 = recipients.iter().map(|recipient| recipient.bps as u32).sum();
+                if total_bps != state::BPS_DENOMINATOR as u32 {
+                    return Err(NFTError::InvalidFeeDistribution.into());
+                }
+                for (i, recipient) in recipients.iter().enumerate() {
+                    if recipients[..i].iter().any(|other| other.recipient.eq(&recipient.recipient)) {
+                        return Err(NFTError::InvalidFeeDistribution.into());
+                    }
+                }
+                Ok(Self::SetFeeDistribution(SetFeeDistribution{
+                    recipients,
+                }))
+            }
+            23 => {
+                if rest.is_empty() {
+                    return Ok(Self::DistributeFees(DistributeFees{}));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            24 => {
+                if rest.len() == 8usize {
+                    let referral_fee = Self::unpack_amount(rest)?;
+                    if referral_fee > state::FEE_DENOMINATOR {
+                        return Err(NFTError::InvalidPlatformFee.into());
+                    }
+                    return Ok(Self::ChangeReferralFee(ChangeReferralFee{
+                        referral_fee,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            25 => {
+                if rest.len() == 1usize {
+                    let fee_bearer = state::FeeBearer::from_u8(rest[0]).ok_or(NFTError::InvalidInstructionData)?;
+                    return Ok(Self::ChangeFeeBearer(ChangeFeeBearer{
+                        fee_bearer,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            26 => {
+                if rest.len() == 16usize {
+                    let (min_bid_amount, min_list_amount) = rest.split_at(8);
+                    return Ok(Self::ChangeMinimums(ChangeMinimums{
+                        min_bid_amount: Self::unpack_amount(min_bid_amount)?,
+                        min_list_amount: Self::unpack_amount(min_list_amount)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            27 => {
+                if rest.len() == 8usize {
+                    return Ok(Self::ChangeBidBond(ChangeBidBond{
+                        bid_bond: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            28 => {
+                if rest.len() == 8usize {
+                    return Ok(Self::SlashBid(SlashBid{
+                        bid_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            29 => {
+                if rest.len() == 124usize {
+                    let (amount, rest) = rest.split_at(8);
+                    let (list_id, rest) = rest.split_at(8);
+                    let (hashes, rest) = rest.split_at(96);
+                    let (nonce, index) = rest.split_at(8);
+                    let (root, data_hash, creator_hash) = array_refs![array_ref![hashes, 0, 96], 32, 32, 32];
+                    return Ok(Self::ListCompressed(ListCompressed{
+                        amount: Self::unpack_amount(amount)?,
+                        list_id: Self::unpack_amount(list_id)?,
+                        root: *root,
+                        data_hash: *data_hash,
+                        creator_hash: *creator_hash,
+                        nonce: Self::unpack_amount(nonce)?,
+                        index: u32::from_be_bytes(*array_ref![index, 0, 4]),
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            30 => {
+                if rest.len() == 40usize {
+                    let (list_id, root) = rest.split_at(8);
+                    return Ok(Self::DeListCompressed(DeListCompressed{
+                        list_id: Self::unpack_amount(list_id)?,
+                        root: *array_ref![root, 0, 32],
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            31 => {
+                if rest.len() == 40usize {
+                    let (list_id, root) = rest.split_at(8);
+                    return Ok(Self::AcceptCompressedListing(AcceptCompressedListing{
+                        list_id: Self::unpack_amount(list_id)?,
+                        root: *array_ref![root, 0, 32],
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            32 => {
+                if rest.len() == 16usize {
+                    let (amount, list_id) = rest.split_at(8);
+                    return Ok(Self::ListDelegated(ListDelegated{
+                        amount: Self::unpack_amount(amount)?,
+                        list_id: Self::unpack_amount(list_id)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            33 => {
+                if rest.len() == 8usize {
+                    return Ok(Self::DeListDelegated(DeListDelegated{
+                        list_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            34 => {
+                if rest.len() == 8usize {
+                    return Ok(Self::AcceptDelegatedListing(AcceptDelegatedListing{
+                        list_id: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            35 => {
+                if rest.len() == 16usize {
+                    let (bid_id, list_id) = rest.split_at(8);
+                    return Ok(Self::MatchOrders(MatchOrders{
+                        bid_id: Self::unpack_amount(bid_id)?,
+                        list_id: Self::unpack_amount(list_id)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            36 => {
+                if rest.len() == 9usize {
+                    let (max_total, skip_unavailable) = rest.split_at(8);
+                    let skip_unavailable = match skip_unavailable[0] {
+                        0 => false,
+                        1 => true,
+                        _ => return Err(NFTError::InvalidInstructionData.into()),
+                    };
+                    return Ok(Self::SweepListings(SweepListings{
+                        max_total: Self::unpack_amount(max_total)?,
+                        skip_unavailable,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            37 => {
+                if rest.len() == 24usize {
+                    let (price, rest) = rest.split_at(8);
+                    let (expiry, nonce) = rest.split_at(8);
+                    return Ok(Self::ExecuteSignedOrder(ExecuteSignedOrder{
+                        price: Self::unpack_amount(price)?,
+                        expiry: Self::unpack_amount(expiry)? as i64,
+                        nonce: Self::unpack_amount(nonce)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            38 => {
+                if rest.len() == 8usize {
+                    return Ok(Self::IncrementNonce(IncrementNonce{
+                        nonce: Self::unpack_amount(rest)?,
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            39 => {
+                if rest.len() == 32usize {
+                    return Ok(Self::CancelOrder(CancelOrder{
+                        order_hash: Hash::new_from_array(*array_ref![rest, 0, 32]),
+                    }));
+                }
+                Err(NFTError::InvalidInstructionData.into())
+            }
+            40 => {
+                if rest.is_empty() {
+                    return Ok(Self::MigrateState(MigrateState{}));
+                }
+                Err(NFTError::InvalidInstructionData.into())
             }
             _ => Err(NFTError::InvalidInstruction.into()),
         }
     }
 
+    /// The price and the most platform fee a taker agreed to, when the instruction data carries them
+    fn unpack_slippage(input: &[u8]) -> Result<Option<(u64, u64)>, ProgramError> {
+        if input.is_empty() {
+            return Ok(None);
+        }
+        let (expected_price, max_fee) = input.split_at(8);
+        Ok(Some((Self::unpack_amount(expected_price)?, Self::unpack_amount(max_fee)?)))
+    }
+
     fn unpack_amount(input: &[u8]) -> Result<u64, ProgramError> {
         let amount = input
             .get(..8)
@@ -1,9 +1,14 @@
 
+pub mod compression;
 pub mod error;
 pub mod instruction;
+pub mod metadata;
+pub mod order;
 pub mod processor;
 pub mod state;
+pub mod token;
 pub mod types;
+pub mod validation;
 
 use solana_program::{
     account_info::{AccountInfo},
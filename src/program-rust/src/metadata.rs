@@ -0,0 +1,270 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+
+use arrayref::array_ref;
+use std::slice::Iter;
+
+solana_program::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+const METADATA_KEY: u8 = 4;
+const MAX_CREATORS: usize = 5;
+
+pub const TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE: u8 = 4;
+pub const TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE_EDITION: u8 = 5;
+
+const FREEZE_DELEGATED_ACCOUNT_INSTRUCTION: u8 = 26;
+const THAW_DELEGATED_ACCOUNT_INSTRUCTION: u8 = 27;
+const TRANSFER_INSTRUCTION: u8 = 49;
+
+/// Number of trailing accounts an instruction takes to move a programmable NFT
+pub const PROGRAMMABLE_ACCOUNTS: usize = 10;
+
+pub fn find_metadata_address(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"metadata", id().as_ref(), mint.as_ref()], &id())
+}
+
+pub fn find_master_edition_address(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"metadata", id().as_ref(), mint.as_ref(), b"edition"], &id())
+}
+
+pub fn find_token_record_address(mint: &Pubkey, token_account: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"metadata", id().as_ref(), mint.as_ref(), b"token_record", token_account.as_ref()], &id())
+}
+
+/// Accounts the metadata program needs to move a programmable NFT, passed after an instruction's own accounts in
+/// this order. Programmable NFTs stay frozen in their token accounts and only move through the metadata program's
+/// `Transfer`, which keeps their token records and enforces the rule set
+pub struct ProgrammableAccounts<'a, 'b> {
+    pub metadata: &'a AccountInfo<'b>,
+    pub edition: &'a AccountInfo<'b>,
+    pub source_token_record: &'a AccountInfo<'b>,
+    pub destination_token_record: &'a AccountInfo<'b>,
+    pub system_program: &'a AccountInfo<'b>,
+    pub sysvar_instructions: &'a AccountInfo<'b>,
+    pub associated_token_program: &'a AccountInfo<'b>,
+    pub metadata_program: &'a AccountInfo<'b>,
+    pub authorization_rules_program: &'a AccountInfo<'b>,
+    pub authorization_rules: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> ProgrammableAccounts<'a, 'b> {
+    /// Takes the programmable NFT accounts when the instruction carries them, the plain token path is used otherwise
+    pub fn next(account_info_iter: &mut Iter<'a, AccountInfo<'b>>) -> Result<Option<Self>, ProgramError> {
+        if account_info_iter.as_slice().len() < PROGRAMMABLE_ACCOUNTS {
+            return Ok(None);
+        }
+        Ok(Some(ProgrammableAccounts {
+            metadata: next_account_info(account_info_iter)?,
+            edition: next_account_info(account_info_iter)?,
+            source_token_record: next_account_info(account_info_iter)?,
+            destination_token_record: next_account_info(account_info_iter)?,
+            system_program: next_account_info(account_info_iter)?,
+            sysvar_instructions: next_account_info(account_info_iter)?,
+            associated_token_program: next_account_info(account_info_iter)?,
+            metadata_program: next_account_info(account_info_iter)?,
+            authorization_rules_program: next_account_info(account_info_iter)?,
+            authorization_rules: next_account_info(account_info_iter)?,
+        }))
+    }
+
+    /// Checks that `mint` is a programmable NFT and that the accounts are the ones the metadata program derives for
+    /// moving it from `source` to `destination`. The rule set accounts are checked by the metadata program itself
+    pub fn check(&self, mint: &Pubkey, source: &Pubkey, destination: &Pubkey) -> Result<(), ProgramError> {
+        if !self.metadata_program.key.eq(&id()) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let metadata = Metadata::load(self.metadata, mint)?.ok_or(ProgramError::InvalidAccountData)?;
+        match metadata.token_standard {
+            Some(TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE) | Some(TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE_EDITION) => {}
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+        if !self.edition.key.eq(&find_master_edition_address(mint).0)
+            || !self.source_token_record.key.eq(&find_token_record_address(mint, source).0)
+            || !self.destination_token_record.key.eq(&find_token_record_address(mint, destination).0)
+            || !self.system_program.key.eq(&system_program::id())
+            || !self.sysvar_instructions.key.eq(&sysvar::instructions::id())
+            || !self.associated_token_program.key.eq(&spl_associated_token_account::id())
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+}
+
+/// The escrow of a programmable NFT is the associated token account of the escrow state, the only kind of
+/// destination the metadata program creates for a programmable NFT
+pub fn find_programmable_vault_address(escrow_state: &Pubkey, mint: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address(escrow_state, mint)
+}
+
+/// `FreezeDelegatedAccount`, which lets the delegate of a token account freeze it with the master edition as the
+/// mint's freeze authority
+pub fn freeze_delegated_account(delegate: &Pubkey, token_account: &Pubkey, mint: &Pubkey) -> Instruction {
+    delegated_account_instruction(FREEZE_DELEGATED_ACCOUNT_INSTRUCTION, delegate, token_account, mint)
+}
+
+pub fn thaw_delegated_account(delegate: &Pubkey, token_account: &Pubkey, mint: &Pubkey) -> Instruction {
+    delegated_account_instruction(THAW_DELEGATED_ACCOUNT_INSTRUCTION, delegate, token_account, mint)
+}
+
+fn delegated_account_instruction(instruction: u8, delegate: &Pubkey, token_account: &Pubkey, mint: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(*delegate, true),
+            AccountMeta::new(*token_account, false),
+            AccountMeta::new_readonly(find_master_edition_address(mint).0, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: vec![instruction],
+    }
+}
+
+/// `Transfer` of a single programmable NFT without authorization data
+#[allow(clippy::too_many_arguments)]
+pub fn transfer(
+    programmable_accounts: &ProgrammableAccounts,
+    token: &Pubkey,
+    token_owner: &Pubkey,
+    destination: &Pubkey,
+    destination_owner: &Pubkey,
+    mint: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    let mut data = vec![TRANSFER_INSTRUCTION, 0];
+    data.extend_from_slice(&1u64.to_le_bytes());
+    data.push(0);
+
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*token_owner, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*destination_owner, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(*programmable_accounts.metadata.key, false),
+            AccountMeta::new_readonly(*programmable_accounts.edition.key, false),
+            AccountMeta::new(*programmable_accounts.source_token_record.key, false),
+            AccountMeta::new(*programmable_accounts.destination_token_record.key, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new_readonly(*programmable_accounts.authorization_rules_program.key, false),
+            AccountMeta::new_readonly(*programmable_accounts.authorization_rules.key, false),
+        ],
+        data,
+    }
+}
+
+/// The fields of a Metaplex metadata account that the marketplace cares about
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub mint: Pubkey,
+    pub token_standard: Option<u8>,
+    pub verified_collection: Option<Pubkey>,
+}
+
+impl Metadata {
+    /// Reads the metadata of `mint`, returning `None` when the mint has no metadata account
+    pub fn load(metadata_account_info: &AccountInfo, mint: &Pubkey) -> Result<Option<Self>, ProgramError> {
+        let (metadata_account_pubkey, _) = find_metadata_address(mint);
+        if !metadata_account_info.key.eq(&metadata_account_pubkey) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if metadata_account_info.data_is_empty() {
+            return Ok(None);
+        }
+        if !metadata_account_info.owner.eq(&id()) {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        let metadata = Self::unpack(&metadata_account_info.data.borrow())?;
+        if !metadata.mint.eq(mint) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Some(metadata))
+    }
+
+    /// Walks the borsh layout of `Metadata` up to the collection field. Accounts written by older versions of the
+    /// metadata program stop before the optional trailing fields, which are then treated as absent
+    pub fn unpack(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut reader = Reader { src, offset: 0 };
+        if reader.read(1)?[0] != METADATA_KEY {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        reader.read(32)?;
+        let mint = Pubkey::new_from_array(*array_ref![reader.read(32)?, 0, 32]);
+        for _ in 0..3 {
+            let len = reader.read_u32()? as usize;
+            reader.read(len)?;
+        }
+        reader.read(2)?;
+        if reader.read(1)?[0] == 1 {
+            let creators = reader.read_u32()? as usize;
+            if creators > MAX_CREATORS {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            reader.read(creators * 34)?;
+        }
+        reader.read(2)?;
+
+        let mut metadata = Metadata {
+            mint,
+            ..Metadata::default()
+        };
+        if reader.read_option(1).is_none() {
+            return Ok(metadata);
+        }
+        metadata.token_standard = match reader.read_option(1) {
+            Some(Some(token_standard)) => Some(token_standard[0]),
+            Some(None) => None,
+            None => return Ok(metadata),
+        };
+        if let Some(Some(collection)) = reader.read_option(33) {
+            if collection[0] == 1 {
+                metadata.verified_collection = Some(Pubkey::new_from_array(*array_ref![collection, 1, 32]));
+            }
+        }
+        Ok(metadata)
+    }
+}
+
+struct Reader<'a> {
+    src: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, len: usize) -> Result<&'a [u8], ProgramError> {
+        let bytes = self.src
+            .get(self.offset..self.offset + len)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ProgramError> {
+        Ok(u32::from_le_bytes(*array_ref![self.read(4)?, 0, 4]))
+    }
+
+    /// Reads a borsh `Option` of `len` bytes. The outer `None` means the account data ended first
+    fn read_option(&mut self, len: usize) -> Option<Option<&'a [u8]>> {
+        match self.read(1).ok()?[0] {
+            1 => self.read(len).ok().map(Some),
+            _ => Some(None),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use solana_program::{
+    account_info::AccountInfo,
+    ed25519_program,
+    hash::{self, Hash},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::instructions,
+};
+use std::convert::TryInto;
+
+use crate::error::NFTError;
+
+// Prefix of every signed order message, so a seller's signature over an order can't be replayed as anything else
+const ORDER_DOMAIN: &[u8] = b"NFTMarketplaceOrder";
+
+// The ed25519 program's instruction data: a signature count and a padding byte, then for every signature the
+// offsets of the signature, public key and message, each with the index of the instruction holding them
+const SIGNATURE_OFFSETS_START: usize = 2;
+const SIGNATURE_OFFSETS_LEN: usize = 14;
+const SIGNATURE_LEN: usize = 64;
+// Instruction index the ed25519 program reads as "this instruction"
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Order a seller signs off-chain to sell `mint` at `price` without escrowing it. `nonce` is burned when the order
+/// settles, so every order of a seller needs its own
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SignedOrder {
+    pub seller: Pubkey,
+    pub mint: Pubkey,
+    pub price: u64,
+    // Unix timestamp from which the order can no longer be executed, zero when it never expires
+    pub expiry: i64,
+    pub nonce: u64,
+}
+
+impl SignedOrder {
+    /// Bytes the seller signs: the order domain, this program's id, then the order fields big-endian, the way
+    /// instruction data is laid out
+    pub fn message(&self, program_id: &Pubkey) -> Vec<u8> {
+        let mut message = ORDER_DOMAIN.to_vec();
+        message.extend_from_slice(program_id.as_ref());
+        message.extend_from_slice(self.seller.as_ref());
+        message.extend_from_slice(self.mint.as_ref());
+        message.extend_from_slice(&self.price.to_be_bytes());
+        message.extend_from_slice(&self.expiry.to_be_bytes());
+        message.extend_from_slice(&self.nonce.to_be_bytes());
+        message
+    }
+
+    /// Hash the seller cancels the order by
+    pub fn hash(&self, program_id: &Pubkey) -> Hash {
+        hash::hash(&self.message(program_id))
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expiry != 0 && now >= self.expiry
+    }
+}
+
+/// Hash a lister cancels a delegated listing by: its escrow state address, which is unique to the listing
+pub fn delegated_listing_hash(escrow_state: &Pubkey) -> Hash {
+    Hash::new_from_array(escrow_state.to_bytes())
+}
+
+/// The program's PDA sellers approve as delegate of the token account an order sells from
+pub fn find_delegate_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"Order", b"Delegate"], program_id)
+}
+
+/// Checks that the instruction right before the current one is an ed25519 program instruction verifying a single
+/// signature of `message` by `signer`. The ed25519 program fails the whole transaction on a bad signature, so
+/// finding the instruction is enough
+pub fn verify_signature(instructions_sysvar_info: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<(), ProgramError> {
+    let current_index = instructions::load_current_index_checked(instructions_sysvar_info)?;
+    if current_index == 0 {
+        return Err(NFTError::InvalidOrderSignature.into());
+    }
+    let ed25519_index = current_index - 1;
+    let instruction = instructions::load_instruction_at_checked(ed25519_index as usize, instructions_sysvar_info)?;
+    if !instruction.program_id.eq(&ed25519_program::id()) {
+        return Err(NFTError::InvalidOrderSignature.into());
+    }
+
+    let data = &instruction.data;
+    if data.len() < SIGNATURE_OFFSETS_START + SIGNATURE_OFFSETS_LEN || data[0] != 1 {
+        return Err(NFTError::InvalidOrderSignature.into());
+    }
+    let offsets: Vec<u16> = data[SIGNATURE_OFFSETS_START..SIGNATURE_OFFSETS_START + SIGNATURE_OFFSETS_LEN]
+        .chunks(2)
+        .map(|offset| u16::from_le_bytes(offset.try_into().unwrap()))
+        .collect();
+    let (signature_offset, public_key_offset, message_offset, message_len) = (offsets[0], offsets[2], offsets[4], offsets[5]);
+    // The signature, public key and message all have to come from the ed25519 instruction's own data
+    if [offsets[1], offsets[3], offsets[6]].iter().any(|index| *index != CURRENT_INSTRUCTION && *index != ed25519_index) {
+        return Err(NFTError::InvalidOrderSignature.into());
+    }
+
+    let slice = |offset: u16, len: usize| data.get(offset as usize..offset as usize + len);
+    if slice(signature_offset, SIGNATURE_LEN).is_none()
+        || slice(public_key_offset, 32) != Some(signer.as_ref())
+        || slice(message_offset, message_len as usize) != Some(message)
+    {
+        return Err(NFTError::InvalidOrderSignature.into());
+    }
+    Ok(())
+}
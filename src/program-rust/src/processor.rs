@@ -1,19 +1,64 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
+    hash::Hash,
+    instruction::Instruction,
     msg,
     pubkey::Pubkey,
     program::{invoke, invoke_signed},
     program_error::ProgramError,
-    program_pack::{IsInitialized, Pack},
+    program_option::COption,
+    program_pack::Pack,
     system_instruction,
     system_program,
     rent::Rent,
     sysvar::Sysvar,
 };
 use spl_token;
+use std::slice::Iter;
 
-use crate::{error::NFTError, instruction, instruction::NFTInstruction, state, state::{BidEscrowState, ListEscrowState, PlatformState}};
+use crate::{compression::{self, TreeAccounts}, error::NFTError, instruction, metadata::{self, Metadata, ProgrammableAccounts}, order::{self, SignedOrder}, token, validation, instruction::NFTInstruction, state, state::{AccountType, BidEscrowState, BundleEscrowState, CompressedListEscrowState, DelegatedListEscrowState, FeeDistributionState, FeeScheduleState, ListEscrowState, MintState, OrderBookBid, OrderBookState, OrderNonceState, PlatformState, SwapEscrowState, TraderState}};
+
+/// Accounts of one listing bought through `AcceptListing` or `SweepListings`. `SweepListings` takes them in this
+/// order for every listing it buys, followed by the programmable NFT accounts when the vault is the escrow state's
+/// associated token account
+struct ListingSale<'a, 'b> {
+    token_account: &'a AccountInfo<'b>,
+    mint: &'a AccountInfo<'b>,
+    lister: &'a AccountInfo<'b>,
+    escrow_state: &'a AccountInfo<'b>,
+    escrow_vault: &'a AccountInfo<'b>,
+    mint_state: &'a AccountInfo<'b>,
+    metadata: &'a AccountInfo<'b>,
+    trader_state: &'a AccountInfo<'b>,
+    order_book: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> ListingSale<'a, 'b> {
+    fn next(account_info_iter: &mut Iter<'a, AccountInfo<'b>>) -> Result<Self, ProgramError> {
+        Ok(ListingSale {
+            token_account: next_account_info(account_info_iter)?,
+            mint: next_account_info(account_info_iter)?,
+            lister: next_account_info(account_info_iter)?,
+            escrow_state: next_account_info(account_info_iter)?,
+            escrow_vault: next_account_info(account_info_iter)?,
+            mint_state: next_account_info(account_info_iter)?,
+            metadata: next_account_info(account_info_iter)?,
+            trader_state: next_account_info(account_info_iter)?,
+            order_book: next_account_info(account_info_iter)?,
+        })
+    }
+}
+
+/// An NFT moving out of `source`, a token account held by `owner`, into `destination`
+struct NftTransfer<'a, 'b> {
+    source: &'a AccountInfo<'b>,
+    owner: &'a AccountInfo<'b>,
+    destination: &'a AccountInfo<'b>,
+    mint: &'a AccountInfo<'b>,
+    token_program: &'a AccountInfo<'b>,
+}
 
 pub struct Processor;
 impl Processor {
@@ -33,37 +78,157 @@ impl Processor {
                 msg!("Instruction: Change Fee");
                 Self::process_change_fee(accounts, platform_fee, program_id)
             }
-            NFTInstruction::List(instruction::List{amount}) => {
+            NFTInstruction::List(instruction::List{amount, list_id, min_bid, reserved_buyer}) => {
                 msg!("Instruction: List");
-                Self::process_list(accounts, amount, program_id)
+                Self::process_list(accounts, amount, list_id, min_bid, reserved_buyer, program_id)
             }
-            NFTInstruction::DeList(instruction::DeList{}) => {
+            NFTInstruction::DeList(instruction::DeList{list_id}) => {
                 msg!("Instruction: Delist");
-                Self::process_delist(accounts, program_id)
+                Self::process_delist(accounts, list_id, program_id)
             }
-            NFTInstruction::Bid(instruction::Bid{amount}) => {
+            NFTInstruction::Bid(instruction::Bid{amount, bid_id, expiry}) => {
                 msg!("Instruction: Bid");
-                Self::process_bid(accounts, amount, program_id)
+                Self::process_bid(accounts, amount, bid_id, expiry, program_id)
             }
-            NFTInstruction::WithdrawBid(instruction::WithdrawBid{}) => {
+            NFTInstruction::WithdrawBid(instruction::WithdrawBid{bid_id}) => {
                 msg!("Instruction: WithdrawBid");
-                Self::process_withdraw_bid(accounts, program_id)
+                Self::process_withdraw_bid(accounts, bid_id, program_id)
             }
-            NFTInstruction::AcceptBid(instruction::AcceptBid{}) => {
+            NFTInstruction::AcceptBid(instruction::AcceptBid{bid_id, list_id, slippage}) => {
                 msg!("Instruction: AcceptBid");
-                Self::process_accept_bid(accounts, program_id)
+                Self::process_accept_bid(accounts, bid_id, list_id, slippage, program_id)
             }
-            NFTInstruction::WithdrawNFTOnSuccess(instruction::WithdrawNFTOnSuccess{}) => {
+            NFTInstruction::WithdrawNFTOnSuccess(instruction::WithdrawNFTOnSuccess{list_id}) => {
                 msg!("Instruction: WithdrawNFTOnSuccess");
-                Self::process_withdraw_nft_on_success(accounts, program_id)
+                Self::process_withdraw_nft_on_success(accounts, list_id, program_id)
             }
-            NFTInstruction::AcceptListing(instruction::AcceptListing{}) => {
+            NFTInstruction::AcceptListing(instruction::AcceptListing{list_id, slippage}) => {
                 msg!("Instruction: AcceptBid");
-                Self::process_accept_listing(accounts, program_id)
+                Self::process_accept_listing(accounts, list_id, slippage, program_id)
             }
-            NFTInstruction::RefundUser(instruction::RefundUser{}) => {
+            NFTInstruction::RefundUser(instruction::RefundUser{bid_id}) => {
                 msg!("Instruction: RefundUser");
-                Self::process_refund(accounts, program_id)
+                Self::process_refund(accounts, bid_id, program_id)
+            }
+            NFTInstruction::RefundListing(instruction::RefundListing{list_id}) => {
+                msg!("Instruction: RefundListing");
+                Self::process_refund_listing(accounts, list_id, program_id)
+            }
+            NFTInstruction::BulkRefund(instruction::BulkRefund{compute_budget}) => {
+                msg!("Instruction: BulkRefund");
+                Self::process_bulk_refund(accounts, compute_budget, program_id)
+            }
+            NFTInstruction::CreateSwap(instruction::CreateSwap{swap_id, offered_lamports, requested_lamports, requested_mints}) => {
+                msg!("Instruction: CreateSwap");
+                Self::process_create_swap(accounts, swap_id, offered_lamports, requested_lamports, &requested_mints, program_id)
+            }
+            NFTInstruction::AcceptSwap(instruction::AcceptSwap{swap_id}) => {
+                msg!("Instruction: AcceptSwap");
+                Self::process_accept_swap(accounts, swap_id, program_id)
+            }
+            NFTInstruction::CancelSwap(instruction::CancelSwap{swap_id}) => {
+                msg!("Instruction: CancelSwap");
+                Self::process_cancel_swap(accounts, swap_id, program_id)
+            }
+            NFTInstruction::ListBundle(instruction::ListBundle{amount, bundle_id}) => {
+                msg!("Instruction: ListBundle");
+                Self::process_list_bundle(accounts, amount, bundle_id, program_id)
+            }
+            NFTInstruction::DeListBundle(instruction::DeListBundle{bundle_id}) => {
+                msg!("Instruction: DeListBundle");
+                Self::process_delist_bundle(accounts, bundle_id, program_id)
+            }
+            NFTInstruction::AcceptBundleListing(instruction::AcceptBundleListing{bundle_id}) => {
+                msg!("Instruction: AcceptBundleListing");
+                Self::process_accept_bundle_listing(accounts, bundle_id, program_id)
+            }
+            NFTInstruction::SetCollectionFee(instruction::SetCollectionFee{collection, platform_fee}) => {
+                msg!("Instruction: SetCollectionFee");
+                Self::process_set_collection_fee(accounts, collection, platform_fee, program_id)
+            }
+            NFTInstruction::SetTierFee(instruction::SetTierFee{tier, platform_fee}) => {
+                msg!("Instruction: SetTierFee");
+                Self::process_set_tier_fee(accounts, tier, platform_fee, program_id)
+            }
+            NFTInstruction::SetTraderTier(instruction::SetTraderTier{trader, tier}) => {
+                msg!("Instruction: SetTraderTier");
+                Self::process_set_trader_tier(accounts, trader, tier, program_id)
+            }
+            NFTInstruction::SetFeeDistribution(instruction::SetFeeDistribution{recipients}) => {
+                msg!("Instruction: SetFeeDistribution");
+                Self::process_set_fee_distribution(accounts, recipients, program_id)
+            }
+            NFTInstruction::DistributeFees(instruction::DistributeFees{}) => {
+                msg!("Instruction: DistributeFees");
+                Self::process_distribute_fees(accounts, program_id)
+            }
+            NFTInstruction::ChangeReferralFee(instruction::ChangeReferralFee{referral_fee}) => {
+                msg!("Instruction: Change Referral Fee");
+                Self::process_change_referral_fee(accounts, referral_fee, program_id)
+            }
+            NFTInstruction::ChangeFeeBearer(instruction::ChangeFeeBearer{fee_bearer}) => {
+                msg!("Instruction: Change Fee Bearer");
+                Self::process_change_fee_bearer(accounts, fee_bearer, program_id)
+            }
+            NFTInstruction::ChangeMinimums(instruction::ChangeMinimums{min_bid_amount, min_list_amount}) => {
+                msg!("Instruction: Change Minimums");
+                Self::process_change_minimums(accounts, min_bid_amount, min_list_amount, program_id)
+            }
+            NFTInstruction::ChangeBidBond(instruction::ChangeBidBond{bid_bond}) => {
+                msg!("Instruction: Change Bid Bond");
+                Self::process_change_bid_bond(accounts, bid_bond, program_id)
+            }
+            NFTInstruction::SlashBid(instruction::SlashBid{bid_id}) => {
+                msg!("Instruction: SlashBid");
+                Self::process_slash_bid(accounts, bid_id, program_id)
+            }
+            NFTInstruction::ListCompressed(listing) => {
+                msg!("Instruction: ListCompressed");
+                Self::process_list_compressed(accounts, listing, program_id)
+            }
+            NFTInstruction::DeListCompressed(instruction::DeListCompressed{list_id, root}) => {
+                msg!("Instruction: DeListCompressed");
+                Self::process_delist_compressed(accounts, list_id, root, program_id)
+            }
+            NFTInstruction::AcceptCompressedListing(instruction::AcceptCompressedListing{list_id, root}) => {
+                msg!("Instruction: AcceptCompressedListing");
+                Self::process_accept_compressed_listing(accounts, list_id, root, program_id)
+            }
+            NFTInstruction::ListDelegated(instruction::ListDelegated{amount, list_id}) => {
+                msg!("Instruction: ListDelegated");
+                Self::process_list_delegated(accounts, amount, list_id, program_id)
+            }
+            NFTInstruction::DeListDelegated(instruction::DeListDelegated{list_id}) => {
+                msg!("Instruction: DeListDelegated");
+                Self::process_delist_delegated(accounts, list_id, program_id)
+            }
+            NFTInstruction::AcceptDelegatedListing(instruction::AcceptDelegatedListing{list_id}) => {
+                msg!("Instruction: AcceptDelegatedListing");
+                Self::process_accept_delegated_listing(accounts, list_id, program_id)
+            }
+            NFTInstruction::MatchOrders(instruction::MatchOrders{bid_id, list_id}) => {
+                msg!("Instruction: MatchOrders");
+                Self::process_match_orders(accounts, bid_id, list_id, program_id)
+            }
+            NFTInstruction::SweepListings(instruction::SweepListings{max_total, skip_unavailable}) => {
+                msg!("Instruction: SweepListings");
+                Self::process_sweep_listings(accounts, max_total, skip_unavailable, program_id)
+            }
+            NFTInstruction::ExecuteSignedOrder(instruction::ExecuteSignedOrder{price, expiry, nonce}) => {
+                msg!("Instruction: ExecuteSignedOrder");
+                Self::process_execute_signed_order(accounts, price, expiry, nonce, program_id)
+            }
+            NFTInstruction::IncrementNonce(instruction::IncrementNonce{nonce}) => {
+                msg!("Instruction: IncrementNonce");
+                Self::process_increment_nonce(accounts, nonce, program_id)
+            }
+            NFTInstruction::CancelOrder(instruction::CancelOrder{order_hash}) => {
+                msg!("Instruction: CancelOrder");
+                Self::process_cancel_order(accounts, order_hash, program_id)
+            }
+            NFTInstruction::MigrateState(instruction::MigrateState{}) => {
+                msg!("Instruction: MigrateState");
+                Self::process_migrate_state(accounts, program_id)
             }
         }
     }
@@ -148,7 +313,12 @@ impl Processor {
             PlatformState{
                 is_initialized: true,
                 authority: authority,
-                platform_fee: platform_fee
+                platform_fee,
+                referral_fee: 0,
+                fee_bearer: state::FeeBearer::Seller,
+                min_bid_amount: 0,
+                min_list_amount: 0,
+                bid_bond: 0
             }, 
             &mut state_account_info.data.borrow_mut()
         )?;
@@ -165,21 +335,7 @@ impl Processor {
         let initializer_info = next_account_info(account_info_iter)?;
         let state_account_info = next_account_info(account_info_iter)?;
 
-        if !initializer_info.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-        
-        let (state_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"State"], program_id);
-        let mut state_info = PlatformState::unpack_unchecked(&state_account_info.data.borrow())?;
-        if !(state_account_info.key.eq(&state_account_pubkey)) {
-            return Err(ProgramError::InvalidInstructionData);
-        }
-        if !state_info.is_initialized(){
-            return Err(ProgramError::UninitializedAccount);
-        }
-        if !state_info.authority.eq(initializer_info.key) {
-            return Err(NFTError::InvalidAuthority.into()); 
-        }
+        let mut state_info = Self::assert_platform_authority(initializer_info, state_account_info, program_id)?;
 
         state_info.authority = authority;
         PlatformState::pack(state_info, &mut state_account_info.data.borrow_mut())?;
@@ -196,21 +352,7 @@ impl Processor {
         let initializer_info = next_account_info(account_info_iter)?;
         let state_account_info = next_account_info(account_info_iter)?;
 
-        if !initializer_info.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-
-        let (state_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"State"], program_id);
-        let mut state_info = PlatformState::unpack_unchecked(&state_account_info.data.borrow())?;
-        if !(state_account_info.key.eq(&state_account_pubkey)) {
-            return Err(ProgramError::InvalidInstructionData);
-        }
-        if !state_info.is_initialized(){
-            return Err(ProgramError::UninitializedAccount);
-        }
-        if !state_info.authority.eq(initializer_info.key) {
-            return Err(NFTError::InvalidAuthority.into()); 
-        }
+        let mut state_info = Self::assert_platform_authority(initializer_info, state_account_info, program_id)?;
 
         state_info.platform_fee = platform_fee;
         PlatformState::pack(state_info, &mut state_account_info.data.borrow_mut())?;
@@ -218,9 +360,78 @@ impl Processor {
         Ok(())
     }
 
+    fn process_change_referral_fee(
+        accounts: &[AccountInfo],
+        referral_fee: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+
+        let mut state_info = Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
+        state_info.referral_fee = referral_fee;
+        PlatformState::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_change_fee_bearer(
+        accounts: &[AccountInfo],
+        fee_bearer: state::FeeBearer,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+
+        let mut state_info = Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
+        state_info.fee_bearer = fee_bearer;
+        PlatformState::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_change_minimums(
+        accounts: &[AccountInfo],
+        min_bid_amount: u64,
+        min_list_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+
+        let mut state_info = Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
+        state_info.min_bid_amount = min_bid_amount;
+        state_info.min_list_amount = min_list_amount;
+        PlatformState::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_change_bid_bond(
+        accounts: &[AccountInfo],
+        bid_bond: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+
+        let mut state_info = Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
+        state_info.bid_bond = bid_bond;
+        PlatformState::pack(state_info, &mut state_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
     fn process_list(
         accounts: &[AccountInfo],
         amount: u64,
+        list_id: u64,
+        min_bid: u64,
+        reserved_buyer: Option<Pubkey>,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -233,19 +444,29 @@ impl Processor {
         let token_program_info = next_account_info(account_info_iter)?;
         let system_program_info = next_account_info(account_info_iter)?;
         let rent_account_info = next_account_info(account_info_iter)?;
+        let mint_state_account_info = next_account_info(account_info_iter)?;
+        let platform_state_account_info = next_account_info(account_info_iter)?;
+        let order_book_account_info = next_account_info(account_info_iter)?;
+        let programmable_accounts = ProgrammableAccounts::next(account_info_iter)?;
 
         if !initializer_info.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        let token_account_data = spl_token::state::Account::unpack_unchecked(&token_account_info.data.borrow())?;
+        let platform_state = validation::load_platform_state(platform_state_account_info, program_id)?;
+        if amount < platform_state.min_list_amount {
+            return Err(NFTError::AmountBelowMinimum.into());
+        }
+
+        let token_account_data = token::unpack_account(token_account_info)?;
         if !(token_account_data.owner.eq(&initializer_info.key)) {
             return Err(ProgramError::InvalidAccountData);
         }
         
-        if !(mint_account_info.owner.eq(&spl_token::id())) {
+        if !(mint_account_info.owner.eq(token_program_info.key)) {
             return Err(ProgramError::InvalidAccountData);
         }
+        token::check_mint(mint_account_info)?;
 
         if !(token_account_data.mint.eq(&mint_account_info.key)) {
             return Err(ProgramError::InvalidAccountData);
@@ -255,7 +476,7 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
        
-        if !(spl_token::id().eq(token_program_info.key)) {
+        if !token::is_token_program(token_program_info.key) {
             return Err(ProgramError::InvalidAccountData);
         }
             
@@ -266,6 +487,7 @@ impl Processor {
         let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
             mint_account_info.key.as_ref(),
             initializer_info.key.as_ref(),
+            &list_id.to_be_bytes(),
             b"List",
             b"State"
             ],
@@ -293,6 +515,7 @@ impl Processor {
             &[&[
                 mint_account_info.key.as_ref(),
                 initializer_info.key.as_ref(),
+                &list_id.to_be_bytes()[..],
                 &b"List"[..],
                 &b"State"[..],
                 &[nonce1]
@@ -300,103 +523,125 @@ impl Processor {
         )?;
 
 
-        let (escrow_vault_account_pubkey, nonce2) = Pubkey::find_program_address(&[
-            mint_account_info.key.as_ref(),
-            initializer_info.key.as_ref(),
-            b"List",
-            b"Vault"
-            ],
-            program_id
-        );
-        if !(escrow_vault_account_info.key.eq(&escrow_vault_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
-        }
-        let required_balance = rent.minimum_balance(spl_token::state::Account::LEN);
-        let create_vault_account_ix = system_instruction::create_account(initializer_info.key, &escrow_vault_account_pubkey, required_balance, spl_token::state::Account::LEN as u64, &spl_token::id());
-        invoke_signed(
-            &create_vault_account_ix,
-            &[
-                initializer_info.clone(),
-                escrow_vault_account_info.clone(),
-                system_program_info.clone(),
-                token_program_info.clone(),
-            ],
-            &[&[
+        if let Some(programmable_accounts) = &programmable_accounts {
+            let escrow_vault_account_pubkey = metadata::find_programmable_vault_address(&escrow_state_account_pubkey, mint_account_info.key);
+            if !(escrow_vault_account_info.key.eq(&escrow_vault_account_pubkey)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            // The metadata program creates the vault as the escrow state's associated token account
+            Self::transfer_programmable(
+                programmable_accounts,
+                &NftTransfer {
+                    source: token_account_info,
+                    owner: initializer_info,
+                    destination: escrow_vault_account_info,
+                    mint: mint_account_info,
+                    token_program: token_program_info,
+                },
+                escrow_state_account_info,
+                initializer_info,
+                &[]
+            )?;
+        } else {
+            let (escrow_vault_account_pubkey, nonce2) = Pubkey::find_program_address(&[
                 mint_account_info.key.as_ref(),
                 initializer_info.key.as_ref(),
-                &b"List"[..],
-                &b"Vault"[..],
-                &[nonce2]
-            ]],
-        )?;
+                &list_id.to_be_bytes(),
+                b"List",
+                b"Vault"
+                ],
+                program_id
+            );
+            if !(escrow_vault_account_info.key.eq(&escrow_vault_account_pubkey)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let required_balance = rent.minimum_balance(spl_token::state::Account::LEN);
+            let create_vault_account_ix = system_instruction::create_account(initializer_info.key, &escrow_vault_account_pubkey, required_balance, spl_token::state::Account::LEN as u64, token_program_info.key);
+            invoke_signed(
+                &create_vault_account_ix,
+                &[
+                    initializer_info.clone(),
+                    escrow_vault_account_info.clone(),
+                    system_program_info.clone(),
+                    token_program_info.clone(),
+                ],
+                &[&[
+                    mint_account_info.key.as_ref(),
+                    initializer_info.key.as_ref(),
+                    &list_id.to_be_bytes()[..],
+                    &b"List"[..],
+                    &b"Vault"[..],
+                    &[nonce2]
+                ]],
+            )?;
 
-        let initialize_vault_account_ix = spl_token::instruction::initialize_account(
-            &spl_token::id(), 
-            &escrow_vault_account_pubkey, 
-            mint_account_info.key, 
-            &escrow_state_account_pubkey
-        )?;
-        invoke_signed(
-            &initialize_vault_account_ix,
-            &[
-                token_program_info.clone(),
-                escrow_vault_account_info.clone(),
-                escrow_state_account_info.clone(),
-                mint_account_info.clone(),
-                program_info.clone(),
-                rent_account_info.clone()
-            ],
-            &[&[
-                mint_account_info.key.as_ref(),
-                initializer_info.key.as_ref(),
-                &b"List"[..],
-                &b"Vault"[..],
-                &[nonce2]
-            ]],
-        )?;
+            let initialize_vault_account_ix = token::initialize_account(token_program_info.key, &escrow_vault_account_pubkey, mint_account_info.key, &escrow_state_account_pubkey);
+            invoke_signed(
+                &initialize_vault_account_ix,
+                &[
+                    token_program_info.clone(),
+                    escrow_vault_account_info.clone(),
+                    escrow_state_account_info.clone(),
+                    mint_account_info.clone(),
+                    program_info.clone(),
+                    rent_account_info.clone()
+                ],
+                &[&[
+                    mint_account_info.key.as_ref(),
+                    initializer_info.key.as_ref(),
+                    &list_id.to_be_bytes()[..],
+                    &b"List"[..],
+                    &b"Vault"[..],
+                    &[nonce2]
+                ]],
+            )?;
 
-        let transfer_token_ix = spl_token::instruction::transfer_checked(
-            &spl_token::id(),
-            token_account_info.key, 
-            mint_account_info.key, 
-            &escrow_vault_account_pubkey, 
-            initializer_info.key,
-            &[
-                initializer_info.key
-            ], 
-            1, 
-            0
-        )?;
+            let transfer_token_ix = token::transfer_checked(token_program_info.key, token_account_info.key, mint_account_info.key, &escrow_vault_account_pubkey, initializer_info.key);
 
-        invoke(
-            &transfer_token_ix,
-            &[
-                token_program_info.clone(),
-                token_account_info.clone(),
-                mint_account_info.clone(),
-                escrow_vault_account_info.clone(),
-                initializer_info.clone()
-            ],
-        )?;
+            invoke(
+                &transfer_token_ix,
+                &[
+                    token_program_info.clone(),
+                    token_account_info.clone(),
+                    mint_account_info.clone(),
+                    escrow_vault_account_info.clone(),
+                    initializer_info.clone()
+                ],
+            )?;
+        }
 
         ListEscrowState::pack(
             ListEscrowState{
+                is_initialized: true,
                 lister: *initializer_info.key,
                 amount: amount,
                 mint: *mint_account_info.key,
                 success: false,
-                successful_buyer: Pubkey::new_from_array([0; 32])
+                successful_buyer: Pubkey::new_from_array([0; 32]),
+                list_id,
+                reserved_buyer: reserved_buyer.unwrap_or_default(),
+                min_bid
             },
             &mut escrow_state_account_info.data.borrow_mut()
         )?;
 
-        msg!("{{action: \"List\", lister \"{}\",, amount: {}, mint: {}}}", initializer_info.key, amount, mint_account_info.key);
+        Self::create_mint_state(initializer_info, mint_state_account_info, mint_account_info.key, system_program_info, rent, program_id)?;
+
+        // Reserved listings are not open to the book, matchers skip them anyway
+        let mut order_book = Self::load_or_create_order_book(initializer_info, order_book_account_info, mint_account_info.key, system_program_info, rent, program_id)?;
+        if reserved_buyer.is_none() {
+            order_book.set_listing(initializer_info.key, list_id, amount);
+        }
+        OrderBookState::pack(order_book, &mut order_book_account_info.data.borrow_mut())?;
+
+        msg!("{{action: \"List\", lister \"{}\", list_id: {}, amount: {}, min_bid: {}, mint: {}, reserved: {}}}", initializer_info.key, list_id, amount, min_bid, mint_account_info.key, reserved_buyer.is_some());
 
         Ok(())
     }
 
     fn process_delist(
         accounts: &[AccountInfo],
+        list_id: u64,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -407,17 +652,19 @@ impl Processor {
         let escrow_vault_account_info = next_account_info(account_info_iter)?;
         let program_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
+        let order_book_account_info = next_account_info(account_info_iter)?;
+        let programmable_accounts = ProgrammableAccounts::next(account_info_iter)?;
 
         if !signer_info.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        let token_account_data = spl_token::state::Account::unpack_unchecked(&token_account_info.data.borrow())?;
+        let token_account_data = token::unpack_account(token_account_info)?;
         if !(token_account_data.owner.eq(&signer_info.key)) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        if !(mint_account_info.owner.eq(&spl_token::id())) {
+        if !(mint_account_info.owner.eq(token_program_info.key)) {
             return Err(ProgramError::InvalidAccountData);
         }
 
@@ -425,13 +672,14 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        if !(spl_token::id().eq(token_program_info.key)) {
+        if !token::is_token_program(token_program_info.key) {
             return Err(ProgramError::InvalidAccountData);
         }
 
         let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
             mint_account_info.key.as_ref(),
             signer_info.key.as_ref(),
+            &list_id.to_be_bytes(),
             b"List",
             b"State"
             ],
@@ -441,77 +689,54 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let (escrow_vault_account_pubkey, _) = Pubkey::find_program_address(&[
-            mint_account_info.key.as_ref(),
-            signer_info.key.as_ref(),
-            b"List",
-            b"Vault"
-            ],
+        let escrow_vault_account_pubkey = Self::find_list_vault_address(
+            mint_account_info.key,
+            signer_info.key,
+            list_id,
+            &escrow_state_account_pubkey,
+            programmable_accounts.is_some(),
             program_id
         );
         if !(escrow_vault_account_info.key.eq(&escrow_vault_account_pubkey)) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let token_tansfer_ix = spl_token::instruction::transfer(
-            &spl_token::id(), 
-            &escrow_vault_account_pubkey, 
-            token_account_info.key, 
-            &escrow_state_account_pubkey, 
-            &[&escrow_state_account_pubkey],
-            1
-        )?; 
-        invoke_signed(
-            &token_tansfer_ix,
-            &[
-                token_program_info.clone(),
-                escrow_vault_account_info.clone(),
-                token_account_info.clone(),
-                escrow_state_account_info.clone()
-            ],
-            &[&[
-                mint_account_info.key.as_ref(),
-                signer_info.key.as_ref(),
-                &b"List"[..],
-                &b"State"[..],
-                &[nonce1]
-            ]],
-        )?;
-
-        let close_ix = spl_token::instruction::close_account(
-            &spl_token::id(), 
-            &escrow_vault_account_pubkey, 
-            &escrow_state_account_pubkey, 
-            &escrow_state_account_pubkey, 
-            &[&escrow_state_account_pubkey]
-        )?;
+        // A sold listing's NFT belongs to the buyer, so nothing may move before the listing is known to be open
+        let list_state = validation::load_list_state(escrow_state_account_info, program_id)?;
+        if list_state.success {
+            return Err(NFTError::ListingAlreadyFullfilled.into());
+        }
 
-        invoke_signed(
-            &close_ix,
+        Self::release_list_escrow_vault(
+            programmable_accounts.as_ref(),
+            &NftTransfer {
+                source: escrow_vault_account_info,
+                owner: escrow_state_account_info,
+                destination: token_account_info,
+                mint: mint_account_info,
+                token_program: token_program_info,
+            },
+            signer_info,
+            signer_info,
             &[
-                token_program_info.clone(),
-                escrow_vault_account_info.clone(),
-                signer_info.clone(),
-                escrow_state_account_info.clone(),
-            ],
-            &[&[
                 mint_account_info.key.as_ref(),
                 signer_info.key.as_ref(),
+                &list_id.to_be_bytes()[..],
                 &b"List"[..],
                 &b"State"[..],
                 &[nonce1]
-            ]],
+            ]
         )?;
 
         let lamports = escrow_state_account_info.lamports();
         **escrow_state_account_info.try_borrow_mut_lamports()? = 0;
         **signer_info.try_borrow_mut_lamports()? += lamports;
 
-        let list_state = ListEscrowState::unpack_unchecked(&escrow_state_account_info.data.borrow())?;
-        if list_state.success {
-            return Err(NFTError::ListingAlreadyFullfilled.into());
-        }
-        msg!("{{action: \"DeList\", lister \"{}\", amount: {}, mint: {}}}", signer_info.key, list_state.amount, mint_account_info.key);
+        Self::update_order_book(order_book_account_info, mint_account_info.key, program_id, |order_book| {
+            order_book.remove_listing(signer_info.key, list_id)
+        })?;
+
+        msg!("{{action: \"DeList\", lister \"{}\", list_id: {}, amount: {}, mint: {}}}", signer_info.key, list_id, list_state.amount, mint_account_info.key);
 
         Ok(())
     }
@@ -519,6 +744,8 @@ impl Processor {
     fn process_bid(
         accounts: &[AccountInfo],
         amount: u64,
+        bid_id: u64,
+        expiry: i64,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -529,12 +756,14 @@ impl Processor {
         let program_info = next_account_info(account_info_iter)?;
         let system_program_info = next_account_info(account_info_iter)?;
         let rent_account_info = next_account_info(account_info_iter)?;
+        let platform_state_account_info = next_account_info(account_info_iter)?;
+        let order_book_account_info = next_account_info(account_info_iter)?;
 
         if !initializer_info.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
   
-        if !(mint_account_info.owner.eq(&spl_token::id())) {
+        if !token::is_token_program(mint_account_info.owner) {
             return Err(ProgramError::InvalidAccountData);
         }
 
@@ -547,9 +776,20 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }        
 
+        let platform_state = validation::load_platform_state(platform_state_account_info, program_id)?;
+        if amount < platform_state.min_bid_amount {
+            return Err(NFTError::AmountBelowMinimum.into());
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        if expiry != 0 && expiry <= now {
+            return Err(NFTError::BidExpired.into());
+        }
+
         let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
             mint_account_info.key.as_ref(),
             initializer_info.key.as_ref(),
+            &bid_id.to_be_bytes(),
             b"Bid",
             b"State"
             ],
@@ -578,6 +818,7 @@ impl Processor {
             &[&[
                 mint_account_info.key.as_ref(),
                 initializer_info.key.as_ref(),
+                &bid_id.to_be_bytes()[..],
                 &b"Bid"[..],
                 &b"State"[..],
                 &[nonce1]
@@ -587,6 +828,7 @@ impl Processor {
         let (escrow_vault_account_pubkey, nonce2) = Pubkey::find_program_address(&[
             mint_account_info.key.as_ref(),
             initializer_info.key.as_ref(),
+            &bid_id.to_be_bytes(),
             b"Bid",
             b"Vault"
             ],
@@ -614,13 +856,17 @@ impl Processor {
             &[&[
                 mint_account_info.key.as_ref(),
                 initializer_info.key.as_ref(),
+                &bid_id.to_be_bytes()[..],
                 &b"Bid"[..],
                 &b"Vault"[..],
                 &[nonce2]
             ]],
         )?;
 
-        let transfer_lamports_ix = system_instruction::transfer(initializer_info.key, &escrow_vault_account_pubkey, amount);
+        // When buyers bear part of the fee, the bid escrows it on top of the price. Settlement never charges the
+        // buyer more than this, even when the seller's collection or tier fee is higher
+        let (_, buyer_fee) = platform_state.fee_bearer.split(state::calculate_fee(amount, platform_state.platform_fee));
+        let transfer_lamports_ix = system_instruction::transfer(initializer_info.key, &escrow_vault_account_pubkey, amount + buyer_fee + platform_state.bid_bond);
         invoke(
             &transfer_lamports_ix,
             &[
@@ -631,20 +877,37 @@ impl Processor {
 
         BidEscrowState::pack(
             BidEscrowState{
+                is_initialized: true,
                 bidder: *initializer_info.key,
                 amount: amount,
-                mint: *mint_account_info.key
+                mint: *mint_account_info.key,
+                bid_id,
+                bond: platform_state.bid_bond,
+                expiry
             },
             &mut escrow_state_account_info.data.borrow_mut()
         )?;
 
-        msg!("{{action: \"Bid\", bidder: \"{}\", amount: {}, mint: \"{}\"}}", initializer_info.key, amount, mint_account_info.key);
+        let mut order_book = Self::load_or_create_order_book(initializer_info, order_book_account_info, mint_account_info.key, system_program_info, rent, program_id)?;
+        let indexed = order_book.insert_bid(
+            OrderBookBid{
+                bidder: *initializer_info.key,
+                bid_id,
+                amount,
+                expiry
+            },
+            now
+        );
+        OrderBookState::pack(order_book, &mut order_book_account_info.data.borrow_mut())?;
+
+        msg!("{{action: \"Bid\", bidder: \"{}\", bid_id: {}, amount: {}, buyer_fee: {}, bond: {}, expiry: {}, mint: \"{}\", indexed: {}}}", initializer_info.key, bid_id, amount, buyer_fee, platform_state.bid_bond, expiry, mint_account_info.key, indexed);
 
         Ok(())
     }
 
     fn process_withdraw_bid(
         accounts: &[AccountInfo],
+        bid_id: u64,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -653,12 +916,13 @@ impl Processor {
         let escrow_state_account_info = next_account_info(account_info_iter)?;
         let escrow_vault_account_info = next_account_info(account_info_iter)?;
         let program_info = next_account_info(account_info_iter)?;
+        let order_book_account_info = next_account_info(account_info_iter)?;
         
         if !signer_info.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        if !(mint_account_info.owner.eq(&spl_token::id())) {
+        if !token::is_token_program(mint_account_info.owner) {
             return Err(ProgramError::InvalidAccountData);
         }        
 
@@ -669,6 +933,7 @@ impl Processor {
         let (escrow_state_account_pubkey, _) = Pubkey::find_program_address(&[
             mint_account_info.key.as_ref(),
             signer_info.key.as_ref(),
+            &bid_id.to_be_bytes(),
             b"Bid",
             b"State"
             ],
@@ -681,6 +946,7 @@ impl Processor {
         let (escrow_vault_account_pubkey, _) = Pubkey::find_program_address(&[
             mint_account_info.key.as_ref(),
             signer_info.key.as_ref(),
+            &bid_id.to_be_bytes(),
             b"Bid",
             b"Vault"
             ],
@@ -690,19 +956,30 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
 
+        let bid_state = validation::load_bid_state(escrow_state_account_info, program_id)?;
+        if !bid_state.bidder.eq(signer_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
         let lamports = escrow_state_account_info.lamports() + escrow_vault_account_info.lamports();
         **escrow_state_account_info.try_borrow_mut_lamports()? = 0;
         **escrow_vault_account_info.try_borrow_mut_lamports()? = 0;
         **signer_info.try_borrow_mut_lamports()? += lamports;
 
-        let bid_state = BidEscrowState::unpack_unchecked(&escrow_state_account_info.data.borrow())?;
-        msg!("{{action: \"WithdrawBid\", bidder: \"{}\", amount: {}, mint: {}}}", signer_info.key, bid_state.amount, mint_account_info.key);
+        Self::update_order_book(order_book_account_info, mint_account_info.key, program_id, |order_book| {
+            order_book.remove_bid(signer_info.key, bid_id)
+        })?;
+
+        msg!("{{action: \"WithdrawBid\", bidder: \"{}\", bid_id: {}, amount: {}, mint: {}}}", signer_info.key, bid_id, bid_state.amount, mint_account_info.key);
 
         Ok(())
     }
 
     fn process_accept_bid(
         accounts: &[AccountInfo],
+        bid_id: u64,
+        list_id: u64,
+        slippage: Option<(u64, u64)>,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -715,19 +992,21 @@ impl Processor {
         let escrow_bid_vault_account_info = next_account_info(account_info_iter)?;
         let escrow_list_state_account_info = next_account_info(account_info_iter)?;
         let escrow_list_vault_account_info = next_account_info(account_info_iter)?;
+        let mint_state_account_info = next_account_info(account_info_iter)?;
+        let fee_schedule_account_info = next_account_info(account_info_iter)?;
+        let metadata_account_info = next_account_info(account_info_iter)?;
+        let trader_state_account_info = next_account_info(account_info_iter)?;
+        let order_book_account_info = next_account_info(account_info_iter)?;
+        let referrer_account_info = Self::next_referrer_account_info(account_info_iter, &[signer_info.key, bidder_account_info.key])?;
 
         if !signer_info.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        if !(mint_account_info.owner.eq(&spl_token::id())) {
+        if !token::is_token_program(mint_account_info.owner) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let (state_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"State"], program_id);
-        if !(state_account_info.key.eq(&state_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
-        }
         let (vault_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"Vault"], program_id);
         if !(vault_account_info.key.eq(&vault_account_pubkey)) {
             return Err(ProgramError::InvalidAccountData);
@@ -736,6 +1015,7 @@ impl Processor {
         let (escrow_bid_state_account_pubkey, _) = Pubkey::find_program_address(&[
             mint_account_info.key.as_ref(),
             bidder_account_info.key.as_ref(),
+            &bid_id.to_be_bytes(),
             b"Bid",
             b"State"
             ],
@@ -747,6 +1027,7 @@ impl Processor {
         let (escrow_bid_vault_account_pubkey, _) = Pubkey::find_program_address(&[
             mint_account_info.key.as_ref(),
             bidder_account_info.key.as_ref(),
+            &bid_id.to_be_bytes(),
             b"Bid",
             b"Vault"
             ],
@@ -759,6 +1040,7 @@ impl Processor {
         let (escrow_list_state_account_pubkey, _) = Pubkey::find_program_address(&[
             mint_account_info.key.as_ref(),
             signer_info.key.as_ref(),
+            &list_id.to_be_bytes(),
             b"List",
             b"State"
             ],
@@ -767,21 +1049,23 @@ impl Processor {
         if !(escrow_list_state_account_info.key.eq(&escrow_list_state_account_pubkey)) {
             return Err(ProgramError::InvalidAccountData);
         }
-        let (escrow_list_vault_account_pubkey, _) = Pubkey::find_program_address(&[
-            mint_account_info.key.as_ref(),
-            signer_info.key.as_ref(),
-            b"List",
-            b"Vault"
-            ],
+        // The NFT stays escrowed until `WithdrawNFTOnSuccess`, so a programmable listing is told by its vault alone
+        let programmable = escrow_list_vault_account_info.key.eq(&metadata::find_programmable_vault_address(escrow_list_state_account_info.key, mint_account_info.key));
+        let escrow_list_vault_account_pubkey = Self::find_list_vault_address(
+            mint_account_info.key,
+            signer_info.key,
+            list_id,
+            escrow_list_state_account_info.key,
+            programmable,
             program_id
         );
         if !(escrow_list_vault_account_info.key.eq(&escrow_list_vault_account_pubkey)) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let platform_state = PlatformState::unpack_unchecked(&state_account_info.data.borrow())?;
+        let platform_state = validation::load_platform_state(state_account_info, program_id)?;
 
-        let mut list_state = ListEscrowState::unpack_unchecked(&escrow_list_state_account_info.data.borrow())?;
+        let mut list_state = validation::load_list_state(escrow_list_state_account_info, program_id)?;
         if !list_state.lister.eq(signer_info.key) {
             return Err(ProgramError::InvalidAccountData);
         }
@@ -789,10 +1073,19 @@ impl Processor {
             return Err(NFTError::ListingAlreadyFullfilled.into());
         }
 
-        let bid_state = BidEscrowState::unpack_unchecked(&escrow_bid_state_account_info.data.borrow())?;
+        let bid_state = validation::load_bid_state(escrow_bid_state_account_info, program_id)?;
         if !bid_state.bidder.eq(bidder_account_info.key) {
             return Err(ProgramError::InvalidAccountData);
         }
+        if !list_state.can_be_bought_by(bidder_account_info.key) {
+            return Err(NFTError::ListingReserved.into());
+        }
+        if list_state.min_bid != 0 && bid_state.amount < list_state.min_bid {
+            return Err(NFTError::BidBelowListingMinimum.into());
+        }
+        if bid_state.is_expired(Clock::get()?.unix_timestamp) {
+            return Err(NFTError::BidExpired.into());
+        }
 
         list_state.amount = bid_state.amount;
         list_state.success = true;
@@ -803,23 +1096,46 @@ impl Processor {
             &mut escrow_list_state_account_info.data.borrow_mut()
         )?;
 
-        let fees_to_platform = (bid_state.amount * platform_state.platform_fee ) / 1000000000;
-        let amount_after_fees = bid_state.amount - fees_to_platform;
-        let lamports_to_bidder = escrow_bid_state_account_info.lamports() + escrow_bid_vault_account_info.lamports() - bid_state.amount;
+        let platform_fee = Self::resolve_platform_fee(
+            &platform_state,
+            fee_schedule_account_info,
+            Some(metadata_account_info),
+            trader_state_account_info,
+            mint_account_info.key,
+            signer_info.key,
+            program_id
+        )?;
+        let (seller_fee, buyer_fee) = platform_state.fee_bearer.split(state::calculate_fee(bid_state.amount, platform_fee));
+        Self::check_slippage(bid_state.amount, seller_fee, slippage)?;
+        let buyer_fee = Self::escrowed_buyer_fee(&bid_state, bid_state.amount, buyer_fee, escrow_bid_vault_account_info)?;
+        let fees_to_platform = seller_fee + buyer_fee;
+        let amount_after_fees = bid_state.amount - seller_fee;
+        let fees_to_referrer = Self::referral_share(&platform_state, fees_to_platform, referrer_account_info);
+
+        let lamports_to_bidder = escrow_bid_state_account_info.lamports() + escrow_bid_vault_account_info.lamports() - bid_state.amount - buyer_fee;
         **escrow_bid_state_account_info.try_borrow_mut_lamports()? = 0;
         **escrow_bid_vault_account_info.try_borrow_mut_lamports()? = 0;
-        **vault_account_info.try_borrow_mut_lamports()? += fees_to_platform;
+        **vault_account_info.try_borrow_mut_lamports()? += fees_to_platform - fees_to_referrer;
+        if let Some(referrer_account_info) = referrer_account_info {
+            **referrer_account_info.try_borrow_mut_lamports()? += fees_to_referrer;
+        }
         **signer_info.try_borrow_mut_lamports()? += amount_after_fees;
         **bidder_account_info.try_borrow_mut_lamports()? += lamports_to_bidder;
 
-        let log = format!("{{action: \"AcceptBid\", bidder: \"{}\", lister: \"{}\", amount: {}, mint: \"{}\"}}", bidder_account_info.key.to_string(), signer_info.key.to_string(), bid_state.amount, list_state.mint.to_string());
-        msg!(&log);
+        let sale_count = Self::record_sale(mint_state_account_info, mint_account_info.key, program_id)?;
+        Self::update_order_book(order_book_account_info, mint_account_info.key, program_id, |order_book| {
+            order_book.remove_bid(bidder_account_info.key, bid_id);
+            order_book.remove_listing(signer_info.key, list_id);
+        })?;
+
+        msg!("{{action: \"AcceptBid\", bidder: \"{}\", bid_id: {}, lister: \"{}\", list_id: {}, amount: {}, mint: \"{}\", sale_count: {}, platform_fee: {}, fee: {}, buyer_fee: {}, referrer: {}, referral_fee: {}}}", bidder_account_info.key, bid_id, signer_info.key, list_id, bid_state.amount, list_state.mint, sale_count, platform_fee, fees_to_platform, buyer_fee, Self::log_referrer(referrer_account_info), fees_to_referrer);
 
         Ok(())
     }
 
     fn process_withdraw_nft_on_success(
         accounts: &[AccountInfo],
+        list_id: u64,
         program_id: &Pubkey,
     ) -> ProgramResult{
         let account_info_iter = &mut accounts.iter();
@@ -830,17 +1146,18 @@ impl Processor {
         let escrow_list_state_account_info = next_account_info(account_info_iter)?;
         let escrow_list_vault_account_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
+        let programmable_accounts = ProgrammableAccounts::next(account_info_iter)?;
 
         if !signer_info.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        let token_account_data = spl_token::state::Account::unpack_unchecked(&token_account_info.data.borrow())?;
+        let token_account_data = token::unpack_account(token_account_info)?;
         if !(token_account_data.owner.eq(&signer_info.key)) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        if !(mint_account_info.owner.eq(&spl_token::id())) {
+        if !(mint_account_info.owner.eq(token_program_info.key)) {
             return Err(ProgramError::InvalidAccountData);
         }
 
@@ -851,6 +1168,7 @@ impl Processor {
         let (escrow_list_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
             mint_account_info.key.as_ref(),
             lister_account_info.key.as_ref(),
+            &list_id.to_be_bytes(),
             b"List",
             b"State"
             ],
@@ -859,23 +1177,23 @@ impl Processor {
         if !(escrow_list_state_account_info.key.eq(&escrow_list_state_account_pubkey)) {
             return Err(ProgramError::InvalidAccountData);
         }
-        let (escrow_list_vault_account_pubkey, _) = Pubkey::find_program_address(&[
-            mint_account_info.key.as_ref(),
-            lister_account_info.key.as_ref(),
-            b"List",
-            b"Vault"
-            ],
+        let escrow_list_vault_account_pubkey = Self::find_list_vault_address(
+            mint_account_info.key,
+            lister_account_info.key,
+            list_id,
+            &escrow_list_state_account_pubkey,
+            programmable_accounts.is_some(),
             program_id
         );
         if !(escrow_list_vault_account_info.key.eq(&escrow_list_vault_account_pubkey)) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        if !(spl_token::id().eq(token_program_info.key)) {
+        if !token::is_token_program(token_program_info.key) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let list_state = ListEscrowState::unpack_unchecked(&escrow_list_state_account_info.data.borrow())?;
+        let list_state = validation::load_list_state(escrow_list_state_account_info, program_id)?;
         if !list_state.lister.eq(lister_account_info.key) {
             return Err(ProgramError::InvalidAccountData);
         }
@@ -886,59 +1204,25 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let nft_transfer_ix = spl_token::instruction::transfer_checked(
-            &spl_token::id(),
-            &escrow_list_vault_account_pubkey,
-            mint_account_info.key,
-            token_account_info.key,
-            &escrow_list_state_account_pubkey,
-            &[&escrow_list_state_account_pubkey],
-            1,
-            0
-        )?;
-        
-        invoke_signed(
-            &nft_transfer_ix,
-            &[
-                token_program_info.clone(),
-                escrow_list_vault_account_info.clone(),
-                mint_account_info.clone(),
-                token_account_info.clone(),
-                escrow_list_state_account_info.clone(),
-            ],
-
-            &[&[
-                mint_account_info.key.as_ref(),
-                lister_account_info.key.as_ref(),
-                &b"List"[..],
-                &b"State"[..],
-                &[nonce1]
-            ]]
-        )?;
-
-        let close_ix = spl_token::instruction::close_account(
-            &spl_token::id(), 
-            &escrow_list_vault_account_pubkey, 
-            &escrow_list_state_account_pubkey, 
-            &escrow_list_state_account_pubkey, 
-            &[&escrow_list_state_account_pubkey]
-        )?;
-
-        invoke_signed(
-            &close_ix,
+        Self::release_list_escrow_vault(
+            programmable_accounts.as_ref(),
+            &NftTransfer {
+                source: escrow_list_vault_account_info,
+                owner: escrow_list_state_account_info,
+                destination: token_account_info,
+                mint: mint_account_info,
+                token_program: token_program_info,
+            },
+            signer_info,
+            signer_info,
             &[
-                token_program_info.clone(),
-                escrow_list_vault_account_info.clone(),
-                signer_info.clone(),
-                escrow_list_state_account_info.clone(),
-            ],
-            &[&[
                 mint_account_info.key.as_ref(),
                 lister_account_info.key.as_ref(),
+                &list_id.to_be_bytes()[..],
                 &b"List"[..],
                 &b"State"[..],
                 &[nonce1]
-            ]],
+            ]
         )?;
 
         let lamports = escrow_list_state_account_info.lamports();
@@ -950,6 +1234,8 @@ impl Processor {
 
     fn process_accept_listing(
         accounts: &[AccountInfo],
+        list_id: u64,
+        slippage: Option<(u64, u64)>,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -963,207 +1249,3460 @@ impl Processor {
         let escrow_list_vault_account_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
         let system_program_info = next_account_info(account_info_iter)?;
+        let mint_state_account_info = next_account_info(account_info_iter)?;
+        let fee_schedule_account_info = next_account_info(account_info_iter)?;
+        let metadata_account_info = next_account_info(account_info_iter)?;
+        let trader_state_account_info = next_account_info(account_info_iter)?;
+        let order_book_account_info = next_account_info(account_info_iter)?;
+        let programmable_accounts = ProgrammableAccounts::next(account_info_iter)?;
+        let referrer_account_info = Self::next_referrer_account_info(account_info_iter, &[signer_info.key, lister_account_info.key])?;
 
         if !signer_info.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        let token_account_data = spl_token::state::Account::unpack_unchecked(&token_account_info.data.borrow())?;
-        if !(token_account_data.owner.eq(&signer_info.key)) {
-            return Err(ProgramError::InvalidAccountData);
-        }
-        
-        if !(mint_account_info.owner.eq(&spl_token::id())) {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        let listing = ListingSale {
+            token_account: token_account_info,
+            mint: mint_account_info,
+            lister: lister_account_info,
+            escrow_state: escrow_list_state_account_info,
+            escrow_vault: escrow_list_vault_account_info,
+            mint_state: mint_state_account_info,
+            metadata: metadata_account_info,
+            trader_state: trader_state_account_info,
+            order_book: order_book_account_info,
+        };
+        let platform_state = validation::load_platform_state(state_account_info, program_id)?;
+        Self::check_sale_accounts(vault_account_info, token_program_info, system_program_info, program_id)?;
+        let (list_state, platform_fee, nonce) = Self::check_listing_sale(
+            signer_info,
+            &listing,
+            &platform_state,
+            fee_schedule_account_info,
+            token_program_info,
+            list_id,
+            programmable_accounts.is_some(),
+            program_id
+        )?;
+        let (_, buyer_fee) = platform_state.fee_bearer.split(state::calculate_fee(list_state.amount, platform_fee));
+        Self::check_slippage(list_state.amount, buyer_fee, slippage)?;
+        Self::settle_listing_sale(
+            signer_info,
+            &listing,
+            vault_account_info,
+            token_program_info,
+            system_program_info,
+            programmable_accounts.as_ref(),
+            referrer_account_info,
+            &platform_state,
+            list_state,
+            platform_fee,
+            nonce,
+            program_id
+        )?;
 
-        if !(token_account_data.mint.eq(&mint_account_info.key)) {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        Ok(())
+    }
 
-        let (state_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"State"], program_id);
-        if !(state_account_info.key.eq(&state_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
+    fn process_sweep_listings(
+        accounts: &[AccountInfo],
+        max_total: u64,
+        skip_unavailable: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let vault_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let fee_schedule_account_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
         }
 
-        let (vault_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"Vault"], program_id);
-        if !(vault_account_info.key.eq(&vault_account_pubkey)) {
-            return Err(ProgramError::InvalidAccountData);
+        let platform_state = validation::load_platform_state(state_account_info, program_id)?;
+        Self::check_sale_accounts(vault_account_info, token_program_info, system_program_info, program_id)?;
+
+        let mut total = 0u64;
+        let mut bought = 0u64;
+        let mut skipped = 0u64;
+        while !account_info_iter.as_slice().is_empty() {
+            let listing = ListingSale::next(account_info_iter)?;
+            let programmable = listing.escrow_vault.key.eq(&metadata::find_programmable_vault_address(listing.escrow_state.key, listing.mint.key));
+            let programmable_accounts = match programmable {
+                true => Some(ProgrammableAccounts::next(account_info_iter)?.ok_or(ProgramError::NotEnoughAccountKeys)?),
+                false => None,
+            };
+
+            // Nothing has moved for this listing until it is settled, so a listing that fails its checks or does
+            // not fit under the maximum can be skipped without undoing anything. Settling moves the NFT and the
+            // lamports, so a failure there fails the whole sweep even with `skip_unavailable` set
+            let checked = validation::load_list_state(listing.escrow_state, program_id).and_then(|list_state| {
+                Self::check_listing_sale(
+                    signer_info,
+                    &listing,
+                    &platform_state,
+                    fee_schedule_account_info,
+                    token_program_info,
+                    list_state.list_id,
+                    programmable,
+                    program_id
+                )
+            }).and_then(|(list_state, platform_fee, nonce)| {
+                let fees_to_platform = state::calculate_fee(list_state.amount, platform_fee);
+                let (_, buyer_fee) = platform_state.fee_bearer.split(fees_to_platform);
+                match total.checked_add(list_state.amount + buyer_fee) {
+                    Some(cost) if cost <= max_total => Ok((list_state, platform_fee, nonce)),
+                    _ => Err(NFTError::SweepLimitExceeded.into()),
+                }
+            });
+            let (list_state, platform_fee, nonce) = match checked {
+                Ok(checked) => checked,
+                Err(error) if skip_unavailable => {
+                    msg!("{{action: \"SweepSkip\", escrow: \"{}\", error: \"{}\"}}", listing.escrow_state.key, error);
+                    skipped += 1;
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+
+            total += Self::settle_listing_sale(
+                signer_info,
+                &listing,
+                vault_account_info,
+                token_program_info,
+                system_program_info,
+                programmable_accounts.as_ref(),
+                None,
+                &platform_state,
+                list_state,
+                platform_fee,
+                nonce,
+                program_id
+            )?;
+            bought += 1;
         }
 
-        let (escrow_list_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
-            mint_account_info.key.as_ref(),
-            lister_account_info.key.as_ref(),
-            b"List",
-            b"State"
-            ],
-            program_id
-        );
-        if !(escrow_list_state_account_info.key.eq(&escrow_list_state_account_pubkey)) {
+        msg!("{{action: \"SweepListings\", buyer: \"{}\", bought: {}, skipped: {}, total: {}, max_total: {}}}", signer_info.key, bought, skipped, total, max_total);
+
+        Ok(())
+    }
+
+    fn process_refund(
+        accounts: &[AccountInfo],
+        bid_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let mint_account_info = next_account_info(account_info_iter)?;
+        let bidder_account_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let escrow_bid_state_account_info = next_account_info(account_info_iter)?;
+        let escrow_bid_vault_account_info = next_account_info(account_info_iter)?;
+        let order_book_account_info = next_account_info(account_info_iter)?;
+
+        if !token::is_token_program(mint_account_info.owner) {
             return Err(ProgramError::InvalidAccountData);
         }
-        let (escrow_list_vault_account_pubkey, _) = Pubkey::find_program_address(&[
-            mint_account_info.key.as_ref(),
-            lister_account_info.key.as_ref(),
-            b"List",
-            b"Vault"
-            ],
+
+        Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
+
+        Self::refund_bid_escrow(
+            mint_account_info.key,
+            bid_id,
+            bidder_account_info,
+            escrow_bid_state_account_info,
+            escrow_bid_vault_account_info,
             program_id
-        );
-        if !(escrow_list_vault_account_info.key.eq(&escrow_list_vault_account_pubkey)) {
+        )?;
+        Self::update_order_book(order_book_account_info, mint_account_info.key, program_id, |order_book| {
+            order_book.remove_bid(bidder_account_info.key, bid_id)
+        })?;
+
+        Ok(())
+    }
+
+    /// Forfeits the bond of a spam bid to the platform vault and refunds the rest of the escrow to the bidder
+    fn process_slash_bid(
+        accounts: &[AccountInfo],
+        bid_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let mint_account_info = next_account_info(account_info_iter)?;
+        let bidder_account_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let vault_account_info = next_account_info(account_info_iter)?;
+        let escrow_bid_state_account_info = next_account_info(account_info_iter)?;
+        let escrow_bid_vault_account_info = next_account_info(account_info_iter)?;
+        let order_book_account_info = next_account_info(account_info_iter)?;
+
+        if !token::is_token_program(mint_account_info.owner) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        if !(spl_token::id().eq(token_program_info.key)) {
+        Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
+
+        let (vault_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"Vault"], program_id);
+        if !(vault_account_info.key.eq(&vault_account_pubkey)) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        if !(system_program_info.key.eq(&system_program::id())) {
+        let bid_state = validation::load_bid_state(escrow_bid_state_account_info, program_id)?;
+        if !bid_state.bidder.eq(bidder_account_info.key) || !bid_state.mint.eq(mint_account_info.key) || bid_state.bid_id != bid_id {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let nft_transfer_ix = spl_token::instruction::transfer_checked(
-            &spl_token::id(),
-            &escrow_list_vault_account_pubkey,
+        Self::assert_bid_escrow(
             mint_account_info.key,
-            token_account_info.key,
-            &escrow_list_state_account_pubkey,
-            &[&escrow_list_state_account_pubkey],
-            1,
-            0
+            bid_id,
+            bidder_account_info,
+            escrow_bid_state_account_info,
+            escrow_bid_vault_account_info,
+            program_id
         )?;
-        
-        invoke_signed(
-            &nft_transfer_ix,
-            &[
-                token_program_info.clone(),
-                escrow_list_vault_account_info.clone(),
-                mint_account_info.clone(),
-                token_account_info.clone(),
-                escrow_list_state_account_info.clone(),
-            ],
+        // The bond leaves the bid vault first, the rest of the escrow goes back to the bidder
+        **escrow_bid_vault_account_info.try_borrow_mut_lamports()? -= bid_state.bond;
+        **vault_account_info.try_borrow_mut_lamports()? += bid_state.bond;
+        Self::close_bid_escrow(bidder_account_info, escrow_bid_state_account_info, escrow_bid_vault_account_info)?;
+        Self::update_order_book(order_book_account_info, mint_account_info.key, program_id, |order_book| {
+            order_book.remove_bid(bidder_account_info.key, bid_id)
+        })?;
 
-            &[&[
-                mint_account_info.key.as_ref(),
-                lister_account_info.key.as_ref(),
-                &b"List"[..],
-                &b"State"[..],
-                &[nonce1]
-            ]]
-        )?;
+        msg!("{{action: \"SlashBid\", authority: \"{}\", bidder: \"{}\", bid_id: {}, bond: {}, mint: \"{}\"}}", signer_info.key, bidder_account_info.key, bid_id, bid_state.bond, mint_account_info.key);
 
-        let close_ix = spl_token::instruction::close_account(
-            &spl_token::id(), 
-            &escrow_list_vault_account_pubkey, 
-            &escrow_list_state_account_pubkey, 
-            &escrow_list_state_account_pubkey, 
-            &[&escrow_list_state_account_pubkey]
-        )?;
+        Ok(())
+    }
 
-        invoke_signed(
-            &close_ix,
-            &[
-                token_program_info.clone(),
-                escrow_list_vault_account_info.clone(),
-                signer_info.clone(),
-                escrow_list_state_account_info.clone(),
-            ],
-            &[&[
-                mint_account_info.key.as_ref(),
-                lister_account_info.key.as_ref(),
-                &b"List"[..],
-                &b"State"[..],
-                &[nonce1]
-            ]],
+    fn process_refund_listing(
+        accounts: &[AccountInfo],
+        list_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let mint_account_info = next_account_info(account_info_iter)?;
+        let lister_account_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let escrow_list_state_account_info = next_account_info(account_info_iter)?;
+        let escrow_list_vault_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let order_book_account_info = next_account_info(account_info_iter)?;
+        let programmable_accounts = ProgrammableAccounts::next(account_info_iter)?;
+
+        Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
+
+        let list_state = Self::refund_list_escrow(
+            list_id,
+            lister_account_info,
+            &NftTransfer {
+                source: escrow_list_vault_account_info,
+                owner: escrow_list_state_account_info,
+                destination: token_account_info,
+                mint: mint_account_info,
+                token_program: token_program_info,
+            },
+            programmable_accounts.as_ref(),
+            signer_info,
+            program_id
         )?;
+        Self::update_order_book(order_book_account_info, mint_account_info.key, program_id, |order_book| {
+            order_book.remove_listing(lister_account_info.key, list_id)
+        })?;
 
-        let platform_state = PlatformState::unpack_unchecked(&state_account_info.data.borrow())?;
+        msg!("{{action: \"RefundListing\", authority: \"{}\", lister: \"{}\", list_id: {}, amount: {}, mint: \"{}\"}}", signer_info.key, lister_account_info.key, list_state.list_id, list_state.amount, mint_account_info.key);
 
-        let mut list_state = ListEscrowState::unpack_unchecked(&escrow_list_state_account_info.data.borrow())?;
-        if !list_state.lister.eq(lister_account_info.key) {
-            return Err(ProgramError::InvalidAccountData);
-        }
-        if list_state.success {
-            return Err(NFTError::ListingAlreadyFullfilled.into());
-        }
+        Ok(())
+    }
+
+    fn process_bulk_refund(
+        accounts: &[AccountInfo],
+        compute_budget: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
 
-        let fees_to_platform = (list_state.amount * platform_state.platform_fee ) / 1000000000;
-        let amount_after_fees = list_state.amount - fees_to_platform;
+        Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
 
-        let transfer_lamports_lister_ix = system_instruction::transfer(signer_info.key, lister_account_info.key, amount_after_fees);
-        invoke(
-            &transfer_lamports_lister_ix,
-            &[
-                signer_info.clone(),
-                lister_account_info.clone(),
-                system_program_info.clone()
-            ]
-        )?;
-        let transfer_lamports_platform_ix = system_instruction::transfer(signer_info.key, &vault_account_pubkey, fees_to_platform);
-        invoke(
-            &transfer_lamports_platform_ix,
-            &[
-                signer_info.clone(),
-                vault_account_info.clone(),
-                system_program_info.clone()
-            ]
-        )?;
-        
+        let escrow_accounts = account_info_iter.as_slice();
+        let mut remaining_compute = if compute_budget == 0 {
+            state::DEFAULT_COMPUTE_BUDGET
+        } else {
+            compute_budget
+        }.saturating_sub(state::BULK_REFUND_BASE_COMPUTE);
 
-        list_state.success = true;
-        list_state.successful_buyer = *signer_info.key;
+        let mut processed = 0u64;
+        let mut position = 0usize;
+        while position < escrow_accounts.len() {
+            let escrow_state_account_info = &escrow_accounts[position];
+            if !escrow_state_account_info.owner.eq(program_id) {
+                return Err(ProgramError::IncorrectProgramId);
+            }
 
-        ListEscrowState::pack(
-            list_state,
-            &mut escrow_list_state_account_info.data.borrow_mut()
-        )?;
+            let account_type = escrow_state_account_info.try_borrow_data()?.first().copied().and_then(AccountType::from_u8);
+            if account_type == Some(AccountType::BidEscrow) {
+                if remaining_compute < state::BID_REFUND_COMPUTE {
+                    break;
+                }
+                let group = escrow_accounts.get(position..position + 4).ok_or(ProgramError::NotEnoughAccountKeys)?;
+                let (escrow_bid_vault_account_info, bidder_account_info, order_book_account_info) = (&group[1], &group[2], &group[3]);
 
-        let lamports = escrow_list_state_account_info.lamports();
-        **escrow_list_state_account_info.try_borrow_mut_lamports()? = 0;
-        **lister_account_info.try_borrow_mut_lamports()? += lamports;
+                let bid_state = validation::load_bid_state(escrow_state_account_info, program_id)?;
+                if !bid_state.bidder.eq(bidder_account_info.key) {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                Self::refund_bid_escrow(
+                    &bid_state.mint,
+                    bid_state.bid_id,
+                    bidder_account_info,
+                    escrow_state_account_info,
+                    escrow_bid_vault_account_info,
+                    program_id
+                )?;
+                Self::update_order_book(order_book_account_info, &bid_state.mint, program_id, |order_book| {
+                    order_book.remove_bid(bidder_account_info.key, bid_state.bid_id)
+                })?;
+
+                msg!("{{action: \"RefundUser\", authority: \"{}\", bidder: \"{}\", bid_id: {}, amount: {}, mint: \"{}\"}}", signer_info.key, bidder_account_info.key, bid_state.bid_id, bid_state.amount, bid_state.mint);
+
+                remaining_compute -= state::BID_REFUND_COMPUTE;
+                position += 4;
+            } else if account_type == Some(AccountType::ListEscrow) {
+                let group = escrow_accounts.get(position..position + 6).ok_or(ProgramError::NotEnoughAccountKeys)?;
+                let (
+                    escrow_list_vault_account_info,
+                    lister_account_info,
+                    token_account_info,
+                    mint_account_info,
+                    order_book_account_info
+                ) = (&group[1], &group[2], &group[3], &group[4], &group[5]);
+
+                // A programmable NFT is escrowed in the state's associated token account and its accounts follow
+                let programmable = escrow_list_vault_account_info.key.eq(&metadata::find_programmable_vault_address(escrow_state_account_info.key, mint_account_info.key));
+                let (refund_compute, group_len) = match programmable {
+                    true => (state::PROGRAMMABLE_LIST_REFUND_COMPUTE, 6 + metadata::PROGRAMMABLE_ACCOUNTS),
+                    false => (state::LIST_REFUND_COMPUTE, 6),
+                };
+                if remaining_compute < refund_compute {
+                    break;
+                }
+                let programmable_accounts = match programmable {
+                    true => Some(
+                        ProgrammableAccounts::next(&mut escrow_accounts[position + 6..].iter())?
+                            .ok_or(ProgramError::NotEnoughAccountKeys)?
+                    ),
+                    false => None,
+                };
 
-        let log = format!("{{action: \"AcceptListing\", bidder: \"{}\", lister: \"{}\", amount: {}, mint: \"{}\"}}", signer_info.key.to_string(), lister_account_info.key.to_string(), list_state.amount, mint_account_info.key.to_string());
-        msg!(&log);
+                let list_state = validation::load_list_state(escrow_state_account_info, program_id)?;
+                let list_state = Self::refund_list_escrow(
+                    list_state.list_id,
+                    lister_account_info,
+                    &NftTransfer {
+                        source: escrow_list_vault_account_info,
+                        owner: escrow_state_account_info,
+                        destination: token_account_info,
+                        mint: mint_account_info,
+                        token_program: token_program_info,
+                    },
+                    programmable_accounts.as_ref(),
+                    signer_info,
+                    program_id
+                )?;
+                Self::update_order_book(order_book_account_info, mint_account_info.key, program_id, |order_book| {
+                    order_book.remove_listing(lister_account_info.key, list_state.list_id)
+                })?;
+
+                msg!("{{action: \"RefundListing\", authority: \"{}\", lister: \"{}\", list_id: {}, amount: {}, mint: \"{}\"}}", signer_info.key, lister_account_info.key, list_state.list_id, list_state.amount, mint_account_info.key);
+
+                remaining_compute -= refund_compute;
+                position += group_len;
+            } else {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            processed += 1;
+        }
+
+        // `next` is the offset into the escrow accounts that a follow-up BulkRefund should resume from
+        msg!("{{action: \"BulkRefund\", processed: {}, next: {}, total: {}}}", processed, position, escrow_accounts.len());
 
         Ok(())
     }
 
-    fn process_refund(
+    fn process_create_swap(
         accounts: &[AccountInfo],
+        swap_id: u64,
+        offered_lamports: u64,
+        requested_lamports: u64,
+        requested_mints: &[Pubkey],
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let signer_info = next_account_info(account_info_iter)?;
-        let mint_account_info = next_account_info(account_info_iter)?;
-        let bidder_account_info = next_account_info(account_info_iter)?;
-        let state_account_info = next_account_info(account_info_iter)?;
-        let escrow_bid_state_account_info = next_account_info(account_info_iter)?;
-        let escrow_bid_vault_account_info = next_account_info(account_info_iter)?;
+        let initializer_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_account_info = next_account_info(account_info_iter)?;
 
-        if !signer_info.is_signer {
+        if !initializer_info.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        if !(mint_account_info.owner.eq(&spl_token::id())) {
+        if !(program_info.key.eq(program_id)) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        let (state_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"State"], program_id);
-        if !(state_account_info.key.eq(&state_account_pubkey)) {
+        if !token::is_token_program(token_program_info.key) {
             return Err(ProgramError::InvalidAccountData);
         }
-        let state_info = PlatformState::unpack_unchecked(&state_account_info.data.borrow())?;
-        if !state_info.is_initialized(){
-            return Err(ProgramError::UninitializedAccount);
+
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
         }
-        if !state_info.authority.eq(signer_info.key) {
-            return Err(NFTError::InvalidAuthority.into()); 
+
+        // Every offered NFT is passed as (offerer token account, mint, vault)
+        let offered_accounts = account_info_iter.as_slice();
+        if offered_accounts.is_empty() || !offered_accounts.len().is_multiple_of(3) || offered_accounts.len() / 3 > state::MAX_SWAP_MINTS {
+            return Err(ProgramError::NotEnoughAccountKeys);
         }
 
-        let (escrow_bid_state_account_pubkey, _) = Pubkey::find_program_address(&[
-            mint_account_info.key.as_ref(),
-            bidder_account_info.key.as_ref(),
-            b"Bid",
+        let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            initializer_info.key.as_ref(),
+            &swap_id.to_be_bytes(),
+            b"Swap",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_state_account_info.key.eq(&escrow_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Offered lamports are held by the state account on top of its rent exempt balance
+        let rent = &Rent::from_account_info(rent_account_info)?;
+        let required_balance = rent.minimum_balance(state::SWAPESCROWSTATE) + offered_lamports;
+        let create_state_account_ix = system_instruction::create_account(
+            initializer_info.key, 
+            &escrow_state_account_pubkey, 
+            required_balance, 
+            state::SWAPESCROWSTATE as u64, 
+            program_id
+        );
+        invoke_signed(
+            &create_state_account_ix,
+            &[
+                initializer_info.clone(),
+                escrow_state_account_info.clone(),
+                system_program_info.clone(),
+                program_info.clone(),
+            ],
+            &[&[
+                initializer_info.key.as_ref(),
+                &swap_id.to_be_bytes()[..],
+                &b"Swap"[..],
+                &b"State"[..],
+                &[nonce1]
+            ]],
+        )?;
+
+        let mut swap_state = SwapEscrowState{
+            is_initialized: true,
+            offerer: *initializer_info.key,
+            swap_id,
+            offered_lamports,
+            requested_lamports,
+            offered_count: 0,
+            offered_mints: [Pubkey::default(); state::MAX_SWAP_MINTS],
+            requested_count: requested_mints.len() as u8,
+            requested_mints: [Pubkey::default(); state::MAX_SWAP_MINTS],
+        };
+        swap_state.requested_mints[..requested_mints.len()].copy_from_slice(requested_mints);
+
+        for offered in offered_accounts.chunks(3) {
+            let (token_account_info, mint_account_info, escrow_vault_account_info) = (&offered[0], &offered[1], &offered[2]);
+
+            if !(mint_account_info.owner.eq(token_program_info.key)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if swap_state.offered().contains(mint_account_info.key) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let token_account_data = token::unpack_account(token_account_info)?;
+            if !(token_account_data.owner.eq(initializer_info.key)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if !(token_account_data.mint.eq(mint_account_info.key)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let (escrow_vault_account_pubkey, nonce2) = Pubkey::find_program_address(&[
+                escrow_state_account_pubkey.as_ref(),
+                mint_account_info.key.as_ref(),
+                b"Swap",
+                b"Vault"
+                ],
+                program_id
+            );
+            if !(escrow_vault_account_info.key.eq(&escrow_vault_account_pubkey)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            Self::create_escrow_vault(
+                &NftTransfer {
+                    source: token_account_info,
+                    owner: initializer_info,
+                    destination: escrow_vault_account_info,
+                    mint: mint_account_info,
+                    token_program: token_program_info,
+                },
+                &escrow_state_account_pubkey,
+                &[
+                    escrow_state_account_pubkey.as_ref(),
+                    mint_account_info.key.as_ref(),
+                    &b"Swap"[..],
+                    &b"Vault"[..],
+                    &[nonce2]
+                ],
+                system_program_info,
+                rent_account_info,
+                rent
+            )?;
+
+            swap_state.offered_mints[swap_state.offered_count as usize] = *mint_account_info.key;
+            swap_state.offered_count += 1;
+        }
+
+        SwapEscrowState::pack(swap_state, &mut escrow_state_account_info.data.borrow_mut())?;
+
+        msg!("{{action: \"CreateSwap\", offerer: \"{}\", swap_id: {}, offered: {:?}, offered_lamports: {}, requested: {:?}, requested_lamports: {}}}", initializer_info.key, swap_id, swap_state.offered().iter().map(|mint| mint.to_string()).collect::<Vec<_>>(), offered_lamports, swap_state.requested().iter().map(|mint| mint.to_string()).collect::<Vec<_>>(), requested_lamports);
+
+        Ok(())
+    }
+
+    fn process_accept_swap(
+        accounts: &[AccountInfo],
+        swap_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let offerer_account_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if !token::is_token_program(token_program_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            offerer_account_info.key.as_ref(),
+            &swap_id.to_be_bytes(),
+            b"Swap",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_state_account_info.key.eq(&escrow_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let swap_state = validation::load_swap_state(escrow_state_account_info, program_id)?;
+        if !swap_state.offerer.eq(offerer_account_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Offered NFTs are passed as (vault, mint, taker token account) followed by requested NFTs as
+        // (taker token account, mint, offerer token account), both in the order stored in the swap state
+        let asset_accounts = account_info_iter.as_slice();
+        let offered_len = swap_state.offered().len() * 3;
+        if asset_accounts.len() != offered_len + swap_state.requested().len() * 3 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (offered_accounts, requested_accounts) = asset_accounts.split_at(offered_len);
+
+        for (requested, mint) in requested_accounts.chunks(3).zip(swap_state.requested()) {
+            let (token_account_info, mint_account_info, offerer_token_account_info) = (&requested[0], &requested[1], &requested[2]);
+            if !mint_account_info.key.eq(mint) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let offerer_token_account_data = token::unpack_account(offerer_token_account_info)?;
+            if !(offerer_token_account_data.owner.eq(offerer_account_info.key)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let transfer_token_ix = token::transfer_checked(token_program_info.key, token_account_info.key, mint_account_info.key, offerer_token_account_info.key, signer_info.key);
+            invoke(
+                &transfer_token_ix,
+                &[
+                    token_program_info.clone(),
+                    token_account_info.clone(),
+                    mint_account_info.clone(),
+                    offerer_token_account_info.clone(),
+                    signer_info.clone()
+                ],
+            )?;
+        }
+
+        let state_seeds: &[&[u8]] = &[
+            offerer_account_info.key.as_ref(),
+            &swap_id.to_be_bytes(),
+            &b"Swap"[..],
+            &b"State"[..],
+            &[nonce1]
+        ];
+        for (offered, mint) in offered_accounts.chunks(3).zip(swap_state.offered()) {
+            let (escrow_vault_account_info, mint_account_info, token_account_info) = (&offered[0], &offered[1], &offered[2]);
+            if !mint_account_info.key.eq(mint) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let (escrow_vault_account_pubkey, _) = Pubkey::find_program_address(&[
+                escrow_state_account_pubkey.as_ref(),
+                mint_account_info.key.as_ref(),
+                b"Swap",
+                b"Vault"
+                ],
+                program_id
+            );
+            if !(escrow_vault_account_info.key.eq(&escrow_vault_account_pubkey)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            Self::release_escrow_vault(
+                escrow_vault_account_info,
+                mint_account_info,
+                token_account_info,
+                escrow_state_account_info,
+                state_seeds,
+                token_program_info
+            )?;
+        }
+
+        if swap_state.requested_lamports > 0 {
+            let transfer_lamports_ix = system_instruction::transfer(signer_info.key, offerer_account_info.key, swap_state.requested_lamports);
+            invoke(
+                &transfer_lamports_ix,
+                &[
+                    signer_info.clone(),
+                    offerer_account_info.clone(),
+                    system_program_info.clone()
+                ]
+            )?;
+        }
+
+        // The offered lamports go to the taker, everything else in the state account (rent and closed vaults) goes back to the offerer
+        let lamports = escrow_state_account_info.lamports() - swap_state.offered_lamports;
+        **escrow_state_account_info.try_borrow_mut_lamports()? = 0;
+        **signer_info.try_borrow_mut_lamports()? += swap_state.offered_lamports;
+        **offerer_account_info.try_borrow_mut_lamports()? += lamports;
+
+        msg!("{{action: \"AcceptSwap\", offerer: \"{}\", taker: \"{}\", swap_id: {}, offered: {:?}, offered_lamports: {}, requested: {:?}, requested_lamports: {}}}", offerer_account_info.key, signer_info.key, swap_id, swap_state.offered().iter().map(|mint| mint.to_string()).collect::<Vec<_>>(), swap_state.offered_lamports, swap_state.requested().iter().map(|mint| mint.to_string()).collect::<Vec<_>>(), swap_state.requested_lamports);
+
+        Ok(())
+    }
+
+    fn process_cancel_swap(
+        accounts: &[AccountInfo],
+        swap_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if !token::is_token_program(token_program_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            signer_info.key.as_ref(),
+            &swap_id.to_be_bytes(),
+            b"Swap",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_state_account_info.key.eq(&escrow_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let swap_state = validation::load_swap_state(escrow_state_account_info, program_id)?;
+        if !swap_state.offerer.eq(signer_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Offered NFTs are passed as (vault, mint, offerer token account) in the order stored in the swap state
+        let offered_accounts = account_info_iter.as_slice();
+        if offered_accounts.len() != swap_state.offered().len() * 3 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        let state_seeds: &[&[u8]] = &[
+            signer_info.key.as_ref(),
+            &swap_id.to_be_bytes(),
+            &b"Swap"[..],
+            &b"State"[..],
+            &[nonce1]
+        ];
+        for (offered, mint) in offered_accounts.chunks(3).zip(swap_state.offered()) {
+            let (escrow_vault_account_info, mint_account_info, token_account_info) = (&offered[0], &offered[1], &offered[2]);
+            if !mint_account_info.key.eq(mint) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let (escrow_vault_account_pubkey, _) = Pubkey::find_program_address(&[
+                escrow_state_account_pubkey.as_ref(),
+                mint_account_info.key.as_ref(),
+                b"Swap",
+                b"Vault"
+                ],
+                program_id
+            );
+            if !(escrow_vault_account_info.key.eq(&escrow_vault_account_pubkey)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let token_account_data = token::unpack_account(token_account_info)?;
+            if !(token_account_data.owner.eq(signer_info.key)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            Self::release_escrow_vault(
+                escrow_vault_account_info,
+                mint_account_info,
+                token_account_info,
+                escrow_state_account_info,
+                state_seeds,
+                token_program_info
+            )?;
+        }
+
+        let lamports = escrow_state_account_info.lamports();
+        **escrow_state_account_info.try_borrow_mut_lamports()? = 0;
+        **signer_info.try_borrow_mut_lamports()? += lamports;
+
+        msg!("{{action: \"CancelSwap\", offerer: \"{}\", swap_id: {}}}", signer_info.key, swap_id);
+
+        Ok(())
+    }
+
+    fn process_list_bundle(
+        accounts: &[AccountInfo],
+        amount: u64,
+        bundle_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_account_info = next_account_info(account_info_iter)?;
+        let platform_state_account_info = next_account_info(account_info_iter)?;
+
+        if !initializer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let platform_state = validation::load_platform_state(platform_state_account_info, program_id)?;
+        if amount < platform_state.min_list_amount {
+            return Err(NFTError::AmountBelowMinimum.into());
+        }
+
+        if !(program_info.key.eq(program_id)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !token::is_token_program(token_program_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Every bundled NFT is passed as (lister token account, mint, vault)
+        let bundled_accounts = account_info_iter.as_slice();
+        if bundled_accounts.is_empty() || !bundled_accounts.len().is_multiple_of(3) || bundled_accounts.len() / 3 > state::MAX_BUNDLE_MINTS {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            initializer_info.key.as_ref(),
+            &bundle_id.to_be_bytes(),
+            b"Bundle",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_state_account_info.key.eq(&escrow_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let rent = &Rent::from_account_info(rent_account_info)?;
+        let required_balance = rent.minimum_balance(state::BUNDLEESCROWSTATE);
+        let create_state_account_ix = system_instruction::create_account(
+            initializer_info.key, 
+            &escrow_state_account_pubkey, 
+            required_balance, 
+            state::BUNDLEESCROWSTATE as u64, 
+            program_id
+        );
+        invoke_signed(
+            &create_state_account_ix,
+            &[
+                initializer_info.clone(),
+                escrow_state_account_info.clone(),
+                system_program_info.clone(),
+                program_info.clone(),
+            ],
+            &[&[
+                initializer_info.key.as_ref(),
+                &bundle_id.to_be_bytes()[..],
+                &b"Bundle"[..],
+                &b"State"[..],
+                &[nonce1]
+            ]],
+        )?;
+
+        let mut bundle_state = BundleEscrowState{
+            is_initialized: true,
+            lister: *initializer_info.key,
+            bundle_id,
+            amount,
+            mint_count: 0,
+            mints: [Pubkey::default(); state::MAX_BUNDLE_MINTS],
+        };
+
+        for bundled in bundled_accounts.chunks(3) {
+            let (token_account_info, mint_account_info, escrow_vault_account_info) = (&bundled[0], &bundled[1], &bundled[2]);
+
+            if !(mint_account_info.owner.eq(token_program_info.key)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if bundle_state.bundled().contains(mint_account_info.key) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let token_account_data = token::unpack_account(token_account_info)?;
+            if !(token_account_data.owner.eq(initializer_info.key)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if !(token_account_data.mint.eq(mint_account_info.key)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let (escrow_vault_account_pubkey, nonce2) = Pubkey::find_program_address(&[
+                escrow_state_account_pubkey.as_ref(),
+                mint_account_info.key.as_ref(),
+                b"Bundle",
+                b"Vault"
+                ],
+                program_id
+            );
+            if !(escrow_vault_account_info.key.eq(&escrow_vault_account_pubkey)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            Self::create_escrow_vault(
+                &NftTransfer {
+                    source: token_account_info,
+                    owner: initializer_info,
+                    destination: escrow_vault_account_info,
+                    mint: mint_account_info,
+                    token_program: token_program_info,
+                },
+                &escrow_state_account_pubkey,
+                &[
+                    escrow_state_account_pubkey.as_ref(),
+                    mint_account_info.key.as_ref(),
+                    &b"Bundle"[..],
+                    &b"Vault"[..],
+                    &[nonce2]
+                ],
+                system_program_info,
+                rent_account_info,
+                rent
+            )?;
+
+            bundle_state.mints[bundle_state.mint_count as usize] = *mint_account_info.key;
+            bundle_state.mint_count += 1;
+        }
+
+        BundleEscrowState::pack(bundle_state, &mut escrow_state_account_info.data.borrow_mut())?;
+
+        msg!("{{action: \"ListBundle\", lister: \"{}\", bundle_id: {}, amount: {}, mints: {:?}}}", initializer_info.key, bundle_id, amount, bundle_state.bundled().iter().map(|mint| mint.to_string()).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    fn process_delist_bundle(
+        accounts: &[AccountInfo],
+        bundle_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if !token::is_token_program(token_program_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            signer_info.key.as_ref(),
+            &bundle_id.to_be_bytes(),
+            b"Bundle",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_state_account_info.key.eq(&escrow_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let bundle_state = validation::load_bundle_state(escrow_state_account_info, program_id)?;
+        if !bundle_state.lister.eq(signer_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Bundled NFTs are passed as (vault, mint, lister token account) in the order stored in the bundle state
+        let bundled_accounts = account_info_iter.as_slice();
+        if bundled_accounts.len() != bundle_state.bundled().len() * 3 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        let state_seeds: &[&[u8]] = &[
+            signer_info.key.as_ref(),
+            &bundle_id.to_be_bytes(),
+            &b"Bundle"[..],
+            &b"State"[..],
+            &[nonce1]
+        ];
+        for (bundled, mint) in bundled_accounts.chunks(3).zip(bundle_state.bundled()) {
+            let (escrow_vault_account_info, mint_account_info, token_account_info) = (&bundled[0], &bundled[1], &bundled[2]);
+
+            let token_account_data = token::unpack_account(token_account_info)?;
+            if !(token_account_data.owner.eq(signer_info.key)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            Self::release_bundled_nft(
+                mint,
+                &NftTransfer {
+                    source: escrow_vault_account_info,
+                    owner: escrow_state_account_info,
+                    destination: token_account_info,
+                    mint: mint_account_info,
+                    token_program: token_program_info,
+                },
+                state_seeds,
+                program_id
+            )?;
+        }
+
+        let lamports = escrow_state_account_info.lamports();
+        **escrow_state_account_info.try_borrow_mut_lamports()? = 0;
+        **signer_info.try_borrow_mut_lamports()? += lamports;
+
+        msg!("{{action: \"DeListBundle\", lister: \"{}\", bundle_id: {}, amount: {}}}", signer_info.key, bundle_id, bundle_state.amount);
+
+        Ok(())
+    }
+
+    fn process_accept_bundle_listing(
+        accounts: &[AccountInfo],
+        bundle_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let lister_account_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let vault_account_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let fee_schedule_account_info = next_account_info(account_info_iter)?;
+        let metadata_account_info = next_account_info(account_info_iter)?;
+        let trader_state_account_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Self::check_sale_accounts(vault_account_info, token_program_info, system_program_info, program_id)?;
+
+        let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            lister_account_info.key.as_ref(),
+            &bundle_id.to_be_bytes(),
+            b"Bundle",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_state_account_info.key.eq(&escrow_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let platform_state = validation::load_platform_state(state_account_info, program_id)?;
+
+        let bundle_state = validation::load_bundle_state(escrow_state_account_info, program_id)?;
+        if !bundle_state.lister.eq(lister_account_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Bundled NFTs are passed as (vault, mint, buyer token account) in the order stored in the bundle state
+        let bundled_accounts = account_info_iter.as_slice();
+        if bundled_accounts.len() != bundle_state.bundled().len() * 3 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        let state_seeds: &[&[u8]] = &[
+            lister_account_info.key.as_ref(),
+            &bundle_id.to_be_bytes(),
+            &b"Bundle"[..],
+            &b"State"[..],
+            &[nonce1]
+        ];
+        for (bundled, mint) in bundled_accounts.chunks(3).zip(bundle_state.bundled()) {
+            let (escrow_vault_account_info, mint_account_info, token_account_info) = (&bundled[0], &bundled[1], &bundled[2]);
+
+            let token_account_data = token::unpack_account(token_account_info)?;
+            if !(token_account_data.owner.eq(signer_info.key)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            Self::release_bundled_nft(
+                mint,
+                &NftTransfer {
+                    source: escrow_vault_account_info,
+                    owner: escrow_state_account_info,
+                    destination: token_account_info,
+                    mint: mint_account_info,
+                    token_program: token_program_info,
+                },
+                state_seeds,
+                program_id
+            )?;
+        }
+
+        // The platform fee is charged once on the bundle price, not per NFT, at the fee of the first NFT's
+        // collection or else the lister's tier
+        let platform_fee = Self::resolve_platform_fee(
+            &platform_state,
+            fee_schedule_account_info,
+            Some(metadata_account_info),
+            trader_state_account_info,
+            bundle_state.bundled().first().ok_or(ProgramError::InvalidAccountData)?,
+            lister_account_info.key,
+            program_id
+        )?;
+        let (fees_to_platform, _, _) = Self::pay_for_sale(
+            signer_info,
+            lister_account_info,
+            vault_account_info,
+            system_program_info,
+            None,
+            &platform_state,
+            bundle_state.amount,
+            platform_fee
+        )?;
+
+        let lamports = escrow_state_account_info.lamports();
+        **escrow_state_account_info.try_borrow_mut_lamports()? = 0;
+        **lister_account_info.try_borrow_mut_lamports()? += lamports;
+
+        msg!("{{action: \"AcceptBundleListing\", bidder: \"{}\", lister: \"{}\", bundle_id: {}, amount: {}, platform_fee: {}, fee: {}, mints: {:?}}}", signer_info.key, lister_account_info.key, bundle_id, bundle_state.amount, platform_fee, fees_to_platform, bundle_state.bundled().iter().map(|mint| mint.to_string()).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    fn process_list_compressed(
+        accounts: &[AccountInfo],
+        listing: instruction::ListCompressed,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lister_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let tree_accounts = TreeAccounts::next(account_info_iter)?;
+        let rent_account_info = next_account_info(account_info_iter)?;
+        let platform_state_account_info = next_account_info(account_info_iter)?;
+        let mint_state_account_info = next_account_info(account_info_iter)?;
+        let proof = account_info_iter.as_slice();
+
+        if !lister_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let platform_state = validation::load_platform_state(platform_state_account_info, program_id)?;
+        if listing.amount < platform_state.min_list_amount {
+            return Err(NFTError::AmountBelowMinimum.into());
+        }
+
+        tree_accounts.check()?;
+
+        let asset_id = compression::find_asset_id(tree_accounts.merkle_tree.key, listing.nonce);
+        let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            asset_id.as_ref(),
+            lister_info.key.as_ref(),
+            &listing.list_id.to_be_bytes(),
+            b"CompressedList",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_state_account_info.key.eq(&escrow_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let rent = &Rent::from_account_info(rent_account_info)?;
+        let required_balance = rent.minimum_balance(state::COMPRESSEDLISTESCROWSTATE);
+        let create_state_account_ix = system_instruction::create_account(
+            lister_info.key,
+            &escrow_state_account_pubkey,
+            required_balance,
+            state::COMPRESSEDLISTESCROWSTATE as u64,
+            program_id);
+        invoke_signed(
+            &create_state_account_ix,
+            &[
+                lister_info.clone(),
+                escrow_state_account_info.clone(),
+                tree_accounts.system_program.clone(),
+            ],
+            &[&[
+                asset_id.as_ref(),
+                lister_info.key.as_ref(),
+                &listing.list_id.to_be_bytes()[..],
+                &b"CompressedList"[..],
+                &b"State"[..],
+                &[nonce1]
+            ]],
+        )?;
+
+        let list_state = CompressedListEscrowState{
+            is_initialized: true,
+            lister: *lister_info.key,
+            asset_id,
+            merkle_tree: *tree_accounts.merkle_tree.key,
+            data_hash: listing.data_hash,
+            creator_hash: listing.creator_hash,
+            nonce: listing.nonce,
+            index: listing.index,
+            amount: listing.amount,
+            list_id: listing.list_id
+        };
+        Self::transfer_compressed(&tree_accounts, &list_state, &listing.root, lister_info, escrow_state_account_info, proof, &[])?;
+
+        CompressedListEscrowState::pack(list_state, &mut escrow_state_account_info.data.borrow_mut())?;
+
+        Self::create_mint_state(lister_info, mint_state_account_info, &asset_id, tree_accounts.system_program, rent, program_id)?;
+
+        msg!("{{action: \"ListCompressed\", lister \"{}\", list_id: {}, amount: {}, asset_id: {}, merkle_tree: {}}}", lister_info.key, listing.list_id, listing.amount, asset_id, tree_accounts.merkle_tree.key);
+
+        Ok(())
+    }
+
+    fn process_delist_compressed(
+        accounts: &[AccountInfo],
+        list_id: u64,
+        root: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let tree_accounts = TreeAccounts::next(account_info_iter)?;
+        let proof = account_info_iter.as_slice();
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        tree_accounts.check()?;
+
+        let list_state = validation::load_compressed_list_state(escrow_state_account_info, program_id)?;
+        let nonce1 = Self::assert_compressed_list_state(&list_state, escrow_state_account_info, signer_info, list_id, &tree_accounts, program_id)?;
+
+        Self::transfer_compressed(
+            &tree_accounts,
+            &list_state,
+            &root,
+            escrow_state_account_info,
+            signer_info,
+            proof,
+            &[&[
+                list_state.asset_id.as_ref(),
+                signer_info.key.as_ref(),
+                &list_id.to_be_bytes()[..],
+                &b"CompressedList"[..],
+                &b"State"[..],
+                &[nonce1]
+            ]]
+        )?;
+
+        let lamports = escrow_state_account_info.lamports();
+        **escrow_state_account_info.try_borrow_mut_lamports()? = 0;
+        **signer_info.try_borrow_mut_lamports()? += lamports;
+
+        msg!("{{action: \"DeListCompressed\", lister \"{}\", list_id: {}, amount: {}, asset_id: {}}}", signer_info.key, list_id, list_state.amount, list_state.asset_id);
+
+        Ok(())
+    }
+
+    fn process_accept_compressed_listing(
+        accounts: &[AccountInfo],
+        list_id: u64,
+        root: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let lister_account_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let vault_account_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let mint_state_account_info = next_account_info(account_info_iter)?;
+        let fee_schedule_account_info = next_account_info(account_info_iter)?;
+        let trader_state_account_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let list_state = validation::load_compressed_list_state(escrow_state_account_info, program_id)?;
+        // The proof takes every account after the tree accounts, so the optional referrer comes before them. The
+        // tree accounts always start at the tree authority, which tells whether a referrer was passed
+        let tree_authority = compression::find_tree_authority(&list_state.merkle_tree);
+        let referrer_account_info = match account_info_iter.as_slice().first() {
+            Some(account_info) if account_info.key.eq(&tree_authority) => None,
+            _ => Self::next_referrer_account_info(account_info_iter, &[signer_info.key, lister_account_info.key])?,
+        };
+        let tree_accounts = TreeAccounts::next(account_info_iter)?;
+        let proof = account_info_iter.as_slice();
+
+        tree_accounts.check()?;
+
+        let (vault_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"Vault"], program_id);
+        if !(vault_account_info.key.eq(&vault_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let platform_state = validation::load_platform_state(state_account_info, program_id)?;
+        let nonce1 = Self::assert_compressed_list_state(&list_state, escrow_state_account_info, lister_account_info, list_id, &tree_accounts, program_id)?;
+
+        let platform_fee = Self::resolve_platform_fee(
+            &platform_state,
+            fee_schedule_account_info,
+            None,
+            trader_state_account_info,
+            &list_state.asset_id,
+            lister_account_info.key,
+            program_id
+        )?;
+        let fees_to_platform = state::calculate_fee(list_state.amount, platform_fee);
+        let (seller_fee, buyer_fee) = platform_state.fee_bearer.split(fees_to_platform);
+        let amount_after_fees = list_state.amount - seller_fee;
+        let fees_to_referrer = Self::referral_share(&platform_state, fees_to_platform, referrer_account_info);
+
+        let transfer_lamports_lister_ix = system_instruction::transfer(signer_info.key, lister_account_info.key, amount_after_fees);
+        invoke(
+            &transfer_lamports_lister_ix,
+            &[
+                signer_info.clone(),
+                lister_account_info.clone(),
+                tree_accounts.system_program.clone()
+            ]
+        )?;
+        let transfer_lamports_platform_ix = system_instruction::transfer(signer_info.key, &vault_account_pubkey, fees_to_platform - fees_to_referrer);
+        invoke(
+            &transfer_lamports_platform_ix,
+            &[
+                signer_info.clone(),
+                vault_account_info.clone(),
+                tree_accounts.system_program.clone()
+            ]
+        )?;
+        if let Some(referrer_account_info) = referrer_account_info {
+            let transfer_lamports_referrer_ix = system_instruction::transfer(signer_info.key, referrer_account_info.key, fees_to_referrer);
+            invoke(
+                &transfer_lamports_referrer_ix,
+                &[
+                    signer_info.clone(),
+                    referrer_account_info.clone(),
+                    tree_accounts.system_program.clone()
+                ]
+            )?;
+        }
+
+        Self::transfer_compressed(
+            &tree_accounts,
+            &list_state,
+            &root,
+            escrow_state_account_info,
+            signer_info,
+            proof,
+            &[&[
+                list_state.asset_id.as_ref(),
+                lister_account_info.key.as_ref(),
+                &list_id.to_be_bytes()[..],
+                &b"CompressedList"[..],
+                &b"State"[..],
+                &[nonce1]
+            ]]
+        )?;
+
+        let lamports = escrow_state_account_info.lamports();
+        **escrow_state_account_info.try_borrow_mut_lamports()? = 0;
+        **lister_account_info.try_borrow_mut_lamports()? += lamports;
+
+        // Listings made before compressed sales were counted have no mint state yet
+        Self::create_mint_state(signer_info, mint_state_account_info, &list_state.asset_id, tree_accounts.system_program, &Rent::get()?, program_id)?;
+        let sale_count = Self::record_sale(mint_state_account_info, &list_state.asset_id, program_id)?;
+
+        msg!("{{action: \"AcceptCompressedListing\", bidder: \"{}\", lister: \"{}\", list_id: {}, amount: {}, asset_id: \"{}\", sale_count: {}, platform_fee: {}, fee: {}, buyer_fee: {}, referrer: {}, referral_fee: {}}}", signer_info.key, lister_account_info.key, list_id, list_state.amount, list_state.asset_id, sale_count, platform_fee, fees_to_platform, buyer_fee, Self::log_referrer(referrer_account_info), fees_to_referrer);
+
+        Ok(())
+    }
+
+    fn process_list_delegated(
+        accounts: &[AccountInfo],
+        amount: u64,
+        list_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let initializer_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let mint_account_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let edition_account_info = next_account_info(account_info_iter)?;
+        let metadata_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_account_info = next_account_info(account_info_iter)?;
+        let mint_state_account_info = next_account_info(account_info_iter)?;
+        let platform_state_account_info = next_account_info(account_info_iter)?;
+
+        if !initializer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let platform_state = validation::load_platform_state(platform_state_account_info, program_id)?;
+        if amount < platform_state.min_list_amount {
+            return Err(NFTError::AmountBelowMinimum.into());
+        }
+
+        let token_account_data = token::unpack_account(token_account_info)?;
+        if !(token_account_data.owner.eq(initializer_info.key)) || token_account_data.amount != 1 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(mint_account_info.owner.eq(token_program_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        token::check_mint(mint_account_info)?;
+
+        if !(token_account_data.mint.eq(mint_account_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !token::is_token_program(token_program_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            mint_account_info.key.as_ref(),
+            initializer_info.key.as_ref(),
+            &list_id.to_be_bytes(),
+            b"DelegatedList",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_state_account_info.key.eq(&escrow_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let rent = &Rent::from_account_info(rent_account_info)?;
+        let required_balance = rent.minimum_balance(state::DELEGATEDLISTESCROWSTATE);
+        let create_state_account_ix = system_instruction::create_account(
+            initializer_info.key,
+            &escrow_state_account_pubkey,
+            required_balance,
+            state::DELEGATEDLISTESCROWSTATE as u64,
+            program_id);
+        let signer_seeds: &[&[u8]] = &[
+            mint_account_info.key.as_ref(),
+            initializer_info.key.as_ref(),
+            &list_id.to_be_bytes(),
+            b"DelegatedList",
+            b"State",
+            &[nonce1]
+        ];
+        invoke_signed(
+            &create_state_account_ix,
+            &[
+                initializer_info.clone(),
+                escrow_state_account_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[signer_seeds],
+        )?;
+
+        // Approving replaces any earlier delegate, which leaves a listing that relied on it stale
+        let approve_ix = token::approve(token_program_info.key, token_account_info.key, &escrow_state_account_pubkey, initializer_info.key);
+        invoke(
+            &approve_ix,
+            &[
+                token_program_info.clone(),
+                token_account_info.clone(),
+                escrow_state_account_info.clone(),
+                initializer_info.clone()
+            ],
+        )?;
+
+        let frozen = Self::can_freeze_delegated(mint_account_info, edition_account_info, token_program_info)?;
+        if frozen {
+            Self::invoke_delegated_account(
+                metadata::freeze_delegated_account(&escrow_state_account_pubkey, token_account_info.key, mint_account_info.key),
+                escrow_state_account_info,
+                token_account_info,
+                edition_account_info,
+                mint_account_info,
+                metadata_program_info,
+                token_program_info,
+                signer_seeds
+            )?;
+        }
+
+        DelegatedListEscrowState::pack(
+            DelegatedListEscrowState{
+                is_initialized: true,
+                lister: *initializer_info.key,
+                mint: *mint_account_info.key,
+                token_account: *token_account_info.key,
+                amount,
+                list_id,
+                frozen
+            },
+            &mut escrow_state_account_info.data.borrow_mut()
+        )?;
+
+        Self::create_mint_state(initializer_info, mint_state_account_info, mint_account_info.key, system_program_info, rent, program_id)?;
+
+        msg!("{{action: \"ListDelegated\", lister \"{}\", list_id: {}, amount: {}, mint: {}, frozen: {}}}", initializer_info.key, list_id, amount, mint_account_info.key, frozen);
+
+        Ok(())
+    }
+
+    fn process_delist_delegated(
+        accounts: &[AccountInfo],
+        list_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let mint_account_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let edition_account_info = next_account_info(account_info_iter)?;
+        let metadata_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if !token::is_token_program(token_program_info.key) || !token_account_info.owner.eq(token_program_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let list_state = validation::load_delegated_list_state(escrow_state_account_info, program_id)?;
+        let nonce1 = Self::assert_delegated_list_state(&list_state, escrow_state_account_info, signer_info, token_account_info, mint_account_info, list_id, program_id)?;
+        let signer_seeds: &[&[u8]] = &[
+            mint_account_info.key.as_ref(),
+            signer_info.key.as_ref(),
+            &list_id.to_be_bytes(),
+            b"DelegatedList",
+            b"State",
+            &[nonce1]
+        ];
+
+        if list_state.frozen {
+            Self::invoke_delegated_account(
+                metadata::thaw_delegated_account(escrow_state_account_info.key, token_account_info.key, mint_account_info.key),
+                escrow_state_account_info,
+                token_account_info,
+                edition_account_info,
+                mint_account_info,
+                metadata_program_info,
+                token_program_info,
+                signer_seeds
+            )?;
+        }
+
+        // The lister may have moved the NFT or delegated it elsewhere since, then there is nothing left to revoke
+        let token_account_data = token::unpack_account(token_account_info)?;
+        if token_account_data.owner.eq(signer_info.key) && token_account_data.delegate == COption::Some(*escrow_state_account_info.key) {
+            let revoke_ix = token::revoke(token_program_info.key, token_account_info.key, signer_info.key);
+            invoke(
+                &revoke_ix,
+                &[
+                    token_program_info.clone(),
+                    token_account_info.clone(),
+                    signer_info.clone()
+                ],
+            )?;
+        }
+
+        let lamports = escrow_state_account_info.lamports();
+        **escrow_state_account_info.try_borrow_mut_lamports()? = 0;
+        **signer_info.try_borrow_mut_lamports()? += lamports;
+
+        msg!("{{action: \"DeListDelegated\", lister \"{}\", list_id: {}, amount: {}, mint: {}}}", signer_info.key, list_id, list_state.amount, mint_account_info.key);
+
+        Ok(())
+    }
+
+    fn process_accept_delegated_listing(
+        accounts: &[AccountInfo],
+        list_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let mint_account_info = next_account_info(account_info_iter)?;
+        let lister_account_info = next_account_info(account_info_iter)?;
+        let lister_token_account_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let vault_account_info = next_account_info(account_info_iter)?;
+        let escrow_state_account_info = next_account_info(account_info_iter)?;
+        let edition_account_info = next_account_info(account_info_iter)?;
+        let metadata_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let mint_state_account_info = next_account_info(account_info_iter)?;
+        let fee_schedule_account_info = next_account_info(account_info_iter)?;
+        let metadata_account_info = next_account_info(account_info_iter)?;
+        let trader_state_account_info = next_account_info(account_info_iter)?;
+        let order_nonce_account_info = next_account_info(account_info_iter)?;
+        let referrer_account_info = Self::next_referrer_account_info(account_info_iter, &[signer_info.key, lister_account_info.key])?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let token_account_data = token::unpack_account(token_account_info)?;
+        if !(token_account_data.owner.eq(signer_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(mint_account_info.owner.eq(token_program_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(token_account_data.mint.eq(mint_account_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (vault_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"Vault"], program_id);
+        if !(vault_account_info.key.eq(&vault_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !token::is_token_program(token_program_info.key) || !lister_token_account_info.owner.eq(token_program_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let platform_state = validation::load_platform_state(state_account_info, program_id)?;
+        let list_state = validation::load_delegated_list_state(escrow_state_account_info, program_id)?;
+        let nonce1 = Self::assert_delegated_list_state(&list_state, escrow_state_account_info, lister_account_info, lister_token_account_info, mint_account_info, list_id, program_id)?;
+        Self::assert_order_not_cancelled(order_nonce_account_info, lister_account_info.key, list_id, &order::delegated_listing_hash(escrow_state_account_info.key), program_id)?;
+
+        // Nothing stops the lister from moving the NFT or replacing the delegate of an unfrozen account after
+        // listing, so the delegation is only trusted once it is seen intact at fill time
+        let lister_token_account_data = token::unpack_account(lister_token_account_info)?;
+        if !lister_token_account_data.owner.eq(lister_account_info.key)
+            || lister_token_account_data.amount != 1
+            || lister_token_account_data.delegate != COption::Some(*escrow_state_account_info.key)
+            || lister_token_account_data.delegated_amount < 1
+        {
+            return Err(NFTError::StaleDelegation.into());
+        }
+
+        let platform_fee = Self::resolve_platform_fee(
+            &platform_state,
+            fee_schedule_account_info,
+            Some(metadata_account_info),
+            trader_state_account_info,
+            mint_account_info.key,
+            lister_account_info.key,
+            program_id
+        )?;
+        let fees_to_platform = state::calculate_fee(list_state.amount, platform_fee);
+        let (seller_fee, buyer_fee) = platform_state.fee_bearer.split(fees_to_platform);
+        let amount_after_fees = list_state.amount - seller_fee;
+        let fees_to_referrer = Self::referral_share(&platform_state, fees_to_platform, referrer_account_info);
+
+        let transfer_lamports_lister_ix = system_instruction::transfer(signer_info.key, lister_account_info.key, amount_after_fees);
+        invoke(
+            &transfer_lamports_lister_ix,
+            &[
+                signer_info.clone(),
+                lister_account_info.clone(),
+                system_program_info.clone()
+            ]
+        )?;
+        let transfer_lamports_platform_ix = system_instruction::transfer(signer_info.key, &vault_account_pubkey, fees_to_platform - fees_to_referrer);
+        invoke(
+            &transfer_lamports_platform_ix,
+            &[
+                signer_info.clone(),
+                vault_account_info.clone(),
+                system_program_info.clone()
+            ]
+        )?;
+        if let Some(referrer_account_info) = referrer_account_info {
+            let transfer_lamports_referrer_ix = system_instruction::transfer(signer_info.key, referrer_account_info.key, fees_to_referrer);
+            invoke(
+                &transfer_lamports_referrer_ix,
+                &[
+                    signer_info.clone(),
+                    referrer_account_info.clone(),
+                    system_program_info.clone()
+                ]
+            )?;
+        }
+
+        let signer_seeds: &[&[u8]] = &[
+            mint_account_info.key.as_ref(),
+            lister_account_info.key.as_ref(),
+            &list_id.to_be_bytes(),
+            b"DelegatedList",
+            b"State",
+            &[nonce1]
+        ];
+        if list_state.frozen {
+            Self::invoke_delegated_account(
+                metadata::thaw_delegated_account(escrow_state_account_info.key, lister_token_account_info.key, mint_account_info.key),
+                escrow_state_account_info,
+                lister_token_account_info,
+                edition_account_info,
+                mint_account_info,
+                metadata_program_info,
+                token_program_info,
+                signer_seeds
+            )?;
+        }
+
+        let transfer_token_ix = token::transfer_checked(token_program_info.key, lister_token_account_info.key, mint_account_info.key, token_account_info.key, escrow_state_account_info.key);
+        invoke_signed(
+            &transfer_token_ix,
+            &[
+                token_program_info.clone(),
+                lister_token_account_info.clone(),
+                mint_account_info.clone(),
+                token_account_info.clone(),
+                escrow_state_account_info.clone()
+            ],
+            &[signer_seeds],
+        )?;
+
+        let lamports = escrow_state_account_info.lamports();
+        **escrow_state_account_info.try_borrow_mut_lamports()? = 0;
+        **lister_account_info.try_borrow_mut_lamports()? += lamports;
+
+        let sale_count = Self::record_sale(mint_state_account_info, mint_account_info.key, program_id)?;
+
+        msg!("{{action: \"AcceptDelegatedListing\", bidder: \"{}\", lister: \"{}\", list_id: {}, amount: {}, mint: \"{}\", sale_count: {}, platform_fee: {}, fee: {}, buyer_fee: {}, referrer: {}, referral_fee: {}}}", signer_info.key, lister_account_info.key, list_id, list_state.amount, mint_account_info.key, sale_count, platform_fee, fees_to_platform, buyer_fee, Self::log_referrer(referrer_account_info), fees_to_referrer);
+
+        Ok(())
+    }
+
+    fn process_match_orders(
+        accounts: &[AccountInfo],
+        bid_id: u64,
+        list_id: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let mint_account_info = next_account_info(account_info_iter)?;
+        let lister_account_info = next_account_info(account_info_iter)?;
+        let bidder_account_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let vault_account_info = next_account_info(account_info_iter)?;
+        let escrow_bid_state_account_info = next_account_info(account_info_iter)?;
+        let escrow_bid_vault_account_info = next_account_info(account_info_iter)?;
+        let escrow_list_state_account_info = next_account_info(account_info_iter)?;
+        let mint_state_account_info = next_account_info(account_info_iter)?;
+        let fee_schedule_account_info = next_account_info(account_info_iter)?;
+        let metadata_account_info = next_account_info(account_info_iter)?;
+        let trader_state_account_info = next_account_info(account_info_iter)?;
+        let order_book_account_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if !token::is_token_program(mint_account_info.owner) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (vault_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"Vault"], program_id);
+        if !(vault_account_info.key.eq(&vault_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (escrow_bid_state_account_pubkey, _) = Pubkey::find_program_address(&[
+            mint_account_info.key.as_ref(),
+            bidder_account_info.key.as_ref(),
+            &bid_id.to_be_bytes(),
+            b"Bid",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_bid_state_account_info.key.eq(&escrow_bid_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (escrow_bid_vault_account_pubkey, _) = Pubkey::find_program_address(&[
+            mint_account_info.key.as_ref(),
+            bidder_account_info.key.as_ref(),
+            &bid_id.to_be_bytes(),
+            b"Bid",
+            b"Vault"
+            ],
+            program_id
+        );
+        if !(escrow_bid_vault_account_info.key.eq(&escrow_bid_vault_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (escrow_list_state_account_pubkey, _) = Pubkey::find_program_address(&[
+            mint_account_info.key.as_ref(),
+            lister_account_info.key.as_ref(),
+            &list_id.to_be_bytes(),
+            b"List",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_list_state_account_info.key.eq(&escrow_list_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let platform_state = validation::load_platform_state(state_account_info, program_id)?;
+
+        let mut list_state = validation::load_list_state(escrow_list_state_account_info, program_id)?;
+        if !list_state.lister.eq(lister_account_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if list_state.success {
+            return Err(NFTError::ListingAlreadyFullfilled.into());
+        }
+
+        let bid_state = validation::load_bid_state(escrow_bid_state_account_info, program_id)?;
+        if !bid_state.bidder.eq(bidder_account_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !list_state.can_be_bought_by(bidder_account_info.key) {
+            return Err(NFTError::ListingReserved.into());
+        }
+        if bid_state.amount < list_state.amount {
+            return Err(NFTError::OrdersDoNotCross.into());
+        }
+        if bid_state.is_expired(Clock::get()?.unix_timestamp) {
+            return Err(NFTError::BidExpired.into());
+        }
+
+        // The crossing bid fills at the listing price, the rest of the bid goes back to the bidder
+        let price = list_state.amount;
+        list_state.success = true;
+        list_state.successful_buyer = *bidder_account_info.key;
+
+        ListEscrowState::pack(
+            list_state,
+            &mut escrow_list_state_account_info.data.borrow_mut()
+        )?;
+
+        let platform_fee = Self::resolve_platform_fee(
+            &platform_state,
+            fee_schedule_account_info,
+            Some(metadata_account_info),
+            trader_state_account_info,
+            mint_account_info.key,
+            lister_account_info.key,
+            program_id
+        )?;
+        let (seller_fee, buyer_fee) = platform_state.fee_bearer.split(state::calculate_fee(price, platform_fee));
+        let buyer_fee = Self::escrowed_buyer_fee(&bid_state, price, buyer_fee, escrow_bid_vault_account_info)?;
+        let fees_to_platform = seller_fee + buyer_fee;
+        let amount_after_fees = price - seller_fee;
+        let keeper_reward = state::calculate_fee(fees_to_platform, state::KEEPER_REWARD);
+
+        let lamports_to_bidder = escrow_bid_state_account_info.lamports() + escrow_bid_vault_account_info.lamports() - price - buyer_fee;
+        **escrow_bid_state_account_info.try_borrow_mut_lamports()? = 0;
+        **escrow_bid_vault_account_info.try_borrow_mut_lamports()? = 0;
+        **vault_account_info.try_borrow_mut_lamports()? += fees_to_platform - keeper_reward;
+        **signer_info.try_borrow_mut_lamports()? += keeper_reward;
+        **lister_account_info.try_borrow_mut_lamports()? += amount_after_fees;
+        **bidder_account_info.try_borrow_mut_lamports()? += lamports_to_bidder;
+
+        let sale_count = Self::record_sale(mint_state_account_info, mint_account_info.key, program_id)?;
+        Self::update_order_book(order_book_account_info, mint_account_info.key, program_id, |order_book| {
+            order_book.remove_bid(bidder_account_info.key, bid_id);
+            order_book.remove_listing(lister_account_info.key, list_id);
+        })?;
+
+        msg!("{{action: \"MatchOrders\", keeper: \"{}\", bidder: \"{}\", bid_id: {}, bid_amount: {}, lister: \"{}\", list_id: {}, amount: {}, mint: \"{}\", sale_count: {}, platform_fee: {}, fee: {}, buyer_fee: {}, keeper_reward: {}}}", signer_info.key, bidder_account_info.key, bid_id, bid_state.amount, lister_account_info.key, list_id, price, mint_account_info.key, sale_count, platform_fee, fees_to_platform, buyer_fee, keeper_reward);
+
+        Ok(())
+    }
+
+    fn process_execute_signed_order(
+        accounts: &[AccountInfo],
+        price: u64,
+        expiry: i64,
+        nonce: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let mint_account_info = next_account_info(account_info_iter)?;
+        let seller_account_info = next_account_info(account_info_iter)?;
+        let seller_token_account_info = next_account_info(account_info_iter)?;
+        let delegate_account_info = next_account_info(account_info_iter)?;
+        let nonce_account_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let vault_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let instructions_sysvar_info = next_account_info(account_info_iter)?;
+        let mint_state_account_info = next_account_info(account_info_iter)?;
+        let fee_schedule_account_info = next_account_info(account_info_iter)?;
+        let metadata_account_info = next_account_info(account_info_iter)?;
+        let trader_state_account_info = next_account_info(account_info_iter)?;
+        let order_nonce_account_info = next_account_info(account_info_iter)?;
+        let referrer_account_info = Self::next_referrer_account_info(account_info_iter, &[signer_info.key, seller_account_info.key])?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // A signed order never passes through List, so the mint is checked here
+        token::check_mint(mint_account_info)?;
+        if !(mint_account_info.owner.eq(token_program_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let token_account_data = token::unpack_account(token_account_info)?;
+        if !(token_account_data.owner.eq(signer_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !(token_account_data.mint.eq(mint_account_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (vault_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"Vault"], program_id);
+        if !(vault_account_info.key.eq(&vault_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (delegate_account_pubkey, nonce1) = order::find_delegate_address(program_id);
+        if !(delegate_account_info.key.eq(&delegate_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (nonce_account_pubkey, nonce2) = Pubkey::find_program_address(&[
+            seller_account_info.key.as_ref(),
+            &nonce.to_be_bytes(),
+            b"Order",
+            b"Nonce"
+            ],
+            program_id
+        );
+        if !(nonce_account_info.key.eq(&nonce_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !token::is_token_program(token_program_info.key) || !seller_token_account_info.owner.eq(token_program_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let signed_order = SignedOrder {
+            seller: *seller_account_info.key,
+            mint: *mint_account_info.key,
+            price,
+            expiry,
+            nonce,
+        };
+        order::verify_signature(instructions_sysvar_info, seller_account_info.key, &signed_order.message(program_id))?;
+        if signed_order.is_expired(Clock::get()?.unix_timestamp) {
+            return Err(NFTError::OrderExpired.into());
+        }
+        Self::assert_order_not_cancelled(order_nonce_account_info, seller_account_info.key, nonce, &signed_order.hash(program_id), program_id)?;
+        // Only the program can own the nonce's PDA, lamports alone prove nothing as anyone can send them there
+        if nonce_account_info.owner.eq(program_id) {
+            return Err(NFTError::NonceAlreadyUsed.into());
+        }
+
+        // The seller approves the order delegate once and can move the NFT or revoke at any time, so the
+        // delegation is only trusted once it is seen intact at fill time
+        let seller_token_account_data = token::unpack_account(seller_token_account_info)?;
+        if !seller_token_account_data.owner.eq(seller_account_info.key)
+            || !seller_token_account_data.mint.eq(mint_account_info.key)
+            || seller_token_account_data.amount != 1
+            || seller_token_account_data.delegate != COption::Some(delegate_account_pubkey)
+            || seller_token_account_data.delegated_amount < 1
+        {
+            return Err(NFTError::StaleDelegation.into());
+        }
+
+        let platform_state = validation::load_platform_state(state_account_info, program_id)?;
+        let platform_fee = Self::resolve_platform_fee(
+            &platform_state,
+            fee_schedule_account_info,
+            Some(metadata_account_info),
+            trader_state_account_info,
+            mint_account_info.key,
+            seller_account_info.key,
+            program_id
+        )?;
+        let fees_to_platform = state::calculate_fee(price, platform_fee);
+        let (seller_fee, buyer_fee) = platform_state.fee_bearer.split(fees_to_platform);
+        let amount_after_fees = price - seller_fee;
+        let fees_to_referrer = Self::referral_share(&platform_state, fees_to_platform, referrer_account_info);
+
+        // Burning the nonce is an empty account at its PDA owned by the program, paid for by the buyer. The PDA may
+        // already hold lamports, which create_account refuses, so it is topped up to rent exemption and then
+        // allocated and assigned instead
+        let rent = Rent::get()?;
+        let nonce_seeds: &[&[u8]] = &[
+            seller_account_info.key.as_ref(),
+            &nonce.to_be_bytes(),
+            &b"Order"[..],
+            &b"Nonce"[..],
+            &[nonce2]
+        ];
+        let required_lamports = rent.minimum_balance(0).saturating_sub(nonce_account_info.lamports());
+        if required_lamports > 0 {
+            let transfer_lamports_nonce_ix = system_instruction::transfer(signer_info.key, &nonce_account_pubkey, required_lamports);
+            invoke(
+                &transfer_lamports_nonce_ix,
+                &[
+                    signer_info.clone(),
+                    nonce_account_info.clone(),
+                    system_program_info.clone()
+                ]
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(&nonce_account_pubkey, 0),
+            &[nonce_account_info.clone(), system_program_info.clone()],
+            &[nonce_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(&nonce_account_pubkey, program_id),
+            &[nonce_account_info.clone(), system_program_info.clone()],
+            &[nonce_seeds],
+        )?;
+
+        let nft_transfer_ix = token::transfer_checked(token_program_info.key, seller_token_account_info.key, mint_account_info.key, token_account_info.key, &delegate_account_pubkey);
+        invoke_signed(
+            &nft_transfer_ix,
+            &[
+                token_program_info.clone(),
+                seller_token_account_info.clone(),
+                mint_account_info.clone(),
+                token_account_info.clone(),
+                delegate_account_info.clone(),
+            ],
+            &[&[&b"Order"[..], &b"Delegate"[..], &[nonce1]]],
+        )?;
+
+        let transfer_lamports_seller_ix = system_instruction::transfer(signer_info.key, seller_account_info.key, amount_after_fees);
+        invoke(
+            &transfer_lamports_seller_ix,
+            &[
+                signer_info.clone(),
+                seller_account_info.clone(),
+                system_program_info.clone()
+            ]
+        )?;
+        let transfer_lamports_platform_ix = system_instruction::transfer(signer_info.key, &vault_account_pubkey, fees_to_platform - fees_to_referrer);
+        invoke(
+            &transfer_lamports_platform_ix,
+            &[
+                signer_info.clone(),
+                vault_account_info.clone(),
+                system_program_info.clone()
+            ]
+        )?;
+        if let Some(referrer_account_info) = referrer_account_info {
+            let transfer_lamports_referrer_ix = system_instruction::transfer(signer_info.key, referrer_account_info.key, fees_to_referrer);
+            invoke(
+                &transfer_lamports_referrer_ix,
+                &[
+                    signer_info.clone(),
+                    referrer_account_info.clone(),
+                    system_program_info.clone()
+                ]
+            )?;
+        }
+
+        Self::create_mint_state(signer_info, mint_state_account_info, mint_account_info.key, system_program_info, &rent, program_id)?;
+        let sale_count = Self::record_sale(mint_state_account_info, mint_account_info.key, program_id)?;
+
+        msg!("{{action: \"ExecuteSignedOrder\", bidder: \"{}\", seller: \"{}\", nonce: {}, amount: {}, mint: \"{}\", sale_count: {}, platform_fee: {}, fee: {}, buyer_fee: {}, referrer: {}, referral_fee: {}}}", signer_info.key, seller_account_info.key, nonce, price, mint_account_info.key, sale_count, platform_fee, fees_to_platform, buyer_fee, Self::log_referrer(referrer_account_info), fees_to_referrer);
+
+        Ok(())
+    }
+
+    fn process_increment_nonce(
+        accounts: &[AccountInfo],
+        nonce: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let order_nonce_account_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut order_nonce_state = Self::load_or_create_order_nonce_state(signer_info, order_nonce_account_info, system_program_info, &Rent::get()?, program_id)?;
+        if nonce <= order_nonce_state.min_nonce {
+            return Err(NFTError::NonceNotIncreased.into());
+        }
+        order_nonce_state.min_nonce = nonce;
+        OrderNonceState::pack(order_nonce_state, &mut order_nonce_account_info.data.borrow_mut())?;
+
+        msg!("{{action: \"IncrementNonce\", owner: \"{}\", min_nonce: {}}}", signer_info.key, nonce);
+
+        Ok(())
+    }
+
+    fn process_cancel_order(
+        accounts: &[AccountInfo],
+        order_hash: Hash,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let order_nonce_account_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut order_nonce_state = Self::load_or_create_order_nonce_state(signer_info, order_nonce_account_info, system_program_info, &Rent::get()?, program_id)?;
+        order_nonce_state.cancel(&order_hash);
+        OrderNonceState::pack(order_nonce_state, &mut order_nonce_account_info.data.borrow_mut())?;
+
+        msg!("{{action: \"CancelOrder\", owner: \"{}\", order_hash: \"{}\"}}", signer_info.key, order_hash);
+
+        Ok(())
+    }
+
+    fn process_migrate_state(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if !state_account_info.owner.eq(program_id) {
+            return Err(ProgramError::IllegalOwner);
+        }
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (platform_state_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"State"], program_id);
+        if !(state_account_info.key.eq(&platform_state_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let migrated = state::migrate_platform_state(&state_account_info.data.borrow())?;
+        let data = match migrated {
+            Some(data) => data,
+            None => {
+                msg!("{{action: \"MigrateState\", account: \"{}\", migrated: false}}", state_account_info.key);
+                return Ok(());
+            }
+        };
+
+        // The payer covers the rent of the bytes the current layout adds
+        let rent_top_up = Rent::get()?.minimum_balance(data.len()).saturating_sub(state_account_info.lamports());
+        if rent_top_up > 0 {
+            let transfer_rent_ix = system_instruction::transfer(signer_info.key, state_account_info.key, rent_top_up);
+            invoke(
+                &transfer_rent_ix,
+                &[
+                    signer_info.clone(),
+                    state_account_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        state_account_info.realloc(data.len(), false)?;
+        state_account_info.data.borrow_mut().copy_from_slice(&data);
+
+        msg!("{{action: \"MigrateState\", account: \"{}\", migrated: true, len: {}}}", state_account_info.key, data.len());
+
+        Ok(())
+    }
+
+    fn process_set_collection_fee(
+        accounts: &[AccountInfo],
+        collection: Pubkey,
+        platform_fee: Option<u64>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let fee_schedule_account_info = next_account_info(account_info_iter)?;
+        let program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_account_info = next_account_info(account_info_iter)?;
+
+        Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
+        if matches!(platform_fee, Some(platform_fee) if platform_fee > state::FEE_DENOMINATOR) {
+            return Err(NFTError::InvalidPlatformFee.into());
+        }
+
+        let mut fee_schedule = Self::load_or_create_fee_schedule(
+            signer_info,
+            fee_schedule_account_info,
+            program_info,
+            system_program_info,
+            rent_account_info,
+            program_id
+        )?;
+        fee_schedule.set_collection_fee(&collection, platform_fee)?;
+        FeeScheduleState::pack(fee_schedule, &mut fee_schedule_account_info.data.borrow_mut())?;
+
+        msg!("{{action: \"SetCollectionFee\", collection: \"{}\", platform_fee: {:?}}}", collection, platform_fee);
+
+        Ok(())
+    }
+
+    fn process_set_tier_fee(
+        accounts: &[AccountInfo],
+        tier: u8,
+        platform_fee: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let fee_schedule_account_info = next_account_info(account_info_iter)?;
+        let program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_account_info = next_account_info(account_info_iter)?;
+
+        let platform_state = Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
+        if platform_fee > state::FEE_DENOMINATOR {
+            return Err(NFTError::InvalidPlatformFee.into());
+        }
+
+        let mut fee_schedule = Self::load_or_create_fee_schedule(
+            signer_info,
+            fee_schedule_account_info,
+            program_info,
+            system_program_info,
+            rent_account_info,
+            program_id
+        )?;
+        // Tiers skipped over when adding a higher tier start out at the regular platform fee
+        while fee_schedule.tier_count < tier {
+            fee_schedule.tier_fees[fee_schedule.tier_count as usize] = platform_state.platform_fee;
+            fee_schedule.tier_count += 1;
+        }
+        fee_schedule.tier_fees[tier as usize - 1] = platform_fee;
+        FeeScheduleState::pack(fee_schedule, &mut fee_schedule_account_info.data.borrow_mut())?;
+
+        msg!("{{action: \"SetTierFee\", tier: {}, platform_fee: {}}}", tier, platform_fee);
+
+        Ok(())
+    }
+
+    fn process_set_trader_tier(
+        accounts: &[AccountInfo],
+        trader: Pubkey,
+        tier: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let trader_state_account_info = next_account_info(account_info_iter)?;
+        let program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_account_info = next_account_info(account_info_iter)?;
+
+        Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
+
+        if !(program_info.key.eq(program_id)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (trader_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            trader.as_ref(),
+            b"Trader",
+            b"State"
+            ],
+            program_id
+        );
+        if !(trader_state_account_info.key.eq(&trader_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if trader_state_account_info.data_is_empty() {
+            let rent = &Rent::from_account_info(rent_account_info)?;
+            let required_balance = rent.minimum_balance(state::TRADERSTATE);
+            let create_trader_state_account_ix = system_instruction::create_account(
+                signer_info.key, 
+                &trader_state_account_pubkey, 
+                required_balance, 
+                state::TRADERSTATE as u64, 
+                program_id
+            );
+            invoke_signed(
+                &create_trader_state_account_ix,
+                &[
+                    signer_info.clone(),
+                    trader_state_account_info.clone(),
+                    system_program_info.clone(),
+                    program_info.clone(),
+                ],
+                &[&[
+                    trader.as_ref(),
+                    &b"Trader"[..],
+                    &b"State"[..],
+                    &[nonce1]
+                ]],
+            )?;
+        }
+
+        TraderState::pack(
+            TraderState{
+                is_initialized: true,
+                trader,
+                tier
+            },
+            &mut trader_state_account_info.data.borrow_mut()
+        )?;
+
+        msg!("{{action: \"SetTraderTier\", trader: \"{}\", tier: {}}}", trader, tier);
+
+        Ok(())
+    }
+
+    fn process_set_fee_distribution(
+        accounts: &[AccountInfo],
+        recipients: Vec<state::FeeRecipient>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let state_account_info = next_account_info(account_info_iter)?;
+        let distribution_account_info = next_account_info(account_info_iter)?;
+        let program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent_account_info = next_account_info(account_info_iter)?;
+
+        Self::assert_platform_authority(signer_info, state_account_info, program_id)?;
+
+        if !(program_info.key.eq(program_id)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (distribution_account_pubkey, nonce1) = Pubkey::find_program_address(&[b"Platform", b"Distribution"], program_id);
+        if !(distribution_account_info.key.eq(&distribution_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if distribution_account_info.data_is_empty() {
+            let rent = &Rent::from_account_info(rent_account_info)?;
+            let required_balance = rent.minimum_balance(state::FEEDISTRIBUTIONSTATE);
+            let create_distribution_account_ix = system_instruction::create_account(
+                signer_info.key, 
+                &distribution_account_pubkey, 
+                required_balance, 
+                state::FEEDISTRIBUTIONSTATE as u64, 
+                program_id
+            );
+            invoke_signed(
+                &create_distribution_account_ix,
+                &[
+                    signer_info.clone(),
+                    distribution_account_info.clone(),
+                    system_program_info.clone(),
+                    program_info.clone(),
+                ],
+                &[&[&b"Platform"[..], &b"Distribution"[..], &[nonce1]]],
+            )?;
+        }
+
+        let mut distribution = FeeDistributionState{
+            is_initialized: true,
+            recipient_count: recipients.len() as u8,
+            ..FeeDistributionState::default()
+        };
+        distribution.recipients[..recipients.len()].copy_from_slice(&recipients);
+        FeeDistributionState::pack(distribution, &mut distribution_account_info.data.borrow_mut())?;
+
+        msg!("{{action: \"SetFeeDistribution\", recipients: {}}}", recipients.len());
+
+        Ok(())
+    }
+
+    /// Pays everything the platform vault holds above its rent exempt minimum out to the fee distribution
+    /// recipients, which are passed in table order after the fixed accounts. Anyone can crank it
+    fn process_distribute_fees(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let signer_info = next_account_info(account_info_iter)?;
+        let vault_account_info = next_account_info(account_info_iter)?;
+        let distribution_account_info = next_account_info(account_info_iter)?;
+        let rent_account_info = next_account_info(account_info_iter)?;
+
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let (vault_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"Vault"], program_id);
+        if !(vault_account_info.key.eq(&vault_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (distribution_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"Distribution"], program_id);
+        if !(distribution_account_info.key.eq(&distribution_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let distribution = validation::load_fee_distribution(distribution_account_info, program_id)?;
+
+        let rent = &Rent::from_account_info(rent_account_info)?;
+        let distributable = vault_account_info
+            .lamports()
+            .saturating_sub(rent.minimum_balance(vault_account_info.data_len()));
+        let shares = state::split_fees(distributable, distribution.recipients());
+
+        for (recipient, share) in distribution.recipients().iter().zip(shares) {
+            let recipient_account_info = next_account_info(account_info_iter)?;
+            if !(recipient_account_info.key.eq(&recipient.recipient)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            **vault_account_info.try_borrow_mut_lamports()? -= share;
+            **recipient_account_info.try_borrow_mut_lamports()? += share;
+        }
+
+        msg!("{{action: \"DistributeFees\", amount: {}, recipients: {}}}", distributable, distribution.recipient_count);
+
+        Ok(())
+    }
+
+    fn load_or_create_fee_schedule<'a>(
+        payer_info: &AccountInfo<'a>,
+        fee_schedule_account_info: &AccountInfo<'a>,
+        program_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        rent_account_info: &AccountInfo<'a>,
+        program_id: &Pubkey,
+    ) -> Result<FeeScheduleState, ProgramError> {
+        if !(program_info.key.eq(program_id)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (fee_schedule_account_pubkey, nonce1) = Pubkey::find_program_address(&[b"Platform", b"FeeSchedule"], program_id);
+        if !(fee_schedule_account_info.key.eq(&fee_schedule_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !fee_schedule_account_info.data_is_empty() {
+            return validation::load_fee_schedule(fee_schedule_account_info, program_id);
+        }
+
+        let rent = &Rent::from_account_info(rent_account_info)?;
+        let required_balance = rent.minimum_balance(state::FEESCHEDULESTATE);
+        let create_fee_schedule_account_ix = system_instruction::create_account(
+            payer_info.key, 
+            &fee_schedule_account_pubkey, 
+            required_balance, 
+            state::FEESCHEDULESTATE as u64, 
+            program_id
+        );
+        invoke_signed(
+            &create_fee_schedule_account_ix,
+            &[
+                payer_info.clone(),
+                fee_schedule_account_info.clone(),
+                system_program_info.clone(),
+                program_info.clone(),
+            ],
+            &[&[&b"Platform"[..], &b"FeeSchedule"[..], &[nonce1]]],
+        )?;
+
+        Ok(FeeScheduleState{
+            is_initialized: true,
+            ..FeeScheduleState::default()
+        })
+    }
+
+    /// Picks the most specific fee for a trade: a verified collection override, then the seller's tier,
+    /// then the global platform fee. The fee schedule and trader accounts may be left uninitialized, and NFTs
+    /// without a metadata account, like compressed ones, skip the collection override
+    fn resolve_platform_fee(
+        platform_state: &PlatformState,
+        fee_schedule_account_info: &AccountInfo,
+        metadata_account_info: Option<&AccountInfo>,
+        trader_state_account_info: &AccountInfo,
+        mint: &Pubkey,
+        trader: &Pubkey,
+        program_id: &Pubkey,
+    ) -> Result<u64, ProgramError> {
+        let (fee_schedule_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"FeeSchedule"], program_id);
+        if !(fee_schedule_account_info.key.eq(&fee_schedule_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if fee_schedule_account_info.data_is_empty() {
+            return Ok(platform_state.platform_fee);
+        }
+        let fee_schedule = validation::load_fee_schedule(fee_schedule_account_info, program_id)?;
+
+        if let Some(metadata_account_info) = metadata_account_info {
+            if let Some(metadata) = Metadata::load(metadata_account_info, mint)? {
+                if let Some(platform_fee) = metadata.verified_collection.and_then(|collection| fee_schedule.collection_fee(&collection)) {
+                    return Ok(platform_fee);
+                }
+            }
+        }
+
+        let (trader_state_account_pubkey, _) = Pubkey::find_program_address(&[
+            trader.as_ref(),
+            b"Trader",
+            b"State"
+            ],
+            program_id
+        );
+        if !(trader_state_account_info.key.eq(&trader_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !trader_state_account_info.data_is_empty() {
+            let trader_state = validation::load_trader_state(trader_state_account_info, program_id)?;
+            if let Some(platform_fee) = fee_schedule.tier_fee(trader_state.tier) {
+                return Ok(platform_fee);
+            }
+        }
+
+        Ok(platform_state.platform_fee)
+    }
+
+    /// Rejects a trade whose price is not the one the taker agreed to, or that charges them more of the platform
+    /// fee than they agreed to bear, so a pending `AcceptListing` or `AcceptBid` pays or earns exactly what the taker
+    /// signed for however the price, the fee rate or the fee bearer change under it
+    fn check_slippage(price: u64, taker_fee: u64, slippage: Option<(u64, u64)>) -> ProgramResult {
+        if let Some((expected_price, max_fee)) = slippage {
+            if price != expected_price {
+                return Err(NFTError::PriceMismatch.into());
+            }
+            if taker_fee > max_fee {
+                return Err(NFTError::FeeAboveMaximum.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// The buyer's part of a trade's fee, capped at what the bid escrowed beyond the price and its bond. `Bid`
+    /// escrows the buyer fee at the platform-wide rate since the seller, whose tier sets the trade's rate, is not
+    /// known yet. The part of a higher fee the bid did not escrow is waived
+    fn escrowed_buyer_fee(bid_state: &BidEscrowState, price: u64, buyer_fee: u64, escrow_bid_vault_account_info: &AccountInfo) -> Result<u64, ProgramError> {
+        let escrowed = escrow_bid_vault_account_info.lamports().saturating_sub(Rent::get()?.minimum_balance(0));
+        let escrowed_buyer_fee = escrowed.checked_sub(price + bid_state.bond).ok_or(NFTError::BidDoesNotCoverFee)?;
+        Ok(buyer_fee.min(escrowed_buyer_fee))
+    }
+
+    /// Reads the optional referrer trailing the accounts of a trade. The parties to the trade cannot refer it to themselves
+    fn next_referrer_account_info<'a, 'b>(account_info_iter: &mut Iter<'a, AccountInfo<'b>>, parties: &[&Pubkey]) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+        let referrer_account_info = next_account_info(account_info_iter).ok();
+        if matches!(referrer_account_info, Some(referrer_account_info) if parties.contains(&referrer_account_info.key)) {
+            return Err(NFTError::InvalidReferrer.into());
+        }
+        Ok(referrer_account_info)
+    }
+
+    /// The part of the platform fee owed to the referrer of a trade, nothing when the trade has no referrer
+    fn referral_share(platform_state: &PlatformState, fees_to_platform: u64, referrer_account_info: Option<&AccountInfo>) -> u64 {
+        match referrer_account_info {
+            Some(_) => state::calculate_fee(fees_to_platform, platform_state.referral_fee),
+            None => 0,
+        }
+    }
+
+    fn log_referrer(referrer_account_info: Option<&AccountInfo>) -> String {
+        match referrer_account_info {
+            Some(referrer_account_info) => format!("\"{}\"", referrer_account_info.key),
+            None => String::from("null"),
+        }
+    }
+
+    fn assert_platform_authority<'a>(
+        signer_info: &AccountInfo<'a>,
+        state_account_info: &AccountInfo<'a>,
+        program_id: &Pubkey,
+    ) -> Result<PlatformState, ProgramError> {
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let state_info = validation::load_platform_state(state_account_info, program_id)?;
+        if !state_info.authority.eq(signer_info.key) {
+            return Err(NFTError::InvalidAuthority.into()); 
+        }
+
+        Ok(state_info)
+    }
+
+    /// Checks the accounts every listing purchase shares: the platform vault, the token program and the system program
+    fn check_sale_accounts(
+        vault_account_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+        system_program_info: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let (vault_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"Vault"], program_id);
+        if !(vault_account_info.key.eq(&vault_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !token::is_token_program(token_program_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `buyer_info` can take a listing and prices it without moving anything. Returns the listing, the
+    /// platform fee of the sale and the bump of the listing's escrow state
+    #[allow(clippy::too_many_arguments)]
+    fn check_listing_sale(
+        buyer_info: &AccountInfo,
+        listing: &ListingSale,
+        platform_state: &PlatformState,
+        fee_schedule_account_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+        list_id: u64,
+        programmable: bool,
+        program_id: &Pubkey,
+    ) -> Result<(ListEscrowState, u64, u8), ProgramError> {
+        let token_account_data = token::unpack_account(listing.token_account)?;
+        if !(token_account_data.owner.eq(buyer_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(listing.mint.owner.eq(token_program_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(token_account_data.mint.eq(listing.mint.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (escrow_list_state_account_pubkey, nonce) = Pubkey::find_program_address(&[
+            listing.mint.key.as_ref(),
+            listing.lister.key.as_ref(),
+            &list_id.to_be_bytes(),
+            b"List",
+            b"State"
+            ],
+            program_id
+        );
+        if !(listing.escrow_state.key.eq(&escrow_list_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let escrow_list_vault_account_pubkey = Self::find_list_vault_address(
+            listing.mint.key,
+            listing.lister.key,
+            list_id,
+            &escrow_list_state_account_pubkey,
+            programmable,
+            program_id
+        );
+        if !(listing.escrow_vault.key.eq(&escrow_list_vault_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let list_state = validation::load_list_state(listing.escrow_state, program_id)?;
+        if !list_state.lister.eq(listing.lister.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if list_state.success {
+            return Err(NFTError::ListingAlreadyFullfilled.into());
+        }
+        if !list_state.can_be_bought_by(buyer_info.key) {
+            return Err(NFTError::ListingReserved.into());
+        }
+
+        let platform_fee = Self::resolve_platform_fee(
+            platform_state,
+            fee_schedule_account_info,
+            Some(listing.metadata),
+            listing.trader_state,
+            listing.mint.key,
+            listing.lister.key,
+            program_id
+        )?;
+
+        Ok((list_state, platform_fee, nonce))
+    }
+
+    /// Settles a listing that passed `check_listing_sale`: the NFT goes to the buyer, the buyer pays the lister, the
+    /// platform and the referrer, and the listing is marked sold. Returns what the buyer paid
+    #[allow(clippy::too_many_arguments)]
+    fn settle_listing_sale<'a>(
+        buyer_info: &AccountInfo<'a>,
+        listing: &ListingSale<'_, 'a>,
+        vault_account_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        programmable_accounts: Option<&ProgrammableAccounts<'_, 'a>>,
+        referrer_account_info: Option<&AccountInfo<'a>>,
+        platform_state: &PlatformState,
+        mut list_state: ListEscrowState,
+        platform_fee: u64,
+        nonce: u8,
+        program_id: &Pubkey,
+    ) -> Result<u64, ProgramError> {
+        let list_id = list_state.list_id;
+        Self::release_list_escrow_vault(
+            programmable_accounts,
+            &NftTransfer {
+                source: listing.escrow_vault,
+                owner: listing.escrow_state,
+                destination: listing.token_account,
+                mint: listing.mint,
+                token_program: token_program_info,
+            },
+            buyer_info,
+            buyer_info,
+            &[
+                listing.mint.key.as_ref(),
+                listing.lister.key.as_ref(),
+                &list_id.to_be_bytes()[..],
+                &b"List"[..],
+                &b"State"[..],
+                &[nonce]
+            ]
+        )?;
+
+        let (fees_to_platform, buyer_fee, fees_to_referrer) = Self::pay_for_sale(
+            buyer_info,
+            listing.lister,
+            vault_account_info,
+            system_program_info,
+            referrer_account_info,
+            platform_state,
+            list_state.amount,
+            platform_fee
+        )?;
+
+        list_state.success = true;
+        list_state.successful_buyer = *buyer_info.key;
+
+        ListEscrowState::pack(
+            list_state,
+            &mut listing.escrow_state.data.borrow_mut()
+        )?;
+
+        let lamports = listing.escrow_state.lamports();
+        **listing.escrow_state.try_borrow_mut_lamports()? = 0;
+        **listing.lister.try_borrow_mut_lamports()? += lamports;
+
+        let sale_count = Self::record_sale(listing.mint_state, listing.mint.key, program_id)?;
+        Self::update_order_book(listing.order_book, listing.mint.key, program_id, |order_book| {
+            order_book.remove_listing(listing.lister.key, list_id)
+        })?;
+
+        msg!("{{action: \"AcceptListing\", bidder: \"{}\", lister: \"{}\", list_id: {}, amount: {}, mint: \"{}\", sale_count: {}, platform_fee: {}, fee: {}, buyer_fee: {}, referrer: {}, referral_fee: {}}}", buyer_info.key, listing.lister.key, list_id, list_state.amount, listing.mint.key, sale_count, platform_fee, fees_to_platform, buyer_fee, Self::log_referrer(referrer_account_info), fees_to_referrer);
+
+        Ok(list_state.amount + buyer_fee)
+    }
+
+    /// Pays for a sale out of `buyer_info`: the price less the seller's share of the platform fee to the lister, and
+    /// the platform fee split between the platform vault and the referrer. Returns the platform fee, the buyer's
+    /// share of it and the referrer's share of it
+    #[allow(clippy::too_many_arguments)]
+    fn pay_for_sale<'a>(
+        buyer_info: &AccountInfo<'a>,
+        lister_info: &AccountInfo<'a>,
+        vault_account_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        referrer_account_info: Option<&AccountInfo<'a>>,
+        platform_state: &PlatformState,
+        amount: u64,
+        platform_fee: u64,
+    ) -> Result<(u64, u64, u64), ProgramError> {
+        let fees_to_platform = state::calculate_fee(amount, platform_fee);
+        let (seller_fee, buyer_fee) = platform_state.fee_bearer.split(fees_to_platform);
+        let amount_after_fees = amount - seller_fee;
+        let fees_to_referrer = Self::referral_share(platform_state, fees_to_platform, referrer_account_info);
+
+        let transfer_lamports_lister_ix = system_instruction::transfer(buyer_info.key, lister_info.key, amount_after_fees);
+        invoke(
+            &transfer_lamports_lister_ix,
+            &[
+                buyer_info.clone(),
+                lister_info.clone(),
+                system_program_info.clone()
+            ]
+        )?;
+        let transfer_lamports_platform_ix = system_instruction::transfer(buyer_info.key, vault_account_info.key, fees_to_platform - fees_to_referrer);
+        invoke(
+            &transfer_lamports_platform_ix,
+            &[
+                buyer_info.clone(),
+                vault_account_info.clone(),
+                system_program_info.clone()
+            ]
+        )?;
+        if let Some(referrer_account_info) = referrer_account_info {
+            let transfer_lamports_referrer_ix = system_instruction::transfer(buyer_info.key, referrer_account_info.key, fees_to_referrer);
+            invoke(
+                &transfer_lamports_referrer_ix,
+                &[
+                    buyer_info.clone(),
+                    referrer_account_info.clone(),
+                    system_program_info.clone()
+                ]
+            )?;
+        }
+
+        Ok((fees_to_platform, buyer_fee, fees_to_referrer))
+    }
+
+    /// Creates the per-mint state on the first listing of a mint. It outlives individual listings, so only that
+    /// first listing pays for it
+    fn create_mint_state<'a>(
+        payer_info: &AccountInfo<'a>,
+        mint_state_account_info: &AccountInfo<'a>,
+        mint: &Pubkey,
+        system_program_info: &AccountInfo<'a>,
+        rent: &Rent,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let (mint_state_account_pubkey, nonce) = Pubkey::find_program_address(&[
+            mint.as_ref(),
+            b"Mint",
+            b"State"
+            ],
+            program_id
+        );
+        if !(mint_state_account_info.key.eq(&mint_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !mint_state_account_info.data_is_empty() {
+            return Ok(());
+        }
+
+        let required_balance = rent.minimum_balance(state::MINTSTATE);
+        let create_mint_state_account_ix = system_instruction::create_account(
+            payer_info.key,
+            &mint_state_account_pubkey,
+            required_balance,
+            state::MINTSTATE as u64,
+            program_id
+        );
+        invoke_signed(
+            &create_mint_state_account_ix,
+            &[
+                payer_info.clone(),
+                mint_state_account_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[&[
+                mint.as_ref(),
+                &b"Mint"[..],
+                &b"State"[..],
+                &[nonce]
+            ]],
+        )?;
+
+        MintState::pack(
+            MintState{
+                is_initialized: true,
+                mint: *mint,
+                sale_count: 0
+            },
+            &mut mint_state_account_info.data.borrow_mut()
+        )
+    }
+
+    /// Loads the order book of `mint`, creating it on the first order of the mint
+    fn load_or_create_order_book<'a>(
+        payer_info: &AccountInfo<'a>,
+        order_book_account_info: &AccountInfo<'a>,
+        mint: &Pubkey,
+        system_program_info: &AccountInfo<'a>,
+        rent: &Rent,
+        program_id: &Pubkey,
+    ) -> Result<OrderBookState, ProgramError> {
+        let (order_book_account_pubkey, nonce) = Pubkey::find_program_address(&[
+            mint.as_ref(),
+            b"OrderBook",
+            b"State"
+            ],
+            program_id
+        );
+        if !(order_book_account_info.key.eq(&order_book_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !order_book_account_info.data_is_empty() {
+            return validation::load_order_book(order_book_account_info, program_id);
+        }
+
+        let required_balance = rent.minimum_balance(state::ORDERBOOKSTATE);
+        let create_order_book_account_ix = system_instruction::create_account(
+            payer_info.key,
+            &order_book_account_pubkey,
+            required_balance,
+            state::ORDERBOOKSTATE as u64,
+            program_id
+        );
+        invoke_signed(
+            &create_order_book_account_ix,
+            &[
+                payer_info.clone(),
+                order_book_account_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[&[
+                mint.as_ref(),
+                &b"OrderBook"[..],
+                &b"State"[..],
+                &[nonce]
+            ]],
+        )?;
+
+        Ok(OrderBookState{
+            is_initialized: true,
+            mint: *mint,
+            ..OrderBookState::default()
+        })
+    }
+
+    /// Loads the order nonce state of `owner`, creating it on their first cancellation
+    fn load_or_create_order_nonce_state<'a>(
+        owner_info: &AccountInfo<'a>,
+        order_nonce_account_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        rent: &Rent,
+        program_id: &Pubkey,
+    ) -> Result<OrderNonceState, ProgramError> {
+        let (order_nonce_account_pubkey, nonce) = Pubkey::find_program_address(&[
+            owner_info.key.as_ref(),
+            b"OrderNonce",
+            b"State"
+            ],
+            program_id
+        );
+        if !(order_nonce_account_info.key.eq(&order_nonce_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !order_nonce_account_info.data_is_empty() {
+            return validation::load_order_nonce_state(order_nonce_account_info, program_id);
+        }
+
+        if !(system_program_info.key.eq(&system_program::id())) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let required_balance = rent.minimum_balance(state::ORDERNONCESTATE);
+        let create_order_nonce_account_ix = system_instruction::create_account(
+            owner_info.key,
+            &order_nonce_account_pubkey,
+            required_balance,
+            state::ORDERNONCESTATE as u64,
+            program_id
+        );
+        invoke_signed(
+            &create_order_nonce_account_ix,
+            &[
+                owner_info.clone(),
+                order_nonce_account_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[&[
+                owner_info.key.as_ref(),
+                &b"OrderNonce"[..],
+                &b"State"[..],
+                &[nonce]
+            ]],
+        )?;
+
+        Ok(OrderNonceState{
+            is_initialized: true,
+            owner: *owner_info.key,
+            ..OrderNonceState::default()
+        })
+    }
+
+    /// Fails when `owner` cancelled the order of `nonce` and `order_hash`. An owner who never cancelled anything
+    /// has no order nonce state yet
+    fn assert_order_not_cancelled(
+        order_nonce_account_info: &AccountInfo,
+        owner: &Pubkey,
+        nonce: u64,
+        order_hash: &Hash,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let (order_nonce_account_pubkey, _) = Pubkey::find_program_address(&[
+            owner.as_ref(),
+            b"OrderNonce",
+            b"State"
+            ],
+            program_id
+        );
+        if !(order_nonce_account_info.key.eq(&order_nonce_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if order_nonce_account_info.data_is_empty() {
+            return Ok(());
+        }
+
+        let order_nonce_state = validation::load_order_nonce_state(order_nonce_account_info, program_id)?;
+        if order_nonce_state.is_cancelled(nonce, order_hash) {
+            return Err(NFTError::OrderCancelled.into());
+        }
+        Ok(())
+    }
+
+    /// Applies `update` to the order book of `mint`. Orders placed before the mint had a book were never indexed,
+    /// so a mint without a book is left alone
+    fn update_order_book(
+        order_book_account_info: &AccountInfo,
+        mint: &Pubkey,
+        program_id: &Pubkey,
+        update: impl FnOnce(&mut OrderBookState),
+    ) -> ProgramResult {
+        let (order_book_account_pubkey, _) = Pubkey::find_program_address(&[
+            mint.as_ref(),
+            b"OrderBook",
+            b"State"
+            ],
+            program_id
+        );
+        if !(order_book_account_info.key.eq(&order_book_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if order_book_account_info.data_is_empty() {
+            return Ok(());
+        }
+
+        let mut order_book = validation::load_order_book(order_book_account_info, program_id)?;
+        update(&mut order_book);
+        OrderBookState::pack(order_book, &mut order_book_account_info.data.borrow_mut())
+    }
+
+    fn record_sale(
+        mint_state_account_info: &AccountInfo,
+        mint: &Pubkey,
+        program_id: &Pubkey,
+    ) -> Result<u64, ProgramError> {
+        let (mint_state_account_pubkey, _) = Pubkey::find_program_address(&[
+            mint.as_ref(),
+            b"Mint",
+            b"State"
+            ],
+            program_id
+        );
+        if !(mint_state_account_info.key.eq(&mint_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut mint_state = validation::load_mint_state(mint_state_account_info, program_id)?;
+        mint_state.sale_count += 1;
+        MintState::pack(mint_state, &mut mint_state_account_info.data.borrow_mut())?;
+
+        Ok(mint_state.sale_count)
+    }
+
+    /// Creates the destination of `transfer` as a token account at the `vault_seeds` PDA owned by `authority`, and
+    /// moves the NFT of its owner, who pays for the vault, into it
+    fn create_escrow_vault<'a>(
+        transfer: &NftTransfer<'_, 'a>,
+        authority: &Pubkey,
+        vault_seeds: &[&[u8]],
+        system_program_info: &AccountInfo<'a>,
+        rent_account_info: &AccountInfo<'a>,
+        rent: &Rent,
+    ) -> ProgramResult {
+        let NftTransfer {
+            source: token_account_info,
+            owner: payer_info,
+            destination: escrow_vault_account_info,
+            mint: mint_account_info,
+            token_program: token_program_info,
+        } = *transfer;
+        token::check_mint(mint_account_info)?;
+
+        let required_balance = rent.minimum_balance(spl_token::state::Account::LEN);
+        let create_vault_account_ix = system_instruction::create_account(payer_info.key, escrow_vault_account_info.key, required_balance, spl_token::state::Account::LEN as u64, token_program_info.key);
+        invoke_signed(
+            &create_vault_account_ix,
+            &[
+                payer_info.clone(),
+                escrow_vault_account_info.clone(),
+                system_program_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[vault_seeds],
+        )?;
+
+        let initialize_vault_account_ix = token::initialize_account(token_program_info.key, escrow_vault_account_info.key, mint_account_info.key, authority);
+        invoke(
+            &initialize_vault_account_ix,
+            &[
+                token_program_info.clone(),
+                escrow_vault_account_info.clone(),
+                mint_account_info.clone(),
+                rent_account_info.clone()
+            ],
+        )?;
+
+        let transfer_token_ix = token::transfer_checked(token_program_info.key, token_account_info.key, mint_account_info.key, escrow_vault_account_info.key, payer_info.key);
+        invoke(
+            &transfer_token_ix,
+            &[
+                token_program_info.clone(),
+                token_account_info.clone(),
+                mint_account_info.clone(),
+                escrow_vault_account_info.clone(),
+                payer_info.clone()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Moves the NFT out of a vault owned by the `authority_seeds` PDA and closes the vault into the authority
+    fn release_escrow_vault<'a>(
+        escrow_vault_account_info: &AccountInfo<'a>,
+        mint_account_info: &AccountInfo<'a>,
+        destination_account_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        authority_seeds: &[&[u8]],
+        token_program_info: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        let nft_transfer_ix = token::transfer_checked(token_program_info.key, escrow_vault_account_info.key, mint_account_info.key, destination_account_info.key, authority_info.key);
+        invoke_signed(
+            &nft_transfer_ix,
+            &[
+                token_program_info.clone(),
+                escrow_vault_account_info.clone(),
+                mint_account_info.clone(),
+                destination_account_info.clone(),
+                authority_info.clone(),
+            ],
+            &[authority_seeds]
+        )?;
+
+        let close_ix = token::close_account(token_program_info.key, escrow_vault_account_info.key, authority_info.key, authority_info.key);
+        invoke_signed(
+            &close_ix,
+            &[
+                token_program_info.clone(),
+                escrow_vault_account_info.clone(),
+                authority_info.clone(),
+            ],
+            &[authority_seeds],
+        )?;
+
+        Ok(())
+    }
+
+    /// Listed NFTs are escrowed in a program owned vault, programmable NFTs in the escrow state's associated token
+    /// account
+    fn find_list_vault_address(
+        mint: &Pubkey,
+        lister: &Pubkey,
+        list_id: u64,
+        escrow_state: &Pubkey,
+        programmable: bool,
+        program_id: &Pubkey,
+    ) -> Pubkey {
+        if programmable {
+            return metadata::find_programmable_vault_address(escrow_state, mint);
+        }
+        Pubkey::find_program_address(&[
+            mint.as_ref(),
+            lister.as_ref(),
+            &list_id.to_be_bytes(),
+            b"List",
+            b"Vault"
+            ],
+            program_id
+        ).0
+    }
+
+    /// Moves a listed NFT from its escrow vault, signed by the escrow state. Only the
+    /// metadata program can close the vault of a programmable NFT, so that vault is left open
+    fn release_list_escrow_vault<'a>(
+        programmable_accounts: Option<&ProgrammableAccounts<'_, 'a>>,
+        transfer: &NftTransfer<'_, 'a>,
+        token_owner_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        state_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        match programmable_accounts {
+            Some(programmable_accounts) => Self::transfer_programmable(
+                programmable_accounts,
+                transfer,
+                token_owner_info,
+                payer_info,
+                &[state_seeds]
+            ),
+            None => Self::release_escrow_vault(
+                transfer.source,
+                transfer.mint,
+                transfer.destination,
+                transfer.owner,
+                state_seeds,
+                transfer.token_program
+            ),
+        }
+    }
+
+    /// Moves a programmable NFT owned by `token_owner_info` through the metadata program's `Transfer`, which creates
+    /// the destination token account and token record when missing
+    fn transfer_programmable<'a>(
+        programmable_accounts: &ProgrammableAccounts<'_, 'a>,
+        transfer: &NftTransfer<'_, 'a>,
+        destination_owner_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let NftTransfer {
+            source: token_account_info,
+            owner: token_owner_info,
+            destination: destination_account_info,
+            mint: mint_account_info,
+            token_program: token_program_info,
+        } = *transfer;
+        programmable_accounts.check(mint_account_info.key, token_account_info.key, destination_account_info.key)?;
+
+        let transfer_ix = metadata::transfer(
+            programmable_accounts,
+            token_account_info.key,
+            token_owner_info.key,
+            destination_account_info.key,
+            destination_owner_info.key,
+            mint_account_info.key,
+            token_owner_info.key,
+            payer_info.key,
+            token_program_info.key
+        );
+        invoke_signed(
+            &transfer_ix,
+            &[
+                programmable_accounts.metadata_program.clone(),
+                token_account_info.clone(),
+                token_owner_info.clone(),
+                destination_account_info.clone(),
+                destination_owner_info.clone(),
+                mint_account_info.clone(),
+                programmable_accounts.metadata.clone(),
+                programmable_accounts.edition.clone(),
+                programmable_accounts.source_token_record.clone(),
+                programmable_accounts.destination_token_record.clone(),
+                payer_info.clone(),
+                programmable_accounts.system_program.clone(),
+                programmable_accounts.sysvar_instructions.clone(),
+                token_program_info.clone(),
+                programmable_accounts.associated_token_program.clone(),
+                programmable_accounts.authorization_rules_program.clone(),
+                programmable_accounts.authorization_rules.clone(),
+            ],
+            signers_seeds
+        )
+    }
+
+    /// Checks that a compressed listing state belongs to `lister_info`, `list_id` and the given tree, returning the
+    /// bump of its address
+    fn assert_compressed_list_state(
+        list_state: &CompressedListEscrowState,
+        escrow_state_account_info: &AccountInfo,
+        lister_info: &AccountInfo,
+        list_id: u64,
+        tree_accounts: &TreeAccounts,
+        program_id: &Pubkey,
+    ) -> Result<u8, ProgramError> {
+        let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            list_state.asset_id.as_ref(),
+            lister_info.key.as_ref(),
+            &list_id.to_be_bytes(),
+            b"CompressedList",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_state_account_info.key.eq(&escrow_state_account_pubkey))
+            || !list_state.lister.eq(lister_info.key)
+            || !list_state.merkle_tree.eq(tree_accounts.merkle_tree.key)
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(nonce1)
+    }
+
+    /// Moves the compressed NFT of a listing from `leaf_owner_info` to `new_leaf_owner_info` through Bubblegum.
+    /// Bubblegum hashes the leaf from the listing's data hashes and checks the proof against the roots the tree
+    /// account holds, so the transfer fails unless `leaf_owner_info` owns the leaf. It resets the delegate to the
+    /// new owner, so the leaf always has its owner as delegate
+    fn transfer_compressed<'a>(
+        tree_accounts: &TreeAccounts<'_, 'a>,
+        list_state: &CompressedListEscrowState,
+        root: &[u8; 32],
+        leaf_owner_info: &AccountInfo<'a>,
+        new_leaf_owner_info: &AccountInfo<'a>,
+        proof: &[AccountInfo<'a>],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let transfer_ix = compression::transfer(
+            tree_accounts.tree_authority.key,
+            leaf_owner_info.key,
+            leaf_owner_info.key,
+            new_leaf_owner_info.key,
+            tree_accounts.merkle_tree.key,
+            root,
+            &list_state.data_hash,
+            &list_state.creator_hash,
+            list_state.nonce,
+            list_state.index,
+            proof
+        );
+        let mut transfer_accounts = vec![
+            tree_accounts.bubblegum_program.clone(),
+            tree_accounts.tree_authority.clone(),
+            leaf_owner_info.clone(),
+            new_leaf_owner_info.clone(),
+            tree_accounts.merkle_tree.clone(),
+            tree_accounts.log_wrapper.clone(),
+            tree_accounts.compression_program.clone(),
+            tree_accounts.system_program.clone(),
+        ];
+        transfer_accounts.extend(proof.iter().cloned());
+        invoke_signed(&transfer_ix, &transfer_accounts, signers_seeds)
+    }
+
+    /// Checks that a delegated listing state belongs to `lister_info`, `list_id`, the mint and the lister's token
+    /// account, returning the bump of its address
+    fn assert_delegated_list_state(
+        list_state: &DelegatedListEscrowState,
+        escrow_state_account_info: &AccountInfo,
+        lister_info: &AccountInfo,
+        token_account_info: &AccountInfo,
+        mint_account_info: &AccountInfo,
+        list_id: u64,
+        program_id: &Pubkey,
+    ) -> Result<u8, ProgramError> {
+        let (escrow_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            mint_account_info.key.as_ref(),
+            lister_info.key.as_ref(),
+            &list_id.to_be_bytes(),
+            b"DelegatedList",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_state_account_info.key.eq(&escrow_state_account_pubkey))
+            || !list_state.lister.eq(lister_info.key)
+            || !list_state.mint.eq(mint_account_info.key)
+            || !list_state.token_account.eq(token_account_info.key)
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(nonce1)
+    }
+
+    /// Whether a delegated listing can freeze the lister's token account. The metadata program only freezes through
+    /// the master edition, so this needs an original token program mint whose freeze authority is its edition
+    fn can_freeze_delegated(
+        mint_account_info: &AccountInfo,
+        edition_account_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+    ) -> Result<bool, ProgramError> {
+        if !edition_account_info.key.eq(&metadata::find_master_edition_address(mint_account_info.key).0) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !token_program_info.key.eq(&spl_token::id()) {
+            return Ok(false);
+        }
+        let mint = spl_token::state::Mint::unpack(&mint_account_info.data.borrow())?;
+        Ok(mint.freeze_authority == COption::Some(*edition_account_info.key))
+    }
+
+    /// Freezes or thaws the lister's token account through the metadata program, signed by the listing state as the
+    /// account's delegate
+    #[allow(clippy::too_many_arguments)]
+    fn invoke_delegated_account<'a>(
+        instruction: Instruction,
+        escrow_state_account_info: &AccountInfo<'a>,
+        token_account_info: &AccountInfo<'a>,
+        edition_account_info: &AccountInfo<'a>,
+        mint_account_info: &AccountInfo<'a>,
+        metadata_program_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        if !metadata_program_info.key.eq(&metadata::id()) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        invoke_signed(
+            &instruction,
+            &[
+                metadata_program_info.clone(),
+                escrow_state_account_info.clone(),
+                token_account_info.clone(),
+                edition_account_info.clone(),
+                mint_account_info.clone(),
+                token_program_info.clone()
+            ],
+            &[signer_seeds],
+        )
+    }
+
+    /// Moves a bundled NFT out of its vault, signed by the bundle's escrow state
+    fn release_bundled_nft(
+        mint: &Pubkey,
+        transfer: &NftTransfer,
+        state_seeds: &[&[u8]],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if !transfer.mint.key.eq(mint) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (escrow_vault_account_pubkey, _) = Pubkey::find_program_address(&[
+            transfer.owner.key.as_ref(),
+            transfer.mint.key.as_ref(),
+            b"Bundle",
+            b"Vault"
+            ],
+            program_id
+        );
+        if !(transfer.source.key.eq(&escrow_vault_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Self::release_escrow_vault(
+            transfer.source,
+            transfer.mint,
+            transfer.destination,
+            transfer.owner,
+            state_seeds,
+            transfer.token_program
+        )
+    }
+
+    fn refund_bid_escrow<'a>(
+        mint: &Pubkey,
+        bid_id: u64,
+        bidder_account_info: &AccountInfo<'a>,
+        escrow_bid_state_account_info: &AccountInfo<'a>,
+        escrow_bid_vault_account_info: &AccountInfo<'a>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        Self::assert_bid_escrow(mint, bid_id, bidder_account_info, escrow_bid_state_account_info, escrow_bid_vault_account_info, program_id)?;
+        Self::close_bid_escrow(bidder_account_info, escrow_bid_state_account_info, escrow_bid_vault_account_info)
+    }
+
+    /// Checks that the state and vault accounts are the escrow of `bidder_account_info`'s bid `bid_id` on `mint`
+    fn assert_bid_escrow(
+        mint: &Pubkey,
+        bid_id: u64,
+        bidder_account_info: &AccountInfo,
+        escrow_bid_state_account_info: &AccountInfo,
+        escrow_bid_vault_account_info: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let (escrow_bid_state_account_pubkey, _) = Pubkey::find_program_address(&[
+            mint.as_ref(),
+            bidder_account_info.key.as_ref(),
+            &bid_id.to_be_bytes(),
+            b"Bid",
             b"State"
             ],
             program_id
@@ -1172,8 +4711,9 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
         let (escrow_bid_vault_account_pubkey, _) = Pubkey::find_program_address(&[
-            mint_account_info.key.as_ref(),
+            mint.as_ref(),
             bidder_account_info.key.as_ref(),
+            &bid_id.to_be_bytes(),
             b"Bid",
             b"Vault"
             ],
@@ -1183,6 +4723,15 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
 
+        Ok(())
+    }
+
+    /// Returns everything left in a bid's escrow accounts to the bidder
+    fn close_bid_escrow(
+        bidder_account_info: &AccountInfo,
+        escrow_bid_state_account_info: &AccountInfo,
+        escrow_bid_vault_account_info: &AccountInfo,
+    ) -> ProgramResult {
         let lamports = escrow_bid_state_account_info.lamports() + escrow_bid_vault_account_info.lamports();
         **escrow_bid_state_account_info.try_borrow_mut_lamports()? = 0;
         **escrow_bid_vault_account_info.try_borrow_mut_lamports()? = 0;
@@ -1190,4 +4739,93 @@ impl Processor {
 
         Ok(())
     }
+
+    /// Returns the NFT of an unsold listing from its vault, the source of `transfer`, to the lister's token account.
+    /// A programmable NFT moves through the metadata program, with `payer_info` paying for its token record
+    fn refund_list_escrow<'a>(
+        list_id: u64,
+        lister_account_info: &AccountInfo<'a>,
+        transfer: &NftTransfer<'_, 'a>,
+        programmable_accounts: Option<&ProgrammableAccounts<'_, 'a>>,
+        payer_info: &AccountInfo<'a>,
+        program_id: &Pubkey,
+    ) -> Result<ListEscrowState, ProgramError> {
+        let NftTransfer {
+            source: escrow_list_vault_account_info,
+            owner: escrow_list_state_account_info,
+            destination: token_account_info,
+            mint: mint_account_info,
+            token_program: token_program_info,
+        } = *transfer;
+        if !(mint_account_info.owner.eq(token_program_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !token::is_token_program(token_program_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let token_account_data = token::unpack_account(token_account_info)?;
+        if !(token_account_data.owner.eq(lister_account_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !(token_account_data.mint.eq(mint_account_info.key)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (escrow_list_state_account_pubkey, nonce1) = Pubkey::find_program_address(&[
+            mint_account_info.key.as_ref(),
+            lister_account_info.key.as_ref(),
+            &list_id.to_be_bytes(),
+            b"List",
+            b"State"
+            ],
+            program_id
+        );
+        if !(escrow_list_state_account_info.key.eq(&escrow_list_state_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let escrow_list_vault_account_pubkey = Self::find_list_vault_address(
+            mint_account_info.key,
+            lister_account_info.key,
+            list_id,
+            &escrow_list_state_account_pubkey,
+            programmable_accounts.is_some(),
+            program_id
+        );
+        if !(escrow_list_vault_account_info.key.eq(&escrow_list_vault_account_pubkey)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let list_state = validation::load_list_state(escrow_list_state_account_info, program_id)?;
+        if !list_state.lister.eq(lister_account_info.key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // A fulfilled listing's NFT belongs to the buyer, who can still claim it with WithdrawNFTOnSuccess
+        if list_state.success {
+            return Err(NFTError::ListingAlreadyFullfilled.into());
+        }
+
+        Self::release_list_escrow_vault(
+            programmable_accounts,
+            transfer,
+            lister_account_info,
+            payer_info,
+            &[
+                mint_account_info.key.as_ref(),
+                lister_account_info.key.as_ref(),
+                &list_id.to_be_bytes()[..],
+                &b"List"[..],
+                &b"State"[..],
+                &[nonce1]
+            ]
+        )?;
+
+        let lamports = escrow_list_state_account_info.lamports();
+        **escrow_list_state_account_info.try_borrow_mut_lamports()? = 0;
+        **lister_account_info.try_borrow_mut_lamports()? += lamports;
+
+        Ok(list_state)
+    }
 }
@@ -1,4 +1,5 @@
 use solana_program::{
+    hash::Hash,
     program_pack::{IsInitialized, Pack, Sealed},
     program_error::ProgramError,
     pubkey::Pubkey,
@@ -6,16 +7,150 @@ use solana_program::{
 
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 
-pub const STATESIZE: usize = 41usize;
-pub const LISTESCROWSTATE: usize = 105usize;
-pub const BIDESCROWSTATE: usize = 72usize;
+pub const STATESIZE: usize = 74usize;
+pub const LISTESCROWSTATE: usize = 154usize;
+pub const BIDESCROWSTATE: usize = 97usize;
+pub const MINTSTATE: usize = 41usize;
+pub const SWAPESCROWSTATE: usize = 315usize;
+pub const BUNDLEESCROWSTATE: usize = 306usize;
+pub const FEESCHEDULESTATE: usize = 675usize;
+pub const TRADERSTATE: usize = 34usize;
+pub const FEEDISTRIBUTIONSTATE: usize = 274usize;
+pub const COMPRESSEDLISTESCROWSTATE: usize = 189usize;
+pub const DELEGATEDLISTESCROWSTATE: usize = 114usize;
+pub const ORDERBOOKSTATE: usize = 978usize;
+pub const ORDERNONCESTATE: usize = 169usize;
+
+pub const MAX_SWAP_MINTS: usize = 4usize;
+pub const MAX_BUNDLE_MINTS: usize = 8usize;
+pub const MAX_COLLECTION_FEES: usize = 16usize;
+pub const MAX_FEE_TIERS: usize = 4usize;
+pub const MAX_FEE_RECIPIENTS: usize = 8usize;
+pub const MAX_ORDER_BOOK_BIDS: usize = 16usize;
+// Words of the cancelled order bitmap, 1024 bits
+pub const CANCELLED_ORDER_WORDS: usize = 16usize;
+
+// Fees are expressed in billionths of the traded amount
+pub const FEE_DENOMINATOR: u64 = 1000000000u64;
+
+pub fn calculate_fee(amount: u64, fee: u64) -> u64 {
+    ((amount as u128 * fee as u128) / FEE_DENOMINATOR as u128) as u64
+}
+
+// Share of the platform fee paid to whoever cranks MatchOrders, in the same scale as fees
+pub const KEEPER_REWARD: u64 = 100000000u64;
+
+// Fee distribution shares are expressed in basis points and must add up to BPS_DENOMINATOR
+pub const BPS_DENOMINATOR: u16 = 10000u16;
+
+/// Splits `amount` between `recipients` by their basis points. Rounding is always down, and the dust left
+/// over goes to the last recipient so the split adds up to exactly `amount`
+pub fn split_fees(amount: u64, recipients: &[FeeRecipient]) -> Vec<u64> {
+    let mut shares: Vec<u64> = recipients
+        .iter()
+        .map(|recipient| ((amount as u128 * recipient.bps as u128) / BPS_DENOMINATOR as u128) as u64)
+        .collect();
+    let distributed: u64 = shares.iter().sum();
+    if let Some(last) = shares.last_mut() {
+        *last += amount - distributed;
+    }
+    shares
+}
+
+// Conservative compute unit estimates used to stop BulkRefund before the transaction runs out of compute
+pub const DEFAULT_COMPUTE_BUDGET: u64 = 200_000u64;
+pub const BULK_REFUND_BASE_COMPUTE: u64 = 15_000u64;
+pub const BID_REFUND_COMPUTE: u64 = 12_000u64;
+pub const LIST_REFUND_COMPUTE: u64 = 35_000u64;
+pub const PROGRAMMABLE_LIST_REFUND_COMPUTE: u64 = 120_000u64;
+
+/// Every program account starts with a byte naming its type, which doubles as its initialization flag. It stays
+/// zero until the account is initialized, so an account of one type can never be unpacked as another
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccountType {
+    Uninitialized = 0,
+    Platform = 1,
+    ListEscrow = 2,
+    BidEscrow = 3,
+    Mint = 4,
+    SwapEscrow = 5,
+    BundleEscrow = 6,
+    FeeSchedule = 7,
+    Trader = 8,
+    FeeDistribution = 9,
+    CompressedListEscrow = 10,
+    DelegatedListEscrow = 11,
+    OrderBook = 12,
+    OrderNonce = 13,
+}
+
+impl AccountType {
+    pub fn from_u8(account_type: u8) -> Option<Self> {
+        match account_type {
+            0 => Some(AccountType::Uninitialized),
+            1 => Some(AccountType::Platform),
+            2 => Some(AccountType::ListEscrow),
+            3 => Some(AccountType::BidEscrow),
+            4 => Some(AccountType::Mint),
+            5 => Some(AccountType::SwapEscrow),
+            6 => Some(AccountType::BundleEscrow),
+            7 => Some(AccountType::FeeSchedule),
+            8 => Some(AccountType::Trader),
+            9 => Some(AccountType::FeeDistribution),
+            10 => Some(AccountType::CompressedListEscrow),
+            11 => Some(AccountType::DelegatedListEscrow),
+            12 => Some(AccountType::OrderBook),
+            13 => Some(AccountType::OrderNonce),
+            _ => None,
+        }
+    }
+}
+
+/// Who pays the platform fee of a trade
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FeeBearer {
+    #[default]
+    Seller = 0,
+    Buyer = 1,
+    Split = 2,
+}
+
+impl FeeBearer {
+    pub fn from_u8(fee_bearer: u8) -> Option<Self> {
+        match fee_bearer {
+            0 => Some(FeeBearer::Seller),
+            1 => Some(FeeBearer::Buyer),
+            2 => Some(FeeBearer::Split),
+            _ => None,
+        }
+    }
+
+    /// Splits a trade's fee into the part deducted from the seller's proceeds and the part the buyer pays on top
+    /// of the price. An odd lamport of a split fee is charged to the buyer
+    pub fn split(&self, fee: u64) -> (u64, u64) {
+        match self {
+            FeeBearer::Seller => (fee, 0),
+            FeeBearer::Buyer => (0, fee),
+            FeeBearer::Split => (fee / 2, fee - fee / 2),
+        }
+    }
+}
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct PlatformState {
     pub is_initialized: bool,
     pub authority: Pubkey,
-    pub platform_fee: u64
+    pub platform_fee: u64,
+    // Share of the platform fee paid to the referrer of a trade, in the same scale as fees
+    pub referral_fee: u64,
+    pub fee_bearer: FeeBearer,
+    pub min_bid_amount: u64,
+    pub min_list_amount: u64,
+    // Anti-spam deposit every bid escrows on top of its amount, returned unless the authority slashes the bid
+    pub bid_bond: u64
 }
 
 impl Sealed for PlatformState{}
@@ -33,17 +168,23 @@ impl Pack for PlatformState {
         let (
             is_initialized,
             authority,
-            platform_fee
-        ) = array_refs![src, 1, 32, 8];
-        let is_initialized = match is_initialized {
-            [0] => false,
-            [1] => true,
-            _ => return Err(ProgramError::InvalidAccountData),
-        };
+            platform_fee,
+            referral_fee,
+            fee_bearer,
+            min_bid_amount,
+            min_list_amount,
+            bid_bond
+        ) = array_refs![src, 1, 32, 8, 8, 1, 8, 8, 8];
+        let is_initialized = unpack_account_type(is_initialized, AccountType::Platform)?;
         Ok(PlatformState{
             is_initialized,
             authority: Pubkey::new_from_array(*authority),
-            platform_fee: u64::from_be_bytes(*platform_fee)
+            platform_fee: u64::from_be_bytes(*platform_fee),
+            referral_fee: u64::from_be_bytes(*referral_fee),
+            fee_bearer: FeeBearer::from_u8(fee_bearer[0]).ok_or(ProgramError::InvalidAccountData)?,
+            min_bid_amount: u64::from_be_bytes(*min_bid_amount),
+            min_list_amount: u64::from_be_bytes(*min_list_amount),
+            bid_bond: u64::from_be_bytes(*bid_bond)
         })
     }
 
@@ -52,18 +193,33 @@ impl Pack for PlatformState {
         let (
             is_initialized_dst,
             authority_dst,
-            platform_fee_dst
-        ) = mut_array_refs![dst, 1, 32, 8];
+            platform_fee_dst,
+            referral_fee_dst,
+            fee_bearer_dst,
+            min_bid_amount_dst,
+            min_list_amount_dst,
+            bid_bond_dst
+        ) = mut_array_refs![dst, 1, 32, 8, 8, 1, 8, 8, 8];
 
         let PlatformState {
             is_initialized,
             authority,
-            platform_fee
+            platform_fee,
+            referral_fee,
+            fee_bearer,
+            min_bid_amount,
+            min_list_amount,
+            bid_bond
         } = self;
 
-        is_initialized_dst[0] = *is_initialized as u8;
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::Platform);
         authority_dst.copy_from_slice(authority.as_ref());
         *platform_fee_dst = platform_fee.to_be_bytes();
+        *referral_fee_dst = referral_fee.to_be_bytes();
+        fee_bearer_dst[0] = *fee_bearer as u8;
+        *min_bid_amount_dst = min_bid_amount.to_be_bytes();
+        *min_list_amount_dst = min_list_amount.to_be_bytes();
+        *bid_bond_dst = bid_bond.to_be_bytes();
     }
 }
 
@@ -71,108 +227,1166 @@ impl Pack for PlatformState {
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct ListEscrowState {
+    pub is_initialized: bool,
     pub lister: Pubkey,
     pub mint: Pubkey,
     pub amount: u64,
     pub success: bool,
     pub successful_buyer: Pubkey,
+    pub list_id: u64,
+    pub reserved_buyer: Pubkey,
+    // Lowest bid the lister is willing to accept through AcceptBid, zero when any bid will do
+    pub min_bid: u64,
+}
+
+impl ListEscrowState {
+    /// Reserved listings can only be filled by their reserved buyer and are skipped by matching instructions
+    pub fn is_reserved(&self) -> bool {
+        self.reserved_buyer != Pubkey::default()
+    }
+
+    pub fn can_be_bought_by(&self, buyer: &Pubkey) -> bool {
+        !self.is_reserved() || self.reserved_buyer.eq(buyer)
+    }
 }
 
 impl Sealed for ListEscrowState{}
 
+impl IsInitialized for ListEscrowState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
 impl Pack for ListEscrowState {
     const LEN: usize = LISTESCROWSTATE;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, ListEscrowState::LEN];
         let (
+            is_initialized,
             lister,
             mint, 
             amount,
             success,
-            successful_buyer
-        ) = array_refs![src, 32, 32, 8, 1, 32];
+            successful_buyer,
+            list_id,
+            reserved_buyer,
+            min_bid
+        ) = array_refs![src, 1, 32, 32, 8, 1, 32, 8, 32, 8];
+        let is_initialized = unpack_account_type(is_initialized, AccountType::ListEscrow)?;
         let success = match success {
             [0] => false,
             [1] => true,
             _ => return Err(ProgramError::InvalidAccountData),
         };
         Ok(ListEscrowState{
+            is_initialized,
             lister: Pubkey::new_from_array(*lister),
             mint: Pubkey::new_from_array(*mint),
             amount: u64::from_be_bytes(*amount),
             success: success,
             successful_buyer: Pubkey::new_from_array(*successful_buyer),
+            list_id: u64::from_be_bytes(*list_id),
+            reserved_buyer: Pubkey::new_from_array(*reserved_buyer),
+            min_bid: u64::from_be_bytes(*min_bid),
         })
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let dst = array_mut_ref![dst, 0, ListEscrowState::LEN];
         let (
+            is_initialized_dst,
             lister_dst,
             mint_dst,
             amount_dst,
             success_dst,
-            successful_buyer_dst
-        ) = mut_array_refs![dst, 32, 32, 8, 1, 32];
+            successful_buyer_dst,
+            list_id_dst,
+            reserved_buyer_dst,
+            min_bid_dst
+        ) = mut_array_refs![dst, 1, 32, 32, 8, 1, 32, 8, 32, 8];
 
         let ListEscrowState {
+            is_initialized,
             lister,
             mint,
             amount,
             success,
-            successful_buyer
+            successful_buyer,
+            list_id,
+            reserved_buyer,
+            min_bid
         } = self;
 
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::ListEscrow);
         lister_dst.copy_from_slice(lister.as_ref());
         mint_dst.copy_from_slice(mint.as_ref());
         *amount_dst = amount.to_be_bytes();
         success_dst[0] = *success as u8;
         successful_buyer_dst.copy_from_slice(successful_buyer.as_ref());
+        *list_id_dst = list_id.to_be_bytes();
+        reserved_buyer_dst.copy_from_slice(reserved_buyer.as_ref());
+        *min_bid_dst = min_bid.to_be_bytes();
     }
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct BidEscrowState {
+    pub is_initialized: bool,
     pub bidder: Pubkey,
     pub mint: Pubkey,
     pub amount: u64,
+    pub bid_id: u64,
+    pub bond: u64,
+    // Unix timestamp from which the bid can no longer be accepted, zero when it never expires
+    pub expiry: i64,
+}
+
+impl BidEscrowState {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expiry != 0 && now >= self.expiry
+    }
 }
 
 impl Sealed for BidEscrowState{}
 
+impl IsInitialized for BidEscrowState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
 impl Pack for BidEscrowState {
     const LEN: usize = BIDESCROWSTATE;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
         let src = array_ref![src, 0, BidEscrowState::LEN];
         let (
+            is_initialized,
             bidder,
             mint, 
             amount,
-        ) = array_refs![src, 32, 32, 8];
+            bid_id,
+            bond,
+            expiry,
+        ) = array_refs![src, 1, 32, 32, 8, 8, 8, 8];
         Ok(BidEscrowState{
+            is_initialized: unpack_account_type(is_initialized, AccountType::BidEscrow)?,
             bidder: Pubkey::new_from_array(*bidder),
             mint: Pubkey::new_from_array(*mint),
             amount: u64::from_be_bytes(*amount),
+            bid_id: u64::from_be_bytes(*bid_id),
+            bond: u64::from_be_bytes(*bond),
+            expiry: i64::from_be_bytes(*expiry),
         })
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
         let dst = array_mut_ref![dst, 0, BidEscrowState::LEN];
         let (
+            is_initialized_dst,
             bidder_dst,
             mint_dst,
             amount_dst,
-        ) = mut_array_refs![dst, 32, 32, 8];
+            bid_id_dst,
+            bond_dst,
+            expiry_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 8, 8, 8, 8];
 
         let BidEscrowState {
+            is_initialized,
             bidder,
             mint,
             amount,
+            bid_id,
+            bond,
+            expiry,
         } = self;
 
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::BidEscrow);
         bidder_dst.copy_from_slice(bidder.as_ref());
         mint_dst.copy_from_slice(mint.as_ref());
         *amount_dst = amount.to_be_bytes();
+        *bid_id_dst = bid_id.to_be_bytes();
+        *bond_dst = bond.to_be_bytes();
+        *expiry_dst = expiry.to_be_bytes();
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MintState {
+    pub is_initialized: bool,
+    pub mint: Pubkey,
+    pub sale_count: u64,
+}
+
+impl Sealed for MintState{}
+
+impl IsInitialized for MintState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for MintState {
+    const LEN: usize = MINTSTATE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, MintState::LEN];
+        let (
+            is_initialized,
+            mint,
+            sale_count
+        ) = array_refs![src, 1, 32, 8];
+        let is_initialized = unpack_account_type(is_initialized, AccountType::Mint)?;
+        Ok(MintState{
+            is_initialized,
+            mint: Pubkey::new_from_array(*mint),
+            sale_count: u64::from_be_bytes(*sale_count)
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, MintState::LEN];
+        let (
+            is_initialized_dst,
+            mint_dst,
+            sale_count_dst
+        ) = mut_array_refs![dst, 1, 32, 8];
+
+        let MintState {
+            is_initialized,
+            mint,
+            sale_count
+        } = self;
+
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::Mint);
+        mint_dst.copy_from_slice(mint.as_ref());
+        *sale_count_dst = sale_count.to_be_bytes();
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SwapEscrowState {
+    pub is_initialized: bool,
+    pub offerer: Pubkey,
+    pub swap_id: u64,
+    pub offered_lamports: u64,
+    pub requested_lamports: u64,
+    pub offered_count: u8,
+    pub offered_mints: [Pubkey; MAX_SWAP_MINTS],
+    pub requested_count: u8,
+    pub requested_mints: [Pubkey; MAX_SWAP_MINTS],
+}
+
+impl SwapEscrowState {
+    pub fn offered(&self) -> &[Pubkey] {
+        &self.offered_mints[..self.offered_count as usize]
+    }
+
+    pub fn requested(&self) -> &[Pubkey] {
+        &self.requested_mints[..self.requested_count as usize]
+    }
+}
+
+impl Sealed for SwapEscrowState{}
+
+impl IsInitialized for SwapEscrowState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SwapEscrowState {
+    const LEN: usize = SWAPESCROWSTATE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, SwapEscrowState::LEN];
+        let (
+            is_initialized,
+            offerer,
+            swap_id,
+            offered_lamports,
+            requested_lamports,
+            offered_count,
+            offered_mints,
+            requested_count,
+            requested_mints
+        ) = array_refs![src, 1, 32, 8, 8, 8, 1, 32 * MAX_SWAP_MINTS, 1, 32 * MAX_SWAP_MINTS];
+        let is_initialized = unpack_account_type(is_initialized, AccountType::SwapEscrow)?;
+        if offered_count[0] as usize > MAX_SWAP_MINTS || requested_count[0] as usize > MAX_SWAP_MINTS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut offered = [Pubkey::default(); MAX_SWAP_MINTS];
+        unpack_pubkeys(offered_mints, &mut offered);
+        let mut requested = [Pubkey::default(); MAX_SWAP_MINTS];
+        unpack_pubkeys(requested_mints, &mut requested);
+        Ok(SwapEscrowState{
+            is_initialized,
+            offerer: Pubkey::new_from_array(*offerer),
+            swap_id: u64::from_be_bytes(*swap_id),
+            offered_lamports: u64::from_be_bytes(*offered_lamports),
+            requested_lamports: u64::from_be_bytes(*requested_lamports),
+            offered_count: offered_count[0],
+            offered_mints: offered,
+            requested_count: requested_count[0],
+            requested_mints: requested,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, SwapEscrowState::LEN];
+        let (
+            is_initialized_dst,
+            offerer_dst,
+            swap_id_dst,
+            offered_lamports_dst,
+            requested_lamports_dst,
+            offered_count_dst,
+            offered_mints_dst,
+            requested_count_dst,
+            requested_mints_dst
+        ) = mut_array_refs![dst, 1, 32, 8, 8, 8, 1, 32 * MAX_SWAP_MINTS, 1, 32 * MAX_SWAP_MINTS];
+
+        let SwapEscrowState {
+            is_initialized,
+            offerer,
+            swap_id,
+            offered_lamports,
+            requested_lamports,
+            offered_count,
+            offered_mints,
+            requested_count,
+            requested_mints
+        } = self;
+
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::SwapEscrow);
+        offerer_dst.copy_from_slice(offerer.as_ref());
+        *swap_id_dst = swap_id.to_be_bytes();
+        *offered_lamports_dst = offered_lamports.to_be_bytes();
+        *requested_lamports_dst = requested_lamports.to_be_bytes();
+        offered_count_dst[0] = *offered_count;
+        pack_pubkeys(offered_mints, offered_mints_dst);
+        requested_count_dst[0] = *requested_count;
+        pack_pubkeys(requested_mints, requested_mints_dst);
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BundleEscrowState {
+    pub is_initialized: bool,
+    pub lister: Pubkey,
+    pub bundle_id: u64,
+    pub amount: u64,
+    pub mint_count: u8,
+    pub mints: [Pubkey; MAX_BUNDLE_MINTS],
+}
+
+impl BundleEscrowState {
+    pub fn bundled(&self) -> &[Pubkey] {
+        &self.mints[..self.mint_count as usize]
+    }
+}
+
+impl Sealed for BundleEscrowState{}
+
+impl IsInitialized for BundleEscrowState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for BundleEscrowState {
+    const LEN: usize = BUNDLEESCROWSTATE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, BundleEscrowState::LEN];
+        let (
+            is_initialized,
+            lister,
+            bundle_id,
+            amount,
+            mint_count,
+            mints
+        ) = array_refs![src, 1, 32, 8, 8, 1, 32 * MAX_BUNDLE_MINTS];
+        let is_initialized = unpack_account_type(is_initialized, AccountType::BundleEscrow)?;
+        if mint_count[0] as usize > MAX_BUNDLE_MINTS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut bundled = [Pubkey::default(); MAX_BUNDLE_MINTS];
+        unpack_pubkeys(mints, &mut bundled);
+        Ok(BundleEscrowState{
+            is_initialized,
+            lister: Pubkey::new_from_array(*lister),
+            bundle_id: u64::from_be_bytes(*bundle_id),
+            amount: u64::from_be_bytes(*amount),
+            mint_count: mint_count[0],
+            mints: bundled,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, BundleEscrowState::LEN];
+        let (
+            is_initialized_dst,
+            lister_dst,
+            bundle_id_dst,
+            amount_dst,
+            mint_count_dst,
+            mints_dst
+        ) = mut_array_refs![dst, 1, 32, 8, 8, 1, 32 * MAX_BUNDLE_MINTS];
+
+        let BundleEscrowState {
+            is_initialized,
+            lister,
+            bundle_id,
+            amount,
+            mint_count,
+            mints
+        } = self;
+
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::BundleEscrow);
+        lister_dst.copy_from_slice(lister.as_ref());
+        *bundle_id_dst = bundle_id.to_be_bytes();
+        *amount_dst = amount.to_be_bytes();
+        mint_count_dst[0] = *mint_count;
+        pack_pubkeys(mints, mints_dst);
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CollectionFee {
+    pub collection: Pubkey,
+    pub platform_fee: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeScheduleState {
+    pub is_initialized: bool,
+    pub collection_count: u8,
+    pub collection_fees: [CollectionFee; MAX_COLLECTION_FEES],
+    pub tier_count: u8,
+    pub tier_fees: [u64; MAX_FEE_TIERS],
+}
+
+impl FeeScheduleState {
+    pub fn collections(&self) -> &[CollectionFee] {
+        &self.collection_fees[..self.collection_count as usize]
+    }
+
+    pub fn collection_fee(&self, collection: &Pubkey) -> Option<u64> {
+        self.collections()
+            .iter()
+            .find(|collection_fee| collection_fee.collection.eq(collection))
+            .map(|collection_fee| collection_fee.platform_fee)
+    }
+
+    /// Tiers are numbered from 1, tier 0 being the regular platform fee
+    pub fn tier_fee(&self, tier: u8) -> Option<u64> {
+        match tier {
+            0 => None,
+            _ if tier > self.tier_count => None,
+            _ => Some(self.tier_fees[tier as usize - 1]),
+        }
+    }
+
+    pub fn set_collection_fee(&mut self, collection: &Pubkey, platform_fee: Option<u64>) -> Result<(), ProgramError> {
+        let count = self.collection_count as usize;
+        let position = self.collections().iter().position(|collection_fee| collection_fee.collection.eq(collection));
+        match (position, platform_fee) {
+            (Some(position), Some(platform_fee)) => {
+                self.collection_fees[position].platform_fee = platform_fee;
+            }
+            (Some(position), None) => {
+                self.collection_fees[position] = self.collection_fees[count - 1];
+                self.collection_fees[count - 1] = CollectionFee::default();
+                self.collection_count -= 1;
+            }
+            (None, Some(platform_fee)) => {
+                if count == MAX_COLLECTION_FEES {
+                    return Err(ProgramError::AccountDataTooSmall);
+                }
+                self.collection_fees[count] = CollectionFee{
+                    collection: *collection,
+                    platform_fee,
+                };
+                self.collection_count += 1;
+            }
+            (None, None) => {}
+        }
+        Ok(())
+    }
+}
+
+impl Sealed for FeeScheduleState{}
+
+impl IsInitialized for FeeScheduleState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for FeeScheduleState {
+    const LEN: usize = FEESCHEDULESTATE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, FeeScheduleState::LEN];
+        let (
+            is_initialized,
+            collection_count,
+            collection_fees,
+            tier_count,
+            tier_fees
+        ) = array_refs![src, 1, 1, 40 * MAX_COLLECTION_FEES, 1, 8 * MAX_FEE_TIERS];
+        let is_initialized = unpack_account_type(is_initialized, AccountType::FeeSchedule)?;
+        if collection_count[0] as usize > MAX_COLLECTION_FEES || tier_count[0] as usize > MAX_FEE_TIERS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut fees = [CollectionFee::default(); MAX_COLLECTION_FEES];
+        for (collection_fee, chunk) in fees.iter_mut().zip(collection_fees.chunks(40)) {
+            let (collection, platform_fee) = array_refs![array_ref![chunk, 0, 40], 32, 8];
+            collection_fee.collection = Pubkey::new_from_array(*collection);
+            collection_fee.platform_fee = u64::from_be_bytes(*platform_fee);
+        }
+        let mut tiers = [0u64; MAX_FEE_TIERS];
+        for (tier_fee, chunk) in tiers.iter_mut().zip(tier_fees.chunks(8)) {
+            *tier_fee = u64::from_be_bytes(*array_ref![chunk, 0, 8]);
+        }
+        Ok(FeeScheduleState{
+            is_initialized,
+            collection_count: collection_count[0],
+            collection_fees: fees,
+            tier_count: tier_count[0],
+            tier_fees: tiers,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, FeeScheduleState::LEN];
+        let (
+            is_initialized_dst,
+            collection_count_dst,
+            collection_fees_dst,
+            tier_count_dst,
+            tier_fees_dst
+        ) = mut_array_refs![dst, 1, 1, 40 * MAX_COLLECTION_FEES, 1, 8 * MAX_FEE_TIERS];
+
+        let FeeScheduleState {
+            is_initialized,
+            collection_count,
+            collection_fees,
+            tier_count,
+            tier_fees
+        } = self;
+
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::FeeSchedule);
+        collection_count_dst[0] = *collection_count;
+        for (collection_fee, chunk) in collection_fees.iter().zip(collection_fees_dst.chunks_mut(40)) {
+            chunk[..32].copy_from_slice(collection_fee.collection.as_ref());
+            chunk[32..].copy_from_slice(&collection_fee.platform_fee.to_be_bytes());
+        }
+        tier_count_dst[0] = *tier_count;
+        for (tier_fee, chunk) in tier_fees.iter().zip(tier_fees_dst.chunks_mut(8)) {
+            chunk.copy_from_slice(&tier_fee.to_be_bytes());
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TraderState {
+    pub is_initialized: bool,
+    pub trader: Pubkey,
+    pub tier: u8,
+}
+
+impl Sealed for TraderState{}
+
+impl IsInitialized for TraderState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for TraderState {
+    const LEN: usize = TRADERSTATE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, TraderState::LEN];
+        let (
+            is_initialized,
+            trader,
+            tier
+        ) = array_refs![src, 1, 32, 1];
+        let is_initialized = unpack_account_type(is_initialized, AccountType::Trader)?;
+        Ok(TraderState{
+            is_initialized,
+            trader: Pubkey::new_from_array(*trader),
+            tier: tier[0],
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, TraderState::LEN];
+        let (
+            is_initialized_dst,
+            trader_dst,
+            tier_dst
+        ) = mut_array_refs![dst, 1, 32, 1];
+
+        let TraderState {
+            is_initialized,
+            trader,
+            tier
+        } = self;
+
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::Trader);
+        trader_dst.copy_from_slice(trader.as_ref());
+        tier_dst[0] = *tier;
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeRecipient {
+    pub recipient: Pubkey,
+    pub bps: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeDistributionState {
+    pub is_initialized: bool,
+    pub recipient_count: u8,
+    pub recipients: [FeeRecipient; MAX_FEE_RECIPIENTS],
+}
+
+impl FeeDistributionState {
+    pub fn recipients(&self) -> &[FeeRecipient] {
+        &self.recipients[..self.recipient_count as usize]
+    }
+}
+
+impl Sealed for FeeDistributionState{}
+
+impl IsInitialized for FeeDistributionState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for FeeDistributionState {
+    const LEN: usize = FEEDISTRIBUTIONSTATE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, FeeDistributionState::LEN];
+        let (
+            is_initialized,
+            recipient_count,
+            recipients_src
+        ) = array_refs![src, 1, 1, 34 * MAX_FEE_RECIPIENTS];
+        let is_initialized = unpack_account_type(is_initialized, AccountType::FeeDistribution)?;
+        if recipient_count[0] as usize > MAX_FEE_RECIPIENTS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut recipients = [FeeRecipient::default(); MAX_FEE_RECIPIENTS];
+        for (recipient, chunk) in recipients.iter_mut().zip(recipients_src.chunks(34)) {
+            let (key, bps) = array_refs![array_ref![chunk, 0, 34], 32, 2];
+            recipient.recipient = Pubkey::new_from_array(*key);
+            recipient.bps = u16::from_be_bytes(*bps);
+        }
+        Ok(FeeDistributionState{
+            is_initialized,
+            recipient_count: recipient_count[0],
+            recipients,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, FeeDistributionState::LEN];
+        let (
+            is_initialized_dst,
+            recipient_count_dst,
+            recipients_dst
+        ) = mut_array_refs![dst, 1, 1, 34 * MAX_FEE_RECIPIENTS];
+
+        let FeeDistributionState {
+            is_initialized,
+            recipient_count,
+            recipients
+        } = self;
+
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::FeeDistribution);
+        recipient_count_dst[0] = *recipient_count;
+        for (recipient, chunk) in recipients.iter().zip(recipients_dst.chunks_mut(34)) {
+            chunk[..32].copy_from_slice(recipient.recipient.as_ref());
+            chunk[32..].copy_from_slice(&recipient.bps.to_be_bytes());
+        }
+    }
+}
+
+/// Listing of a compressed NFT. The escrow state owns the leaf while it is listed, and keeps what is needed to
+/// rebuild the leaf hash for settlement, since the leaf itself lives only in the Merkle tree
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CompressedListEscrowState {
+    pub is_initialized: bool,
+    pub lister: Pubkey,
+    pub asset_id: Pubkey,
+    pub merkle_tree: Pubkey,
+    pub data_hash: [u8; 32],
+    pub creator_hash: [u8; 32],
+    pub nonce: u64,
+    pub index: u32,
+    pub amount: u64,
+    pub list_id: u64,
+}
+
+impl Sealed for CompressedListEscrowState{}
+
+impl IsInitialized for CompressedListEscrowState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for CompressedListEscrowState {
+    const LEN: usize = COMPRESSEDLISTESCROWSTATE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, CompressedListEscrowState::LEN];
+        let (
+            is_initialized,
+            lister,
+            asset_id,
+            merkle_tree,
+            data_hash,
+            creator_hash,
+            nonce,
+            index,
+            amount,
+            list_id,
+        ) = array_refs![src, 1, 32, 32, 32, 32, 32, 8, 4, 8, 8];
+        Ok(CompressedListEscrowState{
+            is_initialized: unpack_account_type(is_initialized, AccountType::CompressedListEscrow)?,
+            lister: Pubkey::new_from_array(*lister),
+            asset_id: Pubkey::new_from_array(*asset_id),
+            merkle_tree: Pubkey::new_from_array(*merkle_tree),
+            data_hash: *data_hash,
+            creator_hash: *creator_hash,
+            nonce: u64::from_be_bytes(*nonce),
+            index: u32::from_be_bytes(*index),
+            amount: u64::from_be_bytes(*amount),
+            list_id: u64::from_be_bytes(*list_id),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, CompressedListEscrowState::LEN];
+        let (
+            is_initialized_dst,
+            lister_dst,
+            asset_id_dst,
+            merkle_tree_dst,
+            data_hash_dst,
+            creator_hash_dst,
+            nonce_dst,
+            index_dst,
+            amount_dst,
+            list_id_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32, 32, 8, 4, 8, 8];
+
+        let CompressedListEscrowState {
+            is_initialized,
+            lister,
+            asset_id,
+            merkle_tree,
+            data_hash,
+            creator_hash,
+            nonce,
+            index,
+            amount,
+            list_id,
+        } = self;
+
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::CompressedListEscrow);
+        lister_dst.copy_from_slice(lister.as_ref());
+        asset_id_dst.copy_from_slice(asset_id.as_ref());
+        merkle_tree_dst.copy_from_slice(merkle_tree.as_ref());
+        *data_hash_dst = *data_hash;
+        *creator_hash_dst = *creator_hash;
+        *nonce_dst = nonce.to_be_bytes();
+        *index_dst = index.to_be_bytes();
+        *amount_dst = amount.to_be_bytes();
+        *list_id_dst = list_id.to_be_bytes();
+    }
+}
+
+/// Listing that leaves the NFT in the lister's own token account. The listing state is approved as the account's
+/// delegate for the single token, and freezes the account through the master edition when the mint allows it
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DelegatedListEscrowState {
+    pub is_initialized: bool,
+    pub lister: Pubkey,
+    pub mint: Pubkey,
+    pub token_account: Pubkey,
+    pub amount: u64,
+    pub list_id: u64,
+    pub frozen: bool,
+}
+
+impl Sealed for DelegatedListEscrowState{}
+
+impl IsInitialized for DelegatedListEscrowState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for DelegatedListEscrowState {
+    const LEN: usize = DELEGATEDLISTESCROWSTATE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, DelegatedListEscrowState::LEN];
+        let (
+            is_initialized,
+            lister,
+            mint,
+            token_account,
+            amount,
+            list_id,
+            frozen,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 8, 1];
+        Ok(DelegatedListEscrowState{
+            is_initialized: unpack_account_type(is_initialized, AccountType::DelegatedListEscrow)?,
+            lister: Pubkey::new_from_array(*lister),
+            mint: Pubkey::new_from_array(*mint),
+            token_account: Pubkey::new_from_array(*token_account),
+            amount: u64::from_be_bytes(*amount),
+            list_id: u64::from_be_bytes(*list_id),
+            frozen: frozen[0] == 1,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, DelegatedListEscrowState::LEN];
+        let (
+            is_initialized_dst,
+            lister_dst,
+            mint_dst,
+            token_account_dst,
+            amount_dst,
+            list_id_dst,
+            frozen_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 8, 1];
+
+        let DelegatedListEscrowState {
+            is_initialized,
+            lister,
+            mint,
+            token_account,
+            amount,
+            list_id,
+            frozen,
+        } = self;
+
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::DelegatedListEscrow);
+        lister_dst.copy_from_slice(lister.as_ref());
+        mint_dst.copy_from_slice(mint.as_ref());
+        token_account_dst.copy_from_slice(token_account.as_ref());
+        *amount_dst = amount.to_be_bytes();
+        *list_id_dst = list_id.to_be_bytes();
+        frozen_dst[0] = *frozen as u8;
+    }
+}
+
+/// A bid as indexed by the order book of its mint
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OrderBookBid {
+    pub bidder: Pubkey,
+    pub bid_id: u64,
+    pub amount: u64,
+    pub expiry: i64,
+}
+
+impl OrderBookBid {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expiry != 0 && now >= self.expiry
+    }
+}
+
+/// Index of the open orders of a mint, so the top of book can be read in one account fetch instead of scanning
+/// every bid escrow. Bids are kept sorted by amount, highest first and oldest first among equal amounts. When the
+/// book is full a new bid only gets in by pushing out the lowest one, and bids left out stay valid in their escrow
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OrderBookState {
+    pub is_initialized: bool,
+    pub mint: Pubkey,
+    // Lister of the open listing, the default key when the mint is not listed
+    pub lister: Pubkey,
+    pub list_id: u64,
+    pub list_amount: u64,
+    pub bid_count: u8,
+    pub bids: [OrderBookBid; MAX_ORDER_BOOK_BIDS],
+}
+
+impl OrderBookState {
+    pub fn bids(&self) -> &[OrderBookBid] {
+        &self.bids[..self.bid_count as usize]
+    }
+
+    pub fn has_listing(&self) -> bool {
+        self.lister != Pubkey::default()
+    }
+
+    /// The highest bid that has not expired yet
+    pub fn best_bid(&self, now: i64) -> Option<&OrderBookBid> {
+        self.bids().iter().find(|bid| !bid.is_expired(now))
+    }
+
+    pub fn set_listing(&mut self, lister: &Pubkey, list_id: u64, amount: u64) {
+        self.lister = *lister;
+        self.list_id = list_id;
+        self.list_amount = amount;
+    }
+
+    /// Clears the listing if it is the one of `lister` and `list_id`
+    pub fn remove_listing(&mut self, lister: &Pubkey, list_id: u64) {
+        if self.lister.eq(lister) && self.list_id == list_id {
+            self.set_listing(&Pubkey::default(), 0, 0);
+        }
+    }
+
+    /// Drops expired bids and adds `bid` at its place, returning whether it made it into the book
+    pub fn insert_bid(&mut self, bid: OrderBookBid, now: i64) -> bool {
+        let mut bids: Vec<OrderBookBid> = self.bids().iter().filter(|bid| !bid.is_expired(now)).cloned().collect();
+        let position = bids.iter().position(|other| other.amount < bid.amount).unwrap_or(bids.len());
+        bids.insert(position, bid);
+        bids.truncate(MAX_ORDER_BOOK_BIDS);
+        self.store_bids(&bids);
+        position < MAX_ORDER_BOOK_BIDS
+    }
+
+    pub fn remove_bid(&mut self, bidder: &Pubkey, bid_id: u64) {
+        let bids: Vec<OrderBookBid> = self.bids().iter().filter(|bid| !(bid.bidder.eq(bidder) && bid.bid_id == bid_id)).cloned().collect();
+        self.store_bids(&bids);
+    }
+
+    fn store_bids(&mut self, bids: &[OrderBookBid]) {
+        self.bids = [OrderBookBid::default(); MAX_ORDER_BOOK_BIDS];
+        self.bids[..bids.len()].copy_from_slice(bids);
+        self.bid_count = bids.len() as u8;
+    }
+}
+
+impl Sealed for OrderBookState{}
+
+impl IsInitialized for OrderBookState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for OrderBookState {
+    const LEN: usize = ORDERBOOKSTATE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, OrderBookState::LEN];
+        let (
+            is_initialized,
+            mint,
+            lister,
+            list_id,
+            list_amount,
+            bid_count,
+            bids_src
+        ) = array_refs![src, 1, 32, 32, 8, 8, 1, 56 * MAX_ORDER_BOOK_BIDS];
+        let is_initialized = unpack_account_type(is_initialized, AccountType::OrderBook)?;
+        if bid_count[0] as usize > MAX_ORDER_BOOK_BIDS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut bids = [OrderBookBid::default(); MAX_ORDER_BOOK_BIDS];
+        for (bid, chunk) in bids.iter_mut().zip(bids_src.chunks(56)) {
+            let (bidder, bid_id, amount, expiry) = array_refs![array_ref![chunk, 0, 56], 32, 8, 8, 8];
+            bid.bidder = Pubkey::new_from_array(*bidder);
+            bid.bid_id = u64::from_be_bytes(*bid_id);
+            bid.amount = u64::from_be_bytes(*amount);
+            bid.expiry = i64::from_be_bytes(*expiry);
+        }
+        Ok(OrderBookState{
+            is_initialized,
+            mint: Pubkey::new_from_array(*mint),
+            lister: Pubkey::new_from_array(*lister),
+            list_id: u64::from_be_bytes(*list_id),
+            list_amount: u64::from_be_bytes(*list_amount),
+            bid_count: bid_count[0],
+            bids,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, OrderBookState::LEN];
+        let (
+            is_initialized_dst,
+            mint_dst,
+            lister_dst,
+            list_id_dst,
+            list_amount_dst,
+            bid_count_dst,
+            bids_dst
+        ) = mut_array_refs![dst, 1, 32, 32, 8, 8, 1, 56 * MAX_ORDER_BOOK_BIDS];
+
+        let OrderBookState {
+            is_initialized,
+            mint,
+            lister,
+            list_id,
+            list_amount,
+            bid_count,
+            bids
+        } = self;
+
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::OrderBook);
+        mint_dst.copy_from_slice(mint.as_ref());
+        lister_dst.copy_from_slice(lister.as_ref());
+        *list_id_dst = list_id.to_be_bytes();
+        *list_amount_dst = list_amount.to_be_bytes();
+        bid_count_dst[0] = *bid_count;
+        for (bid, chunk) in bids.iter().zip(bids_dst.chunks_mut(56)) {
+            chunk[..32].copy_from_slice(bid.bidder.as_ref());
+            chunk[32..40].copy_from_slice(&bid.bid_id.to_be_bytes());
+            chunk[40..48].copy_from_slice(&bid.amount.to_be_bytes());
+            chunk[48..].copy_from_slice(&bid.expiry.to_be_bytes());
+        }
+    }
+}
+
+/// Cancellations of a user's signed orders and delegated listings. Raising `min_nonce` cancels every order with a
+/// lower nonce at once. Single orders are cancelled by setting the bit their hash maps to, so the bitmap stays
+/// bounded but two orders can share a bit, and cancelling one then also cancels the other
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OrderNonceState {
+    pub is_initialized: bool,
+    pub owner: Pubkey,
+    pub min_nonce: u64,
+    pub cancelled: [u64; CANCELLED_ORDER_WORDS],
+}
+
+impl OrderNonceState {
+    /// Word and mask of the bit `order_hash` maps to, from its leading 8 bytes
+    fn cancelled_bit(order_hash: &Hash) -> (usize, u64) {
+        let bits = (CANCELLED_ORDER_WORDS * 64) as u64;
+        let bit = u64::from_be_bytes(*array_ref![order_hash.as_ref(), 0, 8]) % bits;
+        ((bit / 64) as usize, 1 << (bit % 64))
+    }
+
+    pub fn cancel(&mut self, order_hash: &Hash) {
+        let (word, mask) = Self::cancelled_bit(order_hash);
+        self.cancelled[word] |= mask;
+    }
+
+    pub fn is_cancelled(&self, nonce: u64, order_hash: &Hash) -> bool {
+        let (word, mask) = Self::cancelled_bit(order_hash);
+        nonce < self.min_nonce || self.cancelled[word] & mask != 0
     }
 }
+
+impl Sealed for OrderNonceState{}
+
+impl IsInitialized for OrderNonceState{
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for OrderNonceState {
+    const LEN: usize = ORDERNONCESTATE;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, OrderNonceState::LEN];
+        let (
+            is_initialized,
+            owner,
+            min_nonce,
+            cancelled_src
+        ) = array_refs![src, 1, 32, 8, 8 * CANCELLED_ORDER_WORDS];
+        let is_initialized = unpack_account_type(is_initialized, AccountType::OrderNonce)?;
+        let mut cancelled = [0u64; CANCELLED_ORDER_WORDS];
+        for (word, chunk) in cancelled.iter_mut().zip(cancelled_src.chunks(8)) {
+            *word = u64::from_be_bytes(*array_ref![chunk, 0, 8]);
+        }
+        Ok(OrderNonceState{
+            is_initialized,
+            owner: Pubkey::new_from_array(*owner),
+            min_nonce: u64::from_be_bytes(*min_nonce),
+            cancelled,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, OrderNonceState::LEN];
+        let (
+            is_initialized_dst,
+            owner_dst,
+            min_nonce_dst,
+            cancelled_dst
+        ) = mut_array_refs![dst, 1, 32, 8, 8 * CANCELLED_ORDER_WORDS];
+
+        let OrderNonceState {
+            is_initialized,
+            owner,
+            min_nonce,
+            cancelled
+        } = self;
+
+        is_initialized_dst[0] = pack_account_type(*is_initialized, AccountType::OrderNonce);
+        owner_dst.copy_from_slice(owner.as_ref());
+        *min_nonce_dst = min_nonce.to_be_bytes();
+        for (word, chunk) in cancelled.iter().zip(cancelled_dst.chunks_mut(8)) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+    }
+}
+
+/// Rewrites a platform state written by an earlier version of the program in the current layout, or returns `None`
+/// when it already is. Its fields have only ever been appended and a zeroed field reads as its old default, and the
+/// original layout flagged its initialization with a 1, which is the platform's `AccountType`, so the state is
+/// upgraded by zero-padding it to the current length
+pub fn migrate_platform_state(src: &[u8]) -> Result<Option<Vec<u8>>, ProgramError> {
+    if src.len() > STATESIZE {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let mut data = src.to_vec();
+    data.resize(STATESIZE, 0);
+    PlatformState::unpack_unchecked(&data)?;
+    if data == src {
+        return Ok(None);
+    }
+    Ok(Some(data))
+}
+
+fn unpack_account_type(src: &[u8; 1], account_type: AccountType) -> Result<bool, ProgramError> {
+    match src[0] {
+        0 => Ok(false),
+        tag if tag == account_type as u8 => Ok(true),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+fn pack_account_type(is_initialized: bool, account_type: AccountType) -> u8 {
+    match is_initialized {
+        true => account_type as u8,
+        false => AccountType::Uninitialized as u8,
+    }
+}
+
+fn unpack_pubkeys(src: &[u8], pubkeys: &mut [Pubkey]) {
+    for (pubkey, chunk) in pubkeys.iter_mut().zip(src.chunks(32)) {
+        *pubkey = Pubkey::new_from_array(*array_ref![chunk, 0, 32]);
+    }
+}
+
+fn pack_pubkeys(pubkeys: &[Pubkey], dst: &mut [u8]) {
+    for (pubkey, chunk) in pubkeys.iter().zip(dst.chunks_mut(32)) {
+        chunk.copy_from_slice(pubkey.as_ref());
+    }
+}
\ No newline at end of file
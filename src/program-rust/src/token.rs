@@ -0,0 +1,172 @@
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar,
+};
+use spl_token::{instruction::TokenInstruction, state::{Account, Mint}};
+
+use crate::error::NFTError;
+
+pub mod token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+// Token-2022 pads extended mints to the size of a token account, then stores an account type byte and the extensions
+const ACCOUNT_TYPE_OFFSET: usize = Account::LEN;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+// Token-2022 mint extension types that leave an escrowed NFT movable by the escrow alone
+const EXTENSION_MINT_CLOSE_AUTHORITY: u16 = 3;
+const EXTENSION_CONFIDENTIAL_TRANSFER_MINT: u16 = 4;
+const EXTENSION_DEFAULT_ACCOUNT_STATE: u16 = 6;
+const EXTENSION_INTEREST_BEARING_CONFIG: u16 = 10;
+const EXTENSION_METADATA_POINTER: u16 = 18;
+const EXTENSION_TOKEN_METADATA: u16 = 19;
+const EXTENSION_GROUP_POINTER: u16 = 20;
+const EXTENSION_TOKEN_GROUP: u16 = 21;
+const EXTENSION_GROUP_MEMBER_POINTER: u16 = 22;
+const EXTENSION_TOKEN_GROUP_MEMBER: u16 = 23;
+
+const ACCOUNT_STATE_FROZEN: u8 = 2;
+
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    program_id.eq(&spl_token::id()) || program_id.eq(&token_2022::id())
+}
+
+/// Checks that `mint_account_info` is an NFT mint of either token program that can be escrowed. Token-2022 mints
+/// are rejected when an extension could take the NFT out of escrow or stop the escrow from releasing it: transfer
+/// fees, transfer hooks, non-transferable mints, permanent delegates, frozen default account states and any
+/// extension this program does not know about
+pub fn check_mint(mint_account_info: &AccountInfo) -> Result<(), ProgramError> {
+    if !is_token_program(mint_account_info.owner) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let data = mint_account_info.data.borrow();
+    if data.len() < Mint::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let mint = Mint::unpack_from_slice(&data[..Mint::LEN])?;
+    if !mint.is_initialized || mint.decimals != 0 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if data.len() == Mint::LEN {
+        return Ok(());
+    }
+    if mint_account_info.owner.eq(&spl_token::id()) || data.len() <= ACCOUNT_TYPE_OFFSET || data[ACCOUNT_TYPE_OFFSET] != ACCOUNT_TYPE_MINT {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let mut offset = ACCOUNT_TYPE_OFFSET + 1;
+    while offset + 4 <= data.len() {
+        let extension_type = u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let length = u16::from_le_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        let value = data
+            .get(offset + 4..offset + 4 + length)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        match extension_type {
+            // Uninitialized space after the last extension
+            0 => break,
+            EXTENSION_DEFAULT_ACCOUNT_STATE => {
+                if value.first() == Some(&ACCOUNT_STATE_FROZEN) {
+                    return Err(NFTError::UnsupportedMintExtension.into());
+                }
+            }
+            EXTENSION_MINT_CLOSE_AUTHORITY
+            | EXTENSION_CONFIDENTIAL_TRANSFER_MINT
+            | EXTENSION_INTEREST_BEARING_CONFIG
+            | EXTENSION_METADATA_POINTER
+            | EXTENSION_TOKEN_METADATA
+            | EXTENSION_GROUP_POINTER
+            | EXTENSION_TOKEN_GROUP
+            | EXTENSION_GROUP_MEMBER_POINTER
+            | EXTENSION_TOKEN_GROUP_MEMBER => {}
+            _ => return Err(NFTError::UnsupportedMintExtension.into()),
+        }
+        offset += 4 + length;
+    }
+
+    Ok(())
+}
+
+/// Reads the base state of a token account of either token program, ignoring any Token-2022 extensions
+pub fn unpack_account(token_account_info: &AccountInfo) -> Result<Account, ProgramError> {
+    if !is_token_program(token_account_info.owner) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let data = token_account_info.data.borrow();
+    if data.len() < Account::LEN || (data.len() > Account::LEN && data[ACCOUNT_TYPE_OFFSET] != ACCOUNT_TYPE_ACCOUNT) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Account::unpack_from_slice(&data[..Account::LEN])
+}
+
+// The instruction builders of spl-token only accept the original token program, Token-2022 shares their encoding
+
+pub fn initialize_account(token_program_id: &Pubkey, account: &Pubkey, mint: &Pubkey, owner: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: TokenInstruction::InitializeAccount.pack(),
+    }
+}
+
+/// Moves a single NFT between token accounts
+pub fn transfer_checked(token_program_id: &Pubkey, source: &Pubkey, mint: &Pubkey, destination: &Pubkey, authority: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: TokenInstruction::TransferChecked { amount: 1, decimals: 0 }.pack(),
+    }
+}
+
+pub fn close_account(token_program_id: &Pubkey, account: &Pubkey, destination: &Pubkey, owner: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data: TokenInstruction::CloseAccount.pack(),
+    }
+}
+
+/// Lets `delegate` move the single NFT of `source`
+pub fn approve(token_program_id: &Pubkey, source: &Pubkey, delegate: &Pubkey, owner: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*delegate, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data: TokenInstruction::Approve { amount: 1 }.pack(),
+    }
+}
+
+pub fn revoke(token_program_id: &Pubkey, source: &Pubkey, owner: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data: TokenInstruction::Revoke.pack(),
+    }
+}
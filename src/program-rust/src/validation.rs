@@ -0,0 +1,79 @@
+use solana_program::{
+    account_info::AccountInfo,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+};
+
+use crate::state::{
+    BidEscrowState, BundleEscrowState, CompressedListEscrowState, DelegatedListEscrowState, FeeDistributionState, FeeScheduleState,
+    ListEscrowState, MintState, OrderBookState, OrderNonceState, PlatformState, SwapEscrowState, TraderState,
+};
+
+/// Loads a program account of type `T`, checking that the program owns it, that it has the size of `T` and that
+/// its leading account type byte names an initialized `T`
+pub fn load<T: Pack + IsInitialized>(account_info: &AccountInfo, program_id: &Pubkey) -> Result<T, ProgramError> {
+    if !account_info.owner.eq(program_id) {
+        return Err(ProgramError::IllegalOwner);
+    }
+    if account_info.data_len() != T::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    T::unpack(&account_info.data.borrow())
+}
+
+pub fn load_platform_state(account_info: &AccountInfo, program_id: &Pubkey) -> Result<PlatformState, ProgramError> {
+    let (state_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"State"], program_id);
+    if !account_info.key.eq(&state_account_pubkey) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    load(account_info, program_id)
+}
+
+pub fn load_list_state(account_info: &AccountInfo, program_id: &Pubkey) -> Result<ListEscrowState, ProgramError> {
+    load(account_info, program_id)
+}
+
+pub fn load_bid_state(account_info: &AccountInfo, program_id: &Pubkey) -> Result<BidEscrowState, ProgramError> {
+    load(account_info, program_id)
+}
+
+pub fn load_mint_state(account_info: &AccountInfo, program_id: &Pubkey) -> Result<MintState, ProgramError> {
+    load(account_info, program_id)
+}
+
+pub fn load_swap_state(account_info: &AccountInfo, program_id: &Pubkey) -> Result<SwapEscrowState, ProgramError> {
+    load(account_info, program_id)
+}
+
+pub fn load_bundle_state(account_info: &AccountInfo, program_id: &Pubkey) -> Result<BundleEscrowState, ProgramError> {
+    load(account_info, program_id)
+}
+
+pub fn load_fee_schedule(account_info: &AccountInfo, program_id: &Pubkey) -> Result<FeeScheduleState, ProgramError> {
+    load(account_info, program_id)
+}
+
+pub fn load_trader_state(account_info: &AccountInfo, program_id: &Pubkey) -> Result<TraderState, ProgramError> {
+    load(account_info, program_id)
+}
+
+pub fn load_fee_distribution(account_info: &AccountInfo, program_id: &Pubkey) -> Result<FeeDistributionState, ProgramError> {
+    load(account_info, program_id)
+}
+
+pub fn load_compressed_list_state(account_info: &AccountInfo, program_id: &Pubkey) -> Result<CompressedListEscrowState, ProgramError> {
+    load(account_info, program_id)
+}
+
+pub fn load_delegated_list_state(account_info: &AccountInfo, program_id: &Pubkey) -> Result<DelegatedListEscrowState, ProgramError> {
+    load(account_info, program_id)
+}
+
+pub fn load_order_book(account_info: &AccountInfo, program_id: &Pubkey) -> Result<OrderBookState, ProgramError> {
+    load(account_info, program_id)
+}
+
+pub fn load_order_nonce_state(account_info: &AccountInfo, program_id: &Pubkey) -> Result<OrderNonceState, ProgramError> {
+    load(account_info, program_id)
+}
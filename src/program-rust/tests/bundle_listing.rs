@@ -0,0 +1,262 @@
+#[macro_use]
+mod common;
+
+use common::{bincode_rent, nft_mint, packed, process, token_account, TestAccount, TokenMock};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::Pack,
+    program_stubs,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+    sysvar,
+};
+use spl_token::state::Account;
+use test::state::{self, BundleEscrowState, PlatformState};
+
+const BUNDLE_ID: u64 = 0;
+const PRICE: u64 = 3_000_000_000;
+// 2% platform fee, all of it paid by the seller
+const PLATFORM_FEE: u64 = 20_000_000;
+
+const LISTER: usize = 0;
+const ESCROW_STATE: usize = 1;
+// Accounts of `ListBundle` before the bundled NFTs
+const LIST_ACCOUNTS: usize = 7;
+// Accounts of `AcceptBundleListing` before the bundled NFTs
+const BUYER: usize = 0;
+const SELLER: usize = 1;
+const VAULT: usize = 3;
+const BUNDLE_STATE: usize = 4;
+const ACCEPT_ACCOUNTS: usize = 10;
+
+struct Bundle {
+    program_id: Pubkey,
+    lister: Pubkey,
+    buyer: Pubkey,
+    mints: Vec<Pubkey>,
+    state: Pubkey,
+}
+
+impl Bundle {
+    fn new(program_id: Pubkey, size: usize) -> Self {
+        let lister = Pubkey::new_unique();
+        let (state, _) = Pubkey::find_program_address(&[lister.as_ref(), &BUNDLE_ID.to_be_bytes(), b"Bundle", b"State"], &program_id);
+        Bundle {
+            program_id,
+            lister,
+            buyer: Pubkey::new_unique(),
+            mints: (0..size).map(|_| Pubkey::new_unique()).collect(),
+            state,
+        }
+    }
+
+    fn pda(&self, seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &self.program_id).0
+    }
+
+    fn vault(&self, mint: &Pubkey) -> Pubkey {
+        self.pda(&[self.state.as_ref(), mint.as_ref(), b"Bundle", b"Vault"])
+    }
+
+    fn platform_state(&self) -> TestAccount {
+        TestAccount::new(self.pda(&[b"Platform", b"State"]), self.program_id, packed(PlatformState {
+            is_initialized: true,
+            platform_fee: PLATFORM_FEE,
+            ..PlatformState::default()
+        }))
+    }
+
+    fn list_accounts(&self) -> Vec<TestAccount> {
+        let mut accounts = vec![
+            TestAccount::wallet(self.lister, 10 * PRICE, true),
+            TestAccount::new(self.state, self.program_id, vec![0; state::BUNDLEESCROWSTATE]),
+            TestAccount::new(self.program_id, Pubkey::default(), vec![]),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            TestAccount::new(sysvar::rent::id(), sysvar::id(), bincode_rent()),
+            self.platform_state(),
+        ];
+        for mint in self.mints.iter() {
+            accounts.extend([
+                TestAccount::new(Pubkey::new_unique(), spl_token::id(), token_account(*mint, self.lister, 1)),
+                TestAccount::new(*mint, spl_token::id(), nft_mint()),
+                TestAccount::new(self.vault(mint), spl_token::id(), vec![0; Account::LEN]),
+            ]);
+        }
+        accounts
+    }
+
+    fn list_data(&self) -> Vec<u8> {
+        let mut data = vec![16];
+        data.extend_from_slice(&PRICE.to_be_bytes());
+        data.extend_from_slice(&BUNDLE_ID.to_be_bytes());
+        data
+    }
+
+    /// The accounts of a bundle `ListBundle` went through
+    fn listed(&self) -> Vec<TestAccount> {
+        let mut accounts = self.list_accounts();
+        process(&self.program_id, &mut accounts, &self.list_data()).unwrap();
+        accounts
+    }
+
+    /// Each bundled NFT's vault, mint and a token account of `owner` to release it to, in the order they were listed
+    fn release_accounts(&self, listed: &[TestAccount], owner: Pubkey) -> Vec<TestAccount> {
+        let mut accounts = vec![];
+        for (index, mint) in self.mints.iter().enumerate() {
+            let vault = &listed[LIST_ACCOUNTS + 3 * index + 2];
+            accounts.extend([
+                TestAccount::new(vault.key, spl_token::id(), vault.data.clone()).with_lamports(vault.lamports),
+                TestAccount::new(*mint, spl_token::id(), nft_mint()),
+                TestAccount::new(Pubkey::new_unique(), spl_token::id(), token_account(*mint, owner, 0)),
+            ]);
+        }
+        accounts
+    }
+
+    fn escrow_state(&self, listed: &[TestAccount]) -> TestAccount {
+        TestAccount::new(self.state, self.program_id, listed[ESCROW_STATE].data.clone()).with_lamports(listed[ESCROW_STATE].lamports)
+    }
+
+    fn accept_accounts(&self, listed: &[TestAccount]) -> Vec<TestAccount> {
+        let mut accounts = vec![
+            TestAccount::wallet(self.buyer, 10 * PRICE, true),
+            TestAccount::wallet(self.lister, 0, false),
+            self.platform_state(),
+            TestAccount::new(self.pda(&[b"Platform", b"Vault"]), self.program_id, vec![]),
+            self.escrow_state(listed),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            TestAccount::new(self.pda(&[b"Platform", b"FeeSchedule"]), self.program_id, vec![]),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+            TestAccount::new(self.pda(&[self.lister.as_ref(), b"Trader", b"State"]), self.program_id, vec![]),
+        ];
+        accounts.extend(self.release_accounts(listed, self.buyer));
+        accounts
+    }
+
+    fn delist_accounts(&self, listed: &[TestAccount]) -> Vec<TestAccount> {
+        let mut accounts = vec![
+            TestAccount::signer(self.lister),
+            self.escrow_state(listed),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+        ];
+        accounts.extend(self.release_accounts(listed, self.lister));
+        accounts
+    }
+}
+
+fn bundle_data(tag: u8) -> Vec<u8> {
+    let mut data = vec![tag];
+    data.extend_from_slice(&BUNDLE_ID.to_be_bytes());
+    data
+}
+
+fn amount(account: &TestAccount) -> u64 {
+    Account::unpack(&account.data).unwrap().amount
+}
+
+fn list_escrows_every_nft(program_id: Pubkey) {
+    let bundle = Bundle::new(program_id, 3);
+    let accounts = bundle.listed();
+
+    let bundle_state = BundleEscrowState::unpack(&accounts[ESCROW_STATE].data).unwrap();
+    assert_eq!(bundle_state.lister, bundle.lister);
+    assert_eq!(bundle_state.amount, PRICE);
+    assert_eq!(bundle_state.bundled(), &bundle.mints[..]);
+    for index in 0..bundle.mints.len() {
+        assert_eq!(amount(&accounts[LIST_ACCOUNTS + 3 * index]), 0);
+        let vault = Account::unpack(&accounts[LIST_ACCOUNTS + 3 * index + 2].data).unwrap();
+        assert_eq!((vault.mint, vault.owner, vault.amount), (bundle.mints[index], bundle.state, 1));
+    }
+    assert_eq!(accounts[ESCROW_STATE].lamports, Rent::default().minimum_balance(state::BUNDLEESCROWSTATE));
+}
+
+fn list_rejects_incomplete_nft_accounts(program_id: Pubkey) {
+    let bundle = Bundle::new(program_id, 2);
+    let mut accounts = bundle.list_accounts();
+    accounts.pop();
+    assert_eq!(process(&program_id, &mut accounts, &bundle.list_data()), Err(ProgramError::NotEnoughAccountKeys));
+}
+
+fn list_rejects_repeated_mint(program_id: Pubkey) {
+    let mut bundle = Bundle::new(program_id, 2);
+    bundle.mints[1] = bundle.mints[0];
+    let mut accounts = bundle.list_accounts();
+    assert_eq!(process(&program_id, &mut accounts, &bundle.list_data()), Err(ProgramError::InvalidAccountData));
+}
+
+fn accept_pays_lister_and_releases_every_nft(program_id: Pubkey) {
+    let bundle = Bundle::new(program_id, 3);
+    let listed = bundle.listed();
+    let mut accounts = bundle.accept_accounts(&listed);
+    let state_lamports = accounts[BUNDLE_STATE].lamports;
+    let vault_lamports: u64 = (0..3).map(|index| accounts[ACCEPT_ACCOUNTS + 3 * index].lamports).sum();
+    process(&program_id, &mut accounts, &bundle_data(18)).unwrap();
+
+    // One fee on the bundle price, paid by the seller
+    let fee = state::calculate_fee(PRICE, PLATFORM_FEE);
+    assert_eq!(accounts[BUYER].lamports, 10 * PRICE - PRICE);
+    assert_eq!(accounts[SELLER].lamports, PRICE - fee + state_lamports + vault_lamports);
+    assert_eq!(accounts[VAULT].lamports, fee);
+    for index in 0..3 {
+        assert_eq!(amount(&accounts[ACCEPT_ACCOUNTS + 3 * index + 2]), 1);
+        assert_eq!(accounts[ACCEPT_ACCOUNTS + 3 * index].lamports, 0);
+    }
+    assert_eq!(accounts[BUNDLE_STATE].lamports, 0);
+}
+
+fn accept_requires_nfts_in_listed_order(program_id: Pubkey) {
+    let bundle = Bundle::new(program_id, 2);
+    let listed = bundle.listed();
+    let mut accounts = bundle.accept_accounts(&listed);
+    let second = accounts.split_off(ACCEPT_ACCOUNTS + 3);
+    accounts.splice(ACCEPT_ACCOUNTS..ACCEPT_ACCOUNTS, second);
+    assert_eq!(process(&program_id, &mut accounts, &bundle_data(18)), Err(ProgramError::InvalidAccountData));
+}
+
+fn accept_rejects_missing_nft(program_id: Pubkey) {
+    let bundle = Bundle::new(program_id, 2);
+    let listed = bundle.listed();
+    let mut accounts = bundle.accept_accounts(&listed);
+    accounts.truncate(ACCEPT_ACCOUNTS + 3);
+    assert_eq!(process(&program_id, &mut accounts, &bundle_data(18)), Err(ProgramError::NotEnoughAccountKeys));
+    assert_eq!(accounts[BUYER].lamports, 10 * PRICE);
+}
+
+fn delist_returns_every_nft_to_lister(program_id: Pubkey) {
+    let bundle = Bundle::new(program_id, 2);
+    let listed = bundle.listed();
+    let mut accounts = bundle.delist_accounts(&listed);
+    process(&program_id, &mut accounts, &bundle_data(17)).unwrap();
+
+    for index in 0..2 {
+        assert_eq!(amount(&accounts[3 + 3 * index + 2]), 1);
+    }
+    assert_eq!(accounts[ESCROW_STATE].lamports, 0);
+}
+
+fn delist_requires_lister(program_id: Pubkey) {
+    let bundle = Bundle::new(program_id, 2);
+    let listed = bundle.listed();
+    let mut accounts = bundle.delist_accounts(&listed);
+    accounts[LISTER] = TestAccount::signer(bundle.buyer);
+    assert_eq!(process(&program_id, &mut accounts, &bundle_data(17)), Err(ProgramError::InvalidAccountData));
+}
+
+fn main() {
+    let program_id = Pubkey::new_unique();
+    program_stubs::set_syscall_stubs(Box::new(TokenMock));
+
+    run_tests!("bundle_listing", [
+        list_escrows_every_nft(program_id),
+        list_rejects_incomplete_nft_accounts(program_id),
+        list_rejects_repeated_mint(program_id),
+        accept_pays_lister_and_releases_every_nft(program_id),
+        accept_requires_nfts_in_listed_order(program_id),
+        accept_rejects_missing_nft(program_id),
+        delist_returns_every_nft_to_lister(program_id),
+        delist_requires_lister(program_id),
+    ]);
+}
@@ -0,0 +1,167 @@
+// Fixtures shared by the integration tests, each test binary only uses some of them
+#![allow(dead_code)]
+
+use std::convert::TryInto;
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_pack::Pack,
+    program_stubs::SyscallStubs,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+use spl_token::state::{Account, AccountState, Mint};
+use test::processor::Processor;
+
+/// Runs each test in turn and prints a summary, since the test binaries have no harness: the program's library is
+/// named `test`, which shadows libtest. Tests are written as calls, an optional block runs before each of them
+macro_rules! run_tests {
+    ($suite:literal, [$($test:ident $args:tt),+ $(,)?]) => {
+        run_tests!($suite, [$($test $args),+], {})
+    };
+    ($suite:literal, [$($test:ident $args:tt),+ $(,)?], $before_each:block) => {{
+        let mut passed = 0;
+        $(
+            $before_each
+            $test $args;
+            println!("test {} ... ok", stringify!($test));
+            passed += 1;
+        )+
+        println!("{}: {} passed", $suite, passed);
+    }};
+}
+
+/// An account the tests own, lent to the program as an `AccountInfo` for one instruction at a time
+pub struct TestAccount {
+    pub key: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub is_signer: bool,
+}
+
+impl TestAccount {
+    pub fn new(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+        TestAccount { key, owner, lamports: 0, data, is_signer: false }
+    }
+
+    /// A system account holding `lamports`
+    pub fn wallet(key: Pubkey, lamports: u64, is_signer: bool) -> Self {
+        TestAccount { lamports, is_signer, ..TestAccount::new(key, system_program::id(), vec![]) }
+    }
+
+    pub fn signer(key: Pubkey) -> Self {
+        TestAccount::wallet(key, 0, true)
+    }
+
+    pub fn with_lamports(self, lamports: u64) -> Self {
+        TestAccount { lamports, ..self }
+    }
+
+    pub fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(&self.key, self.is_signer, true, &mut self.lamports, &mut self.data, &self.owner, false, 0)
+    }
+}
+
+pub fn process(program_id: &Pubkey, accounts: &mut [TestAccount], instruction_data: &[u8]) -> ProgramResult {
+    let account_infos: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+    Processor::process(program_id, &account_infos, instruction_data)
+}
+
+pub fn packed<T: Pack>(state: T) -> Vec<u8> {
+    let mut data = vec![0; T::LEN];
+    T::pack(state, &mut data).unwrap();
+    data
+}
+
+/// An initialized token account of the original token program holding `amount` of `mint`
+pub fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Vec<u8> {
+    packed(Account {
+        mint,
+        owner,
+        amount,
+        state: AccountState::Initialized,
+        ..Account::default()
+    })
+}
+
+/// A mint of the original token program with a supply of one and no decimals
+pub fn nft_mint() -> Vec<u8> {
+    packed(Mint {
+        supply: 1,
+        decimals: 0,
+        is_initialized: true,
+        ..Mint::default()
+    })
+}
+
+// The rent sysvar as the runtime lays it out: lamports per byte-year, exemption threshold and burn percent
+pub fn bincode_rent() -> Vec<u8> {
+    let rent = Rent::default();
+    let mut data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+    data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+    data.push(rent.burn_percent);
+    data
+}
+
+/// Moves the lamports of a system program `Transfer` or `CreateAccount` between the accounts of a CPI. The tests
+/// create program accounts at their final size and owner up front, so creating one only funds it
+pub fn move_system_lamports(instruction: &Instruction, account_infos: &[AccountInfo]) {
+    let find = |key: &Pubkey| account_infos.iter().find(|account_info| account_info.key.eq(key)).unwrap();
+    let lamports = u64::from_le_bytes(instruction.data[4..12].try_into().unwrap());
+    **find(&instruction.accounts[0].pubkey).lamports.borrow_mut() -= lamports;
+    **find(&instruction.accounts[1].pubkey).lamports.borrow_mut() += lamports;
+}
+
+/// Opens escrow vaults, moves the NFT and closes the vaults on the token account data, system program calls only move
+/// lamports
+pub struct TokenMock;
+
+impl TokenMock {
+    fn find<'a, 'b>(account_infos: &'a [AccountInfo<'b>], key: &Pubkey) -> &'a AccountInfo<'b> {
+        account_infos.iter().find(|account_info| account_info.key.eq(key)).unwrap()
+    }
+
+    fn update(account_info: &AccountInfo, update: impl FnOnce(&mut Account)) -> ProgramResult {
+        let mut account = Account::unpack(&account_info.data.borrow())?;
+        update(&mut account);
+        Account::pack(account, &mut account_info.data.borrow_mut())
+    }
+}
+
+impl SyscallStubs for TokenMock {
+    fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], _signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|account_meta| account_meta.pubkey).collect();
+        if instruction.program_id.eq(&system_program::id()) {
+            move_system_lamports(instruction, account_infos);
+            return Ok(());
+        }
+
+        assert_eq!(instruction.program_id, spl_token::id());
+        match instruction.data[0] {
+            // InitializeAccount, of a vault the test created at its final size
+            1 => {
+                let vault = Self::find(account_infos, &keys[0]);
+                vault.data.borrow_mut().copy_from_slice(&token_account(keys[1], keys[2], 0));
+                Ok(())
+            }
+            // CloseAccount
+            9 => {
+                let vault = Self::find(account_infos, &keys[0]);
+                **Self::find(account_infos, &keys[1]).lamports.borrow_mut() += vault.lamports();
+                **vault.lamports.borrow_mut() = 0;
+                vault.data.borrow_mut().fill(0);
+                Ok(())
+            }
+            // TransferChecked
+            12 => {
+                Self::update(Self::find(account_infos, &keys[0]), |account| account.amount -= 1)?;
+                Self::update(Self::find(account_infos, &keys[2]), |account| account.amount += 1)
+            }
+            _ => panic!("unexpected token instruction {}", instruction.data[0]),
+        }
+    }
+}
@@ -0,0 +1,386 @@
+#[macro_use]
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{bincode_rent, move_system_lamports, packed, process, TestAccount};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::{ProgramResult, SUCCESS},
+    instruction::Instruction,
+    keccak,
+    program_error::ProgramError,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+    sysvar,
+};
+use test::{
+    compression,
+    error::NFTError,
+    state::{self, CompressedListEscrowState, FeeScheduleState, MintState, PlatformState, TraderState},
+};
+
+const LIST_ID: u64 = 0;
+const PRICE: u64 = 1_000_000_000;
+const PLATFORM_FEE: u64 = 20_000_000;
+const TREE_DEPTH: usize = 3;
+const LEAF_INDEX: u32 = 5;
+const NONCE: u64 = 5;
+// 1% platform fee for tier 1 traders, a quarter of fees going to the referrer
+const TIER_FEE: u64 = 10_000_000;
+const REFERRAL_FEE: u64 = 250_000_000;
+
+/// A Bubblegum `transfer` seen by the mock, with the program derived addresses that signed it
+struct Invocation {
+    instruction: Instruction,
+    signers: Vec<Pubkey>,
+}
+
+/// Mocks the Bubblegum interface: checks that the leaf owner signed and records the transfer. System program
+/// calls only move lamports, the tests create program accounts at their final size up front. Rent is the default
+/// schedule
+struct BubblegumMock {
+    program_id: Pubkey,
+    invocations: Arc<Mutex<Vec<Invocation>>>,
+}
+
+impl SyscallStubs for BubblegumMock {
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Rent) = Rent::default();
+        }
+        SUCCESS
+    }
+
+    fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        let signers: Vec<Pubkey> = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &self.program_id).unwrap())
+            .collect();
+
+        if instruction.program_id.eq(&system_program::id()) {
+            move_system_lamports(instruction, account_infos);
+            return Ok(());
+        }
+
+        assert_eq!(instruction.program_id, compression::id());
+        assert_eq!(instruction.data.len(), 8 + 96 + 8 + 4);
+        let leaf_owner = instruction.accounts[1].pubkey;
+        let owner_signed = signers.contains(&leaf_owner)
+            || account_infos.iter().any(|account_info| account_info.key.eq(&leaf_owner) && account_info.is_signer);
+        assert!(owner_signed, "leaf owner {} did not sign the transfer", leaf_owner);
+        for account_meta in instruction.accounts.iter() {
+            assert!(account_infos.iter().any(|account_info| account_info.key.eq(&account_meta.pubkey)));
+        }
+        self.invocations.lock().unwrap().push(Invocation {
+            instruction: instruction.clone(),
+            signers,
+        });
+        Ok(())
+    }
+}
+
+/// A full tree of depth `TREE_DEPTH` holding the listed leaf at `LEAF_INDEX` among random leaves
+struct Tree {
+    key: Pubkey,
+    asset_id: Pubkey,
+    data_hash: [u8; 32],
+    creator_hash: [u8; 32],
+    others: Vec<[u8; 32]>,
+}
+
+impl Tree {
+    fn new() -> Self {
+        let key = Pubkey::new_unique();
+        Tree {
+            key,
+            asset_id: compression::find_asset_id(&key, NONCE),
+            data_hash: Pubkey::new_unique().to_bytes(),
+            creator_hash: Pubkey::new_unique().to_bytes(),
+            others: (0..1 << TREE_DEPTH).map(|_| Pubkey::new_unique().to_bytes()).collect(),
+        }
+    }
+
+    fn levels(&self, owner: &Pubkey) -> Vec<Vec<[u8; 32]>> {
+        let mut leaves = self.others.clone();
+        leaves[LEAF_INDEX as usize] = compression::hash_leaf(&self.asset_id, owner, owner, NONCE, &self.data_hash, &self.creator_hash);
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap().chunks(2).map(|pair| keccak::hashv(&[&pair[0], &pair[1]]).to_bytes()).collect();
+            levels.push(level);
+        }
+        levels
+    }
+
+    fn root(&self, owner: &Pubkey) -> [u8; 32] {
+        self.levels(owner).last().unwrap()[0]
+    }
+
+    fn proof(&self, owner: &Pubkey) -> Vec<TestAccount> {
+        let levels = self.levels(owner);
+        (0..TREE_DEPTH)
+            .map(|level| {
+                let sibling = levels[level][(LEAF_INDEX as usize >> level) ^ 1];
+                TestAccount::new(Pubkey::new_from_array(sibling), Pubkey::default(), vec![])
+            })
+            .collect()
+    }
+
+    fn accounts(&self) -> Vec<TestAccount> {
+        vec![
+            TestAccount::new(compression::find_tree_authority(&self.key), compression::id(), vec![]),
+            TestAccount::new(self.key, compression::account_compression::id(), vec![]),
+            TestAccount::new(compression::noop::id(), Pubkey::default(), vec![]),
+            TestAccount::new(compression::account_compression::id(), Pubkey::default(), vec![]),
+            TestAccount::new(compression::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+        ]
+    }
+}
+
+struct Market {
+    program_id: Pubkey,
+    lister: Pubkey,
+    buyer: Pubkey,
+    tree: Tree,
+    state: Pubkey,
+}
+
+impl Market {
+    fn new(program_id: Pubkey) -> Self {
+        let lister = Pubkey::new_unique();
+        let tree = Tree::new();
+        let (state, _) = Pubkey::find_program_address(
+            &[tree.asset_id.as_ref(), lister.as_ref(), &LIST_ID.to_be_bytes(), b"CompressedList", b"State"],
+            &program_id,
+        );
+        Market { program_id, lister, buyer: Pubkey::new_unique(), tree, state }
+    }
+
+    fn platform_state(&self) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[b"Platform", b"State"], &self.program_id);
+        TestAccount::new(key, self.program_id, packed(PlatformState {
+            is_initialized: true,
+            authority: Pubkey::new_unique(),
+            platform_fee: PLATFORM_FEE,
+            referral_fee: REFERRAL_FEE,
+            ..PlatformState::default()
+        }))
+    }
+
+    /// The per-asset state, created by an earlier listing of the asset
+    fn mint_state(&self) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[self.tree.asset_id.as_ref(), b"Mint", b"State"], &self.program_id);
+        TestAccount::new(key, self.program_id, packed(MintState { is_initialized: true, mint: self.tree.asset_id, sale_count: 0 }))
+    }
+
+    fn fee_schedule(&self, tier_fees: &[u64]) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[b"Platform", b"FeeSchedule"], &self.program_id);
+        let data = match tier_fees {
+            [] => vec![],
+            _ => {
+                let mut fee_schedule = FeeScheduleState { is_initialized: true, ..FeeScheduleState::default() };
+                fee_schedule.tier_count = tier_fees.len() as u8;
+                fee_schedule.tier_fees[..tier_fees.len()].copy_from_slice(tier_fees);
+                packed(fee_schedule)
+            }
+        };
+        TestAccount::new(key, self.program_id, data)
+    }
+
+    fn trader_state(&self, tier: Option<u8>) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[self.lister.as_ref(), b"Trader", b"State"], &self.program_id);
+        let data = tier.map(|tier| packed(TraderState { is_initialized: true, trader: self.lister, tier })).unwrap_or_default();
+        TestAccount::new(key, self.program_id, data)
+    }
+
+    fn platform_vault(&self) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[b"Platform", b"Vault"], &self.program_id);
+        TestAccount::new(key, self.program_id, vec![])
+    }
+
+    fn list_state(&self) -> CompressedListEscrowState {
+        CompressedListEscrowState {
+            is_initialized: true,
+            lister: self.lister,
+            asset_id: self.tree.asset_id,
+            merkle_tree: self.tree.key,
+            data_hash: self.tree.data_hash,
+            creator_hash: self.tree.creator_hash,
+            nonce: NONCE,
+            index: LEAF_INDEX,
+            amount: PRICE,
+            list_id: LIST_ID,
+        }
+    }
+
+    fn list_data(&self) -> Vec<u8> {
+        let mut data = vec![29];
+        data.extend_from_slice(&PRICE.to_be_bytes());
+        data.extend_from_slice(&LIST_ID.to_be_bytes());
+        data.extend_from_slice(&self.tree.root(&self.lister));
+        data.extend_from_slice(&self.tree.data_hash);
+        data.extend_from_slice(&self.tree.creator_hash);
+        data.extend_from_slice(&NONCE.to_be_bytes());
+        data.extend_from_slice(&LEAF_INDEX.to_be_bytes());
+        data
+    }
+
+    fn list_accounts(&self) -> Vec<TestAccount> {
+        let mut accounts = vec![
+            TestAccount::wallet(self.lister, 10 * PRICE, true),
+            TestAccount::new(self.state, self.program_id, vec![0; state::COMPRESSEDLISTESCROWSTATE]),
+        ];
+        accounts.extend(self.tree.accounts());
+        accounts.push(TestAccount::new(sysvar::rent::id(), sysvar::id(), bincode_rent()));
+        accounts.push(self.platform_state());
+        accounts.push(self.mint_state());
+        accounts.extend(self.tree.proof(&self.lister));
+        accounts
+    }
+
+    fn accept_data(&self) -> Vec<u8> {
+        let mut data = vec![31];
+        data.extend_from_slice(&LIST_ID.to_be_bytes());
+        data.extend_from_slice(&self.tree.root(&self.state));
+        data
+    }
+
+    fn accept_accounts(&self) -> Vec<TestAccount> {
+        self.accept_accounts_with(vec![], None, None)
+    }
+
+    /// Accounts of `AcceptCompressedListing` under the given fee schedule tiers and lister tier, with `referrer`
+    /// between the fixed accounts and the tree accounts
+    fn accept_accounts_with(&self, tier_fees: Vec<u64>, tier: Option<u8>, referrer: Option<Pubkey>) -> Vec<TestAccount> {
+        let escrow_state = TestAccount::new(self.state, self.program_id, packed(self.list_state())).with_lamports(2_000_000);
+        let mut accounts = vec![
+            TestAccount::wallet(self.buyer, 10 * PRICE, true),
+            TestAccount::wallet(self.lister, 10 * PRICE, false),
+            self.platform_state(),
+            self.platform_vault(),
+            escrow_state,
+            self.mint_state(),
+            self.fee_schedule(&tier_fees),
+            self.trader_state(tier),
+        ];
+        accounts.extend(referrer.map(|referrer| TestAccount::wallet(referrer, 10 * PRICE, false)));
+        accounts.extend(self.tree.accounts());
+        accounts.extend(self.tree.proof(&self.state));
+        accounts
+    }
+}
+
+// The rent sysvar as the runtime lays it out: lamports per byte-year, exemption threshold and burn percent
+fn list_escrows_leaf(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let market = Market::new(program_id);
+    let mut accounts = market.list_accounts();
+    process(&program_id, &mut accounts, &market.list_data()).unwrap();
+
+    assert_eq!(CompressedListEscrowState::unpack(&accounts[1].data), Ok(market.list_state()));
+    assert_eq!(accounts[1].lamports, Rent::default().minimum_balance(state::COMPRESSEDLISTESCROWSTATE));
+
+    let invocations = invocations.lock().unwrap();
+    assert_eq!(invocations.len(), 1);
+    let instruction = &invocations[0].instruction;
+    let keys: Vec<Pubkey> = instruction.accounts.iter().map(|account_meta| account_meta.pubkey).collect();
+    let mut expected = vec![
+        compression::find_tree_authority(&market.tree.key),
+        market.lister,
+        market.lister,
+        market.state,
+        market.tree.key,
+        compression::noop::id(),
+        compression::account_compression::id(),
+        system_program::id(),
+    ];
+    expected.extend(accounts[11..].iter().map(|node| node.key));
+    assert_eq!(keys, expected);
+    assert_eq!(&instruction.data[8..40], &market.tree.root(&market.lister));
+    assert_eq!(&instruction.data[104..112], &NONCE.to_le_bytes());
+    assert_eq!(&instruction.data[112..116], &LEAF_INDEX.to_le_bytes());
+    assert!(invocations[0].signers.is_empty());
+}
+
+fn accept_pays_lister_and_transfers_leaf(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let market = Market::new(program_id);
+    let mut accounts = market.accept_accounts();
+    process(&program_id, &mut accounts, &market.accept_data()).unwrap();
+
+    let fee = state::calculate_fee(PRICE, PLATFORM_FEE);
+    assert_eq!(accounts[0].lamports, 10 * PRICE - PRICE);
+    assert_eq!(accounts[1].lamports, 10 * PRICE + PRICE - fee + 2_000_000);
+    assert_eq!(accounts[3].lamports, fee);
+    assert_eq!(accounts[4].lamports, 0);
+    assert_eq!(MintState::unpack(&accounts[5].data).unwrap().sale_count, 1);
+
+    // Bubblegum checks the root and the proof against the tree, they are passed through untouched
+    let invocations = invocations.lock().unwrap();
+    assert_eq!(invocations.len(), 1);
+    let instruction = &invocations[0].instruction;
+    assert_eq!(instruction.accounts[1].pubkey, market.state);
+    assert_eq!(instruction.accounts[3].pubkey, market.buyer);
+    let proof: Vec<Pubkey> = instruction.accounts[8..].iter().map(|account_meta| account_meta.pubkey).collect();
+    assert_eq!(proof, accounts[14..].iter().map(|node| node.key).collect::<Vec<_>>());
+    assert_eq!(&instruction.data[8..40], &market.tree.root(&market.state));
+    assert_eq!(invocations[0].signers, vec![market.state]);
+}
+
+fn accept_applies_tier_fee_and_referral(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let market = Market::new(program_id);
+    let referrer = Pubkey::new_unique();
+    let mut accounts = market.accept_accounts_with(vec![TIER_FEE], Some(1), Some(referrer));
+    process(&program_id, &mut accounts, &market.accept_data()).unwrap();
+
+    let fee = state::calculate_fee(PRICE, TIER_FEE);
+    let referral = state::calculate_fee(fee, REFERRAL_FEE);
+    assert_eq!(accounts[1].lamports, 10 * PRICE + PRICE - fee + 2_000_000);
+    assert_eq!(accounts[3].lamports, fee - referral);
+    assert_eq!(accounts[8].key, referrer);
+    assert_eq!(accounts[8].lamports, 10 * PRICE + referral);
+    assert_eq!(invocations.lock().unwrap().len(), 1);
+}
+
+fn accept_rejects_lister_as_referrer(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let market = Market::new(program_id);
+    let mut accounts = market.accept_accounts_with(vec![], None, Some(market.lister));
+    assert_eq!(process(&program_id, &mut accounts, &market.accept_data()), Err(NFTError::InvalidReferrer.into()));
+    assert!(invocations.lock().unwrap().is_empty());
+}
+
+fn delist_requires_lister(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let market = Market::new(program_id);
+    let mut accounts = vec![
+        TestAccount::wallet(market.buyer, 10 * PRICE, true),
+        TestAccount::new(market.state, program_id, packed(market.list_state())),
+    ];
+    accounts.extend(market.tree.accounts());
+    accounts.extend(market.tree.proof(&market.state));
+    let mut data = vec![30];
+    data.extend_from_slice(&LIST_ID.to_be_bytes());
+    data.extend_from_slice(&market.tree.root(&market.state));
+    assert_eq!(process(&program_id, &mut accounts, &data), Err(ProgramError::InvalidAccountData));
+    assert!(invocations.lock().unwrap().is_empty());
+}
+
+fn main() {
+    let program_id = Pubkey::new_unique();
+    let invocations = Arc::new(Mutex::new(Vec::new()));
+    program_stubs::set_syscall_stubs(Box::new(BubblegumMock {
+        program_id,
+        invocations: invocations.clone(),
+    }));
+
+    run_tests!("compressed_nft", [
+        list_escrows_leaf(&invocations, program_id),
+        accept_pays_lister_and_transfers_leaf(&invocations, program_id),
+        accept_applies_tier_fee_and_referral(&invocations, program_id),
+        accept_rejects_lister_as_referrer(&invocations, program_id),
+        delist_requires_lister(&invocations, program_id),
+    ], {
+        invocations.lock().unwrap().clear();
+    });
+}
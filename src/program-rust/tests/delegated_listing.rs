@@ -0,0 +1,381 @@
+#[macro_use]
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{bincode_rent, move_system_lamports, packed, process, token_account, TestAccount};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use spl_token::state::{Account, AccountState, Mint};
+use test::{
+    error::NFTError,
+    metadata,
+    order,
+    state::{self, DelegatedListEscrowState, MintState, OrderNonceState, PlatformState},
+};
+
+const LIST_ID: u64 = 0;
+const PRICE: u64 = 1_000_000_000;
+const PLATFORM_FEE: u64 = 20_000_000;
+// Index of the lister's order nonce state in the accounts of a fill
+const ORDER_NONCE: usize = 16;
+
+/// Mocks the token and metadata programs on the token account data itself, so the tests can check the delegate
+/// and freeze state the listing leaves behind. System program calls only move lamports, the tests create program
+/// accounts at their final size up front
+struct TokenMock {
+    program_id: Pubkey,
+    invocations: Arc<Mutex<Vec<(Pubkey, u8)>>>,
+}
+
+impl TokenMock {
+    fn find<'a, 'b>(account_infos: &'a [AccountInfo<'b>], key: &Pubkey) -> &'a AccountInfo<'b> {
+        account_infos.iter().find(|account_info| account_info.key.eq(key)).unwrap()
+    }
+
+    fn update(account_info: &AccountInfo, update: impl FnOnce(&mut Account) -> ProgramResult) -> ProgramResult {
+        let mut account = Account::unpack(&account_info.data.borrow())?;
+        update(&mut account)?;
+        Account::pack(account, &mut account_info.data.borrow_mut())
+    }
+
+    fn signed(&self, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]], key: &Pubkey) -> bool {
+        signers_seeds.iter().any(|seeds| Pubkey::create_program_address(seeds, &self.program_id).unwrap().eq(key))
+            || Self::find(account_infos, key).is_signer
+    }
+}
+
+impl SyscallStubs for TokenMock {
+    fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|account_meta| account_meta.pubkey).collect();
+        if instruction.program_id.eq(&system_program::id()) {
+            move_system_lamports(instruction, account_infos);
+            return Ok(());
+        }
+        self.invocations.lock().unwrap().push((instruction.program_id, instruction.data[0]));
+
+        if instruction.program_id.eq(&metadata::id()) {
+            assert!(self.signed(account_infos, signers_seeds, &keys[0]));
+            assert_eq!(keys[2], metadata::find_master_edition_address(&keys[3]).0);
+            return Self::update(Self::find(account_infos, &keys[1]), |account| {
+                assert_eq!(account.delegate, COption::Some(keys[0]));
+                account.state = match instruction.data[0] {
+                    26 => AccountState::Frozen,
+                    _ => AccountState::Initialized,
+                };
+                Ok(())
+            });
+        }
+
+        assert_eq!(instruction.program_id, spl_token::id());
+        let source = Self::find(account_infos, &keys[0]);
+        match instruction.data[0] {
+            // Approve
+            4 => {
+                assert!(self.signed(account_infos, signers_seeds, &keys[2]));
+                Self::update(source, |account| {
+                    account.delegate = COption::Some(keys[1]);
+                    account.delegated_amount = 1;
+                    Ok(())
+                })
+            }
+            // Revoke
+            5 => {
+                assert!(self.signed(account_infos, signers_seeds, &keys[1]));
+                Self::update(source, |account| {
+                    account.delegate = COption::None;
+                    account.delegated_amount = 0;
+                    Ok(())
+                })
+            }
+            // TransferChecked, by the delegate
+            12 => {
+                assert!(self.signed(account_infos, signers_seeds, &keys[3]));
+                Self::update(source, |account| {
+                    if account.is_frozen() || account.delegate != COption::Some(keys[3]) {
+                        return Err(ProgramError::InvalidAccountData);
+                    }
+                    account.amount -= 1;
+                    account.delegate = COption::None;
+                    account.delegated_amount = 0;
+                    Ok(())
+                })?;
+                Self::update(Self::find(account_infos, &keys[2]), |account| {
+                    account.amount += 1;
+                    Ok(())
+                })
+            }
+            _ => panic!("unexpected token instruction {}", instruction.data[0]),
+        }
+    }
+}
+
+// The rent sysvar as the runtime lays it out: lamports per byte-year, exemption threshold and burn percent
+struct Market {
+    program_id: Pubkey,
+    lister: Pubkey,
+    buyer: Pubkey,
+    mint: Pubkey,
+    lister_token: Pubkey,
+    buyer_token: Pubkey,
+    state: Pubkey,
+}
+
+impl Market {
+    fn new(program_id: Pubkey) -> Self {
+        let lister = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let (state, _) = Pubkey::find_program_address(
+            &[mint.as_ref(), lister.as_ref(), &LIST_ID.to_be_bytes(), b"DelegatedList", b"State"],
+            &program_id,
+        );
+        Market {
+            program_id,
+            lister,
+            buyer: Pubkey::new_unique(),
+            mint,
+            lister_token: Pubkey::new_unique(),
+            buyer_token: Pubkey::new_unique(),
+            state,
+        }
+    }
+
+    fn edition(&self) -> Pubkey {
+        metadata::find_master_edition_address(&self.mint).0
+    }
+
+    fn mint_account(&self, freeze_authority: COption<Pubkey>) -> TestAccount {
+        TestAccount::new(self.mint, spl_token::id(), packed(Mint {
+            supply: 1,
+            decimals: 0,
+            is_initialized: true,
+            freeze_authority,
+            ..Mint::default()
+        }))
+    }
+
+    fn pda(&self, seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &self.program_id).0
+    }
+
+    fn platform_state(&self) -> TestAccount {
+        TestAccount::new(self.pda(&[b"Platform", b"State"]), self.program_id, packed(PlatformState {
+            is_initialized: true,
+            authority: Pubkey::new_unique(),
+            platform_fee: PLATFORM_FEE,
+            ..PlatformState::default()
+        }))
+    }
+
+    fn mint_state(&self, data: Vec<u8>) -> TestAccount {
+        TestAccount::new(self.pda(&[self.mint.as_ref(), b"Mint", b"State"]), self.program_id, data)
+    }
+
+    fn list_state(&self, frozen: bool) -> DelegatedListEscrowState {
+        DelegatedListEscrowState {
+            is_initialized: true,
+            lister: self.lister,
+            mint: self.mint,
+            token_account: self.lister_token,
+            amount: PRICE,
+            list_id: LIST_ID,
+            frozen,
+        }
+    }
+
+    fn list_accounts(&self, freeze_authority: COption<Pubkey>) -> Vec<TestAccount> {
+        vec![
+            TestAccount::wallet(self.lister, 10 * PRICE, true),
+            TestAccount::new(self.lister_token, spl_token::id(), token_account(self.mint, self.lister, 1)),
+            self.mint_account(freeze_authority),
+            TestAccount::new(self.state, self.program_id, vec![0; state::DELEGATEDLISTESCROWSTATE]),
+            TestAccount::new(self.edition(), metadata::id(), vec![]),
+            TestAccount::new(metadata::id(), Pubkey::default(), vec![]),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            TestAccount::new(sysvar::rent::id(), sysvar::id(), bincode_rent()),
+            self.mint_state(packed(MintState { is_initialized: true, mint: self.mint, sale_count: 0 })),
+            self.platform_state(),
+        ]
+    }
+
+    fn list_data(&self) -> Vec<u8> {
+        let mut data = vec![32];
+        data.extend_from_slice(&PRICE.to_be_bytes());
+        data.extend_from_slice(&LIST_ID.to_be_bytes());
+        data
+    }
+
+    /// Accounts of a fill after `list_accounts` went through `ListDelegated`
+    fn accept_accounts(&self, listed: &[TestAccount]) -> Vec<TestAccount> {
+        let escrow_state = TestAccount::new(self.state, self.program_id, listed[3].data.clone()).with_lamports(2_000_000);
+        vec![
+            TestAccount::wallet(self.buyer, 10 * PRICE, true),
+            TestAccount::new(self.buyer_token, spl_token::id(), token_account(self.mint, self.buyer, 0)),
+            TestAccount::new(self.mint, spl_token::id(), listed[2].data.clone()),
+            TestAccount::wallet(self.lister, 10 * PRICE, false),
+            TestAccount::new(self.lister_token, spl_token::id(), listed[1].data.clone()),
+            self.platform_state(),
+            TestAccount::new(self.pda(&[b"Platform", b"Vault"]), self.program_id, vec![]),
+            escrow_state,
+            TestAccount::new(self.edition(), metadata::id(), vec![]),
+            TestAccount::new(metadata::id(), Pubkey::default(), vec![]),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            self.mint_state(listed[9].data.clone()),
+            TestAccount::new(self.pda(&[b"Platform", b"FeeSchedule"]), self.program_id, vec![]),
+            TestAccount::new(metadata::find_metadata_address(&self.mint).0, metadata::id(), vec![]),
+            TestAccount::new(self.pda(&[self.lister.as_ref(), b"Trader", b"State"]), self.program_id, vec![]),
+            TestAccount::new(self.pda(&[self.lister.as_ref(), b"OrderNonce", b"State"]), self.program_id, vec![]),
+        ]
+    }
+
+    fn accept_data(&self) -> Vec<u8> {
+        let mut data = vec![34];
+        data.extend_from_slice(&LIST_ID.to_be_bytes());
+        data
+    }
+
+    fn listed(&self, freeze_authority: COption<Pubkey>) -> Vec<TestAccount> {
+        let mut accounts = self.list_accounts(freeze_authority);
+        process(&self.program_id, &mut accounts, &self.list_data()).unwrap();
+        accounts
+    }
+}
+
+fn list_delegates_and_freezes_in_place(invocations: &Mutex<Vec<(Pubkey, u8)>>, program_id: Pubkey) {
+    let market = Market::new(program_id);
+    let accounts = market.listed(COption::Some(market.edition()));
+
+    assert_eq!(DelegatedListEscrowState::unpack(&accounts[3].data), Ok(market.list_state(true)));
+    let lister_token = Account::unpack(&accounts[1].data).unwrap();
+    assert_eq!(lister_token.owner, market.lister);
+    assert_eq!(lister_token.amount, 1);
+    assert_eq!(lister_token.delegate, COption::Some(market.state));
+    assert_eq!(lister_token.delegated_amount, 1);
+    assert!(lister_token.is_frozen());
+    assert_eq!(MintState::unpack(&accounts[9].data).unwrap().sale_count, 0);
+    assert_eq!(*invocations.lock().unwrap(), vec![(spl_token::id(), 4), (metadata::id(), 26)]);
+}
+
+fn list_only_delegates_without_edition_freeze_authority(invocations: &Mutex<Vec<(Pubkey, u8)>>, program_id: Pubkey) {
+    let market = Market::new(program_id);
+    let accounts = market.listed(COption::None);
+
+    assert_eq!(DelegatedListEscrowState::unpack(&accounts[3].data), Ok(market.list_state(false)));
+    let lister_token = Account::unpack(&accounts[1].data).unwrap();
+    assert_eq!(lister_token.delegate, COption::Some(market.state));
+    assert!(!lister_token.is_frozen());
+    assert_eq!(*invocations.lock().unwrap(), vec![(spl_token::id(), 4)]);
+}
+
+fn accept_moves_nft_from_seller_account(invocations: &Mutex<Vec<(Pubkey, u8)>>, program_id: Pubkey) {
+    let market = Market::new(program_id);
+    let listed = market.listed(COption::Some(market.edition()));
+    invocations.lock().unwrap().clear();
+    let mut accounts = market.accept_accounts(&listed);
+    process(&program_id, &mut accounts, &market.accept_data()).unwrap();
+
+    let fee = state::calculate_fee(PRICE, PLATFORM_FEE);
+    assert_eq!(accounts[0].lamports, 10 * PRICE - PRICE);
+    assert_eq!(accounts[3].lamports, 10 * PRICE + PRICE - fee + 2_000_000);
+    assert_eq!(accounts[6].lamports, fee);
+    assert_eq!(accounts[7].lamports, 0);
+    assert_eq!(Account::unpack(&accounts[1].data).unwrap().amount, 1);
+    let lister_token = Account::unpack(&accounts[4].data).unwrap();
+    assert_eq!(lister_token.amount, 0);
+    assert!(!lister_token.is_frozen());
+    assert_eq!(MintState::unpack(&accounts[12].data).unwrap().sale_count, 1);
+    assert_eq!(*invocations.lock().unwrap(), vec![(metadata::id(), 27), (spl_token::id(), 12)]);
+}
+
+fn accept_rejects_stale_delegation(invocations: &Mutex<Vec<(Pubkey, u8)>>, program_id: Pubkey) {
+    let market = Market::new(program_id);
+    let listed = market.listed(COption::None);
+    invocations.lock().unwrap().clear();
+
+    // The lister approved someone else after listing
+    let mut accounts = market.accept_accounts(&listed);
+    let mut lister_token = Account::unpack(&accounts[4].data).unwrap();
+    lister_token.delegate = COption::Some(Pubkey::new_unique());
+    Account::pack(lister_token, &mut accounts[4].data).unwrap();
+    assert_eq!(process(&program_id, &mut accounts, &market.accept_data()), Err(NFTError::StaleDelegation.into()));
+
+    // The lister moved the NFT away after listing
+    let mut accounts = market.accept_accounts(&listed);
+    let mut lister_token = Account::unpack(&accounts[4].data).unwrap();
+    lister_token.amount = 0;
+    Account::pack(lister_token, &mut accounts[4].data).unwrap();
+    assert_eq!(process(&program_id, &mut accounts, &market.accept_data()), Err(NFTError::StaleDelegation.into()));
+
+    assert!(invocations.lock().unwrap().is_empty());
+}
+
+fn accept_rejects_cancelled_listing(invocations: &Mutex<Vec<(Pubkey, u8)>>, program_id: Pubkey) {
+    let market = Market::new(program_id);
+    let listed = market.listed(COption::None);
+    invocations.lock().unwrap().clear();
+
+    let mut order_nonce_state = OrderNonceState { is_initialized: true, owner: market.lister, ..OrderNonceState::default() };
+    order_nonce_state.cancel(&order::delegated_listing_hash(&market.state));
+    let mut accounts = market.accept_accounts(&listed);
+    accounts[ORDER_NONCE].data = packed(order_nonce_state);
+    assert_eq!(process(&program_id, &mut accounts, &market.accept_data()), Err(NFTError::OrderCancelled.into()));
+
+    // Raising the nonce floor above the list id cancels the listing too
+    let order_nonce_state = OrderNonceState { is_initialized: true, owner: market.lister, min_nonce: LIST_ID + 1, ..OrderNonceState::default() };
+    let mut accounts = market.accept_accounts(&listed);
+    accounts[ORDER_NONCE].data = packed(order_nonce_state);
+    assert_eq!(process(&program_id, &mut accounts, &market.accept_data()), Err(NFTError::OrderCancelled.into()));
+
+    assert!(invocations.lock().unwrap().is_empty());
+}
+
+fn delist_thaws_and_revokes(invocations: &Mutex<Vec<(Pubkey, u8)>>, program_id: Pubkey) {
+    let market = Market::new(program_id);
+    let mut accounts = market.listed(COption::Some(market.edition()));
+    invocations.lock().unwrap().clear();
+    accounts[3].lamports = 2_000_000;
+
+    let mut data = vec![33];
+    data.extend_from_slice(&LIST_ID.to_be_bytes());
+    let lamports = accounts[0].lamports;
+    process(&program_id, &mut accounts[..7], &data).unwrap();
+
+    let lister_token = Account::unpack(&accounts[1].data).unwrap();
+    assert_eq!(lister_token.delegate, COption::None);
+    assert!(!lister_token.is_frozen());
+    assert_eq!(accounts[0].lamports, lamports + 2_000_000);
+    assert_eq!(accounts[3].lamports, 0);
+    assert_eq!(*invocations.lock().unwrap(), vec![(metadata::id(), 27), (spl_token::id(), 5)]);
+}
+
+fn main() {
+    let program_id = Pubkey::new_unique();
+    let invocations = Arc::new(Mutex::new(Vec::new()));
+    program_stubs::set_syscall_stubs(Box::new(TokenMock {
+        program_id,
+        invocations: invocations.clone(),
+    }));
+
+    run_tests!("delegated_listing", [
+        list_delegates_and_freezes_in_place(&invocations, program_id),
+        list_only_delegates_without_edition_freeze_authority(&invocations, program_id),
+        accept_moves_nft_from_seller_account(&invocations, program_id),
+        accept_rejects_stale_delegation(&invocations, program_id),
+        accept_rejects_cancelled_listing(&invocations, program_id),
+        delist_thaws_and_revokes(&invocations, program_id),
+    ], {
+        invocations.lock().unwrap().clear();
+    });
+}
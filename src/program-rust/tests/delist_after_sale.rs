@@ -0,0 +1,273 @@
+// Regression test: once AcceptBid marks a listing as sold, the lister can no longer DeList it and the NFT stays in
+// escrow for the buyer's WithdrawNFTOnSuccess
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use test::{
+    error::NFTError,
+    metadata,
+    state::{self, PlatformState},
+};
+
+const LIST_ID: u64 = 0;
+const BID_ID: u64 = 0;
+const PRICE: u64 = 1_000_000_000;
+
+struct Market {
+    program_id: Pubkey,
+    mint: Pubkey,
+    lister: Keypair,
+    lister_token: Pubkey,
+    bidder: Keypair,
+    bidder_token: Pubkey,
+}
+
+impl Market {
+    fn pda(&self, seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &self.program_id).0
+    }
+
+    fn list_pda(&self, kind: &[u8]) -> Pubkey {
+        self.pda(&[self.mint.as_ref(), self.lister.pubkey().as_ref(), &LIST_ID.to_be_bytes(), b"List", kind])
+    }
+
+    fn bid_pda(&self, kind: &[u8]) -> Pubkey {
+        self.pda(&[self.mint.as_ref(), self.bidder.pubkey().as_ref(), &BID_ID.to_be_bytes(), b"Bid", kind])
+    }
+
+    fn order_book_pda(&self) -> Pubkey {
+        self.pda(&[self.mint.as_ref(), b"OrderBook", b"State"])
+    }
+
+    fn instruction(&self, data: Vec<u8>, accounts: Vec<AccountMeta>) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        }
+    }
+
+    fn list(&self) -> Instruction {
+        let mut data = vec![3];
+        data.extend_from_slice(&PRICE.to_be_bytes());
+        data.extend_from_slice(&LIST_ID.to_be_bytes());
+        data.extend_from_slice(&0u64.to_be_bytes());
+        self.instruction(data, vec![
+            AccountMeta::new(self.lister.pubkey(), true),
+            AccountMeta::new(self.lister_token, false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new(self.list_pda(b"State"), false),
+            AccountMeta::new(self.list_pda(b"Vault"), false),
+            AccountMeta::new_readonly(self.program_id, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new(self.pda(&[self.mint.as_ref(), b"Mint", b"State"]), false),
+            AccountMeta::new_readonly(self.pda(&[b"Platform", b"State"]), false),
+            AccountMeta::new(self.order_book_pda(), false),
+        ])
+    }
+
+    fn bid(&self) -> Instruction {
+        let mut data = vec![5];
+        data.extend_from_slice(&PRICE.to_be_bytes());
+        data.extend_from_slice(&BID_ID.to_be_bytes());
+        self.instruction(data, vec![
+            AccountMeta::new(self.bidder.pubkey(), true),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new(self.bid_pda(b"State"), false),
+            AccountMeta::new(self.bid_pda(b"Vault"), false),
+            AccountMeta::new_readonly(self.program_id, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(self.pda(&[b"Platform", b"State"]), false),
+            AccountMeta::new(self.order_book_pda(), false),
+        ])
+    }
+
+    fn accept_bid(&self) -> Instruction {
+        let mut data = vec![7];
+        data.extend_from_slice(&BID_ID.to_be_bytes());
+        data.extend_from_slice(&LIST_ID.to_be_bytes());
+        self.instruction(data, vec![
+            AccountMeta::new(self.lister.pubkey(), true),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new(self.bidder.pubkey(), false),
+            AccountMeta::new_readonly(self.pda(&[b"Platform", b"State"]), false),
+            AccountMeta::new(self.pda(&[b"Platform", b"Vault"]), false),
+            AccountMeta::new(self.bid_pda(b"State"), false),
+            AccountMeta::new(self.bid_pda(b"Vault"), false),
+            AccountMeta::new(self.list_pda(b"State"), false),
+            AccountMeta::new(self.list_pda(b"Vault"), false),
+            AccountMeta::new(self.pda(&[self.mint.as_ref(), b"Mint", b"State"]), false),
+            AccountMeta::new_readonly(self.pda(&[b"Platform", b"FeeSchedule"]), false),
+            AccountMeta::new_readonly(metadata::find_metadata_address(&self.mint).0, false),
+            AccountMeta::new_readonly(self.pda(&[self.lister.pubkey().as_ref(), b"Trader", b"State"]), false),
+            AccountMeta::new(self.order_book_pda(), false),
+        ])
+    }
+
+    fn delist(&self) -> Instruction {
+        let mut data = vec![4];
+        data.extend_from_slice(&LIST_ID.to_be_bytes());
+        self.instruction(data, vec![
+            AccountMeta::new(self.lister.pubkey(), true),
+            AccountMeta::new(self.lister_token, false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new(self.list_pda(b"State"), false),
+            AccountMeta::new(self.list_pda(b"Vault"), false),
+            AccountMeta::new_readonly(self.program_id, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(self.order_book_pda(), false),
+        ])
+    }
+
+    fn withdraw_nft_on_success(&self) -> Instruction {
+        let mut data = vec![8];
+        data.extend_from_slice(&LIST_ID.to_be_bytes());
+        self.instruction(data, vec![
+            AccountMeta::new(self.bidder.pubkey(), true),
+            AccountMeta::new(self.bidder_token, false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new(self.lister.pubkey(), false),
+            AccountMeta::new(self.list_pda(b"State"), false),
+            AccountMeta::new(self.list_pda(b"Vault"), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ])
+    }
+}
+
+fn packed<T: Pack>(state: T) -> Vec<u8> {
+    let mut data = vec![0; T::LEN];
+    T::pack(state, &mut data).unwrap();
+    data
+}
+
+fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+    Account {
+        lamports: 10_000_000,
+        data: packed(spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            delegate: COption::None,
+            state: spl_token::state::AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        }),
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn wallet() -> Account {
+    Account {
+        lamports: 10 * PRICE,
+        data: vec![],
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+async fn token_amount(banks_client: &mut BanksClient, token_account: Pubkey) -> u64 {
+    let account = banks_client.get_account(token_account).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data).unwrap().amount
+}
+
+async fn delist_after_accept_bid() {
+    let market = Market {
+        program_id: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        lister: Keypair::new(),
+        lister_token: Pubkey::new_unique(),
+        bidder: Keypair::new(),
+        bidder_token: Pubkey::new_unique(),
+    };
+
+    let mut program_test = ProgramTest::new("test", market.program_id, processor!(test::process_instruction));
+    program_test.add_account(market.mint, Account {
+        lamports: 10_000_000,
+        data: packed(spl_token::state::Mint {
+            mint_authority: COption::None,
+            supply: 1,
+            decimals: 0,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }),
+        owner: spl_token::id(),
+        executable: false,
+        rent_epoch: 0,
+    });
+    program_test.add_account(market.lister.pubkey(), wallet());
+    program_test.add_account(market.bidder.pubkey(), wallet());
+    program_test.add_account(market.lister_token, token_account(market.mint, market.lister.pubkey(), 1));
+    program_test.add_account(market.bidder_token, token_account(market.mint, market.bidder.pubkey(), 0));
+    program_test.add_account(market.pda(&[b"Platform", b"State"]), Account {
+        lamports: 10_000_000,
+        data: packed(PlatformState {
+            is_initialized: true,
+            authority: Pubkey::new_unique(),
+            platform_fee: 10_000_000,
+            ..PlatformState::default()
+        }),
+        owner: market.program_id,
+        executable: false,
+        rent_epoch: 0,
+    });
+    program_test.add_account(market.pda(&[b"Platform", b"Vault"]), Account {
+        lamports: 10_000_000,
+        data: vec![],
+        owner: market.program_id,
+        executable: false,
+        rent_epoch: 0,
+    });
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let send = |instruction: Instruction, signer: &Keypair| {
+        Transaction::new_signed_with_payer(&[instruction], Some(&payer.pubkey()), &[&payer, signer], recent_blockhash)
+    };
+
+    banks_client.process_transaction(send(market.list(), &market.lister)).await.unwrap();
+    banks_client.process_transaction(send(market.bid(), &market.bidder)).await.unwrap();
+    banks_client.process_transaction(send(market.accept_bid(), &market.lister)).await.unwrap();
+
+    let error = banks_client
+        .process_transaction(send(market.delist(), &market.lister))
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(0, InstructionError::Custom(NFTError::ListingAlreadyFullfilled as u32))
+    );
+    assert_eq!(token_amount(&mut banks_client, market.lister_token).await, 0);
+    let list_state = banks_client.get_account(market.list_pda(b"State")).await.unwrap().unwrap();
+    assert_eq!(list_state.data.len(), state::LISTESCROWSTATE);
+
+    banks_client
+        .process_transaction(send(market.withdraw_nft_on_success(), &market.bidder))
+        .await
+        .unwrap();
+    assert_eq!(token_amount(&mut banks_client, market.bidder_token).await, 1);
+    assert!(banks_client.get_account(market.list_pda(b"Vault")).await.unwrap().is_none());
+}
+
+fn main() {
+    tokio::runtime::Runtime::new().unwrap().block_on(delist_after_accept_bid());
+    println!("delist_after_sale: 1 passed");
+}
@@ -0,0 +1,106 @@
+#[macro_use]
+mod common;
+
+use common::{bincode_rent, packed, process, TestAccount};
+use solana_program::{
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar,
+};
+use test::state::{split_fees, FeeDistributionState, FeeRecipient, BPS_DENOMINATOR, MAX_FEE_RECIPIENTS};
+
+fn recipients(bps: &[u16]) -> Vec<FeeRecipient> {
+    bps.iter()
+        .map(|&bps| FeeRecipient {
+            recipient: Pubkey::new_unique(),
+            bps,
+        })
+        .collect()
+}
+
+fn split_is_proportional() {
+    let shares = split_fees(1_000_000, &recipients(&[5000, 3000, 2000]));
+    assert_eq!(shares, vec![500_000, 300_000, 200_000]);
+}
+
+fn dust_goes_to_last_recipient() {
+    let shares = split_fees(10, &recipients(&[3333, 3333, 3334]));
+    assert_eq!(shares, vec![3, 3, 4]);
+
+    let shares = split_fees(1, &recipients(&[5000, 5000]));
+    assert_eq!(shares, vec![0, 1]);
+}
+
+fn no_dust_is_stranded() {
+    let tables = [
+        vec![BPS_DENOMINATOR],
+        vec![1, BPS_DENOMINATOR - 1],
+        vec![3333, 3333, 3334],
+        vec![1250; 8],
+        vec![7, 13, 2980, 7000],
+    ];
+    let amounts = [0, 1, 2, 3, 7, 9_999, 10_001, 123_456_789, u64::MAX / 3, u64::MAX];
+    for table in tables.iter() {
+        let recipients = recipients(table);
+        for &amount in amounts.iter() {
+            let shares = split_fees(amount, &recipients);
+            assert_eq!(shares.len(), recipients.len());
+            assert_eq!(shares.iter().map(|&share| share as u128).sum::<u128>(), amount as u128);
+        }
+    }
+}
+
+// The accounts of `DistributeFees` for a vault holding `fees` above its rent and a distribution over `table`
+fn distribute_accounts(program_id: &Pubkey, table: &[FeeRecipient], fees: u64) -> Vec<TestAccount> {
+    let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, program_id).0;
+    let mut recipients = [FeeRecipient::default(); MAX_FEE_RECIPIENTS];
+    recipients[..table.len()].copy_from_slice(table);
+    let mut accounts = vec![
+        TestAccount::signer(Pubkey::new_unique()),
+        TestAccount::new(pda(&[b"Platform", b"Vault"]), *program_id, vec![]).with_lamports(Rent::default().minimum_balance(0) + fees),
+        TestAccount::new(pda(&[b"Platform", b"Distribution"]), *program_id, packed(FeeDistributionState {
+            is_initialized: true,
+            recipient_count: table.len() as u8,
+            recipients,
+        })),
+        TestAccount::new(sysvar::rent::id(), sysvar::id(), bincode_rent()),
+    ];
+    accounts.extend(table.iter().map(|recipient| TestAccount::wallet(recipient.recipient, 0, false)));
+    accounts
+}
+
+fn distribute_drains_vault_to_rent() {
+    let program_id = Pubkey::new_unique();
+    let table = recipients(&[5000, 3000, 2000]);
+    let mut accounts = distribute_accounts(&program_id, &table, 1_000_001);
+    process(&program_id, &mut accounts, &[23]).unwrap();
+
+    assert_eq!(accounts[1].lamports, Rent::default().minimum_balance(0));
+    let paid: Vec<(Pubkey, u64)> = accounts[4..].iter().map(|account| (account.key, account.lamports)).collect();
+    let expected: Vec<(Pubkey, u64)> = table.iter().map(|recipient| recipient.recipient).zip([500_000, 300_000, 200_001]).collect();
+    assert_eq!(paid, expected);
+
+    // Nothing is left above rent to pay out a second time
+    process(&program_id, &mut accounts, &[23]).unwrap();
+    assert_eq!(accounts[1].lamports, Rent::default().minimum_balance(0));
+}
+
+fn distribute_requires_recipients_in_table_order() {
+    let program_id = Pubkey::new_unique();
+    let table = recipients(&[5000, 5000]);
+    let mut accounts = distribute_accounts(&program_id, &table, 1_000_000);
+    accounts.swap(4, 5);
+    assert_eq!(process(&program_id, &mut accounts, &[23]), Err(ProgramError::InvalidAccountData));
+    assert_eq!(accounts[1].lamports, Rent::default().minimum_balance(0) + 1_000_000);
+}
+
+fn main() {
+    run_tests!("fee_distribution", [
+        split_is_proportional(),
+        dust_goes_to_last_recipient(),
+        no_dust_is_stranded(),
+        distribute_drains_vault_to_rent(),
+        distribute_requires_recipients_in_table_order(),
+    ]);
+}
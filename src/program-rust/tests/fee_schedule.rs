@@ -0,0 +1,92 @@
+#[macro_use]
+mod common;
+
+use common::{packed, process, TestAccount};
+use solana_program::{
+    entrypoint::ProgramResult,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use test::{
+    error::NFTError,
+    state::{self, FeeScheduleState, PlatformState},
+};
+
+struct Schedule {
+    program_id: Pubkey,
+    accounts: Vec<TestAccount>,
+}
+
+impl Schedule {
+    fn new() -> Self {
+        let program_id = Pubkey::new_unique();
+        let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &program_id).0;
+        let authority = Pubkey::new_unique();
+        let accounts = vec![
+            TestAccount::wallet(authority, 1_000_000_000, true),
+            TestAccount::new(pda(&[b"Platform", b"State"]), program_id, packed(PlatformState {
+                is_initialized: true,
+                authority,
+                platform_fee: 20_000_000,
+                ..PlatformState::default()
+            })),
+            TestAccount::new(pda(&[b"Platform", b"FeeSchedule"]), program_id, packed(FeeScheduleState {
+                is_initialized: true,
+                ..FeeScheduleState::default()
+            })),
+            TestAccount::new(program_id, Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            TestAccount::new(sysvar::rent::id(), sysvar::id(), vec![]),
+        ];
+        Schedule { program_id, accounts }
+    }
+
+    fn process(&mut self, data: &[u8]) -> ProgramResult {
+        process(&self.program_id, &mut self.accounts, data)
+    }
+
+    fn fee_schedule(&self) -> FeeScheduleState {
+        FeeScheduleState::unpack(&self.accounts[2].data).unwrap()
+    }
+}
+
+fn set_tier_fee(tier: u8, platform_fee: u64) -> Vec<u8> {
+    let mut data = vec![20, tier];
+    data.extend_from_slice(&platform_fee.to_be_bytes());
+    data
+}
+
+fn set_collection_fee(collection: &Pubkey, platform_fee: u64) -> Vec<u8> {
+    let mut data = vec![19];
+    data.extend_from_slice(collection.as_ref());
+    data.extend_from_slice(&platform_fee.to_be_bytes());
+    data
+}
+
+fn rejects_tier_fee_above_whole_amount() {
+    let mut schedule = Schedule::new();
+    assert_eq!(schedule.process(&set_tier_fee(1, state::FEE_DENOMINATOR + 1)), Err(NFTError::InvalidPlatformFee.into()));
+    assert_eq!(schedule.fee_schedule().tier_count, 0);
+
+    schedule.process(&set_tier_fee(1, state::FEE_DENOMINATOR)).unwrap();
+    assert_eq!(schedule.fee_schedule().tier_fee(1), Some(state::FEE_DENOMINATOR));
+}
+
+fn rejects_collection_fee_above_whole_amount() {
+    let mut schedule = Schedule::new();
+    let collection = Pubkey::new_unique();
+    assert_eq!(schedule.process(&set_collection_fee(&collection, u64::MAX)), Err(NFTError::InvalidPlatformFee.into()));
+    assert_eq!(schedule.fee_schedule().collection_fee(&collection), None);
+
+    schedule.process(&set_collection_fee(&collection, 5_000_000)).unwrap();
+    assert_eq!(schedule.fee_schedule().collection_fee(&collection), Some(5_000_000));
+}
+
+fn main() {
+    run_tests!("fee_schedule", [
+        rejects_tier_fee_above_whole_amount(),
+        rejects_collection_fee_above_whole_amount(),
+    ]);
+}
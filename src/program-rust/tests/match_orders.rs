@@ -0,0 +1,221 @@
+#[macro_use]
+mod common;
+
+use common::{packed, process, TestAccount};
+use solana_program::{
+    clock::Clock,
+    entrypoint::{ProgramResult, SUCCESS},
+    program_error::ProgramError,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+};
+use test::{
+    error::NFTError,
+    state::{self, BidEscrowState, FeeBearer, ListEscrowState, MintState, OrderBookBid, OrderBookState, PlatformState},
+};
+
+const NOW: i64 = 1_700_000_000;
+const PRICE: u64 = 1_000_000_000;
+// 2% platform fee, all of it paid by the seller
+const PLATFORM_FEE: u64 = 20_000_000;
+const BOND: u64 = 10_000;
+
+/// The clock always reads `NOW` and rent is the default schedule
+struct SysvarMock;
+
+impl SyscallStubs for SysvarMock {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Clock) = Clock { unix_timestamp: NOW, ..Clock::default() };
+        }
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Rent) = Rent::default();
+        }
+        SUCCESS
+    }
+}
+
+const KEEPER: usize = 0;
+const LISTER: usize = 2;
+const BIDDER: usize = 3;
+const PLATFORM_STATE: usize = 4;
+const VAULT: usize = 5;
+const BID_STATE: usize = 6;
+const BID_VAULT: usize = 7;
+const LIST_STATE: usize = 8;
+const ORDER_BOOK: usize = 13;
+
+struct Match {
+    program_id: Pubkey,
+    accounts: Vec<TestAccount>,
+}
+
+impl Match {
+    fn new(program_id: Pubkey, list_amount: u64, bid_amount: u64, expiry: i64) -> Self {
+        let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &program_id).0;
+        let mint = Pubkey::new_unique();
+        let lister = Pubkey::new_unique();
+        let bidder = Pubkey::new_unique();
+        let bid_seeds = |kind: &[u8]| pda(&[mint.as_ref(), bidder.as_ref(), &0u64.to_be_bytes(), b"Bid", kind]);
+
+        let bid_vault = TestAccount::new(bid_seeds(b"Vault"), program_id, vec![])
+            .with_lamports(Rent::default().minimum_balance(0) + bid_amount + BOND);
+        let bid_state = TestAccount::new(bid_seeds(b"State"), program_id, packed(BidEscrowState {
+            is_initialized: true,
+            bidder,
+            mint,
+            amount: bid_amount,
+            bond: BOND,
+            expiry,
+            ..BidEscrowState::default()
+        }))
+        .with_lamports(Rent::default().minimum_balance(state::BIDESCROWSTATE));
+
+        let mut order_book = OrderBookState { is_initialized: true, mint, ..OrderBookState::default() };
+        order_book.set_listing(&lister, 0, list_amount);
+        order_book.insert_bid(OrderBookBid { bidder, bid_id: 0, amount: bid_amount, expiry }, NOW - 1);
+
+        let accounts = vec![
+            TestAccount::signer(Pubkey::new_unique()),
+            TestAccount::new(mint, spl_token::id(), vec![]),
+            TestAccount::new(lister, Pubkey::default(), vec![]),
+            TestAccount::new(bidder, Pubkey::default(), vec![]),
+            TestAccount::new(pda(&[b"Platform", b"State"]), program_id, packed(PlatformState {
+                is_initialized: true,
+                platform_fee: PLATFORM_FEE,
+                ..PlatformState::default()
+            })),
+            TestAccount::new(pda(&[b"Platform", b"Vault"]), program_id, vec![]),
+            bid_state,
+            bid_vault,
+            TestAccount::new(pda(&[mint.as_ref(), lister.as_ref(), &0u64.to_be_bytes(), b"List", b"State"]), program_id, packed(ListEscrowState {
+                is_initialized: true,
+                lister,
+                mint,
+                amount: list_amount,
+                ..ListEscrowState::default()
+            })),
+            TestAccount::new(pda(&[mint.as_ref(), b"Mint", b"State"]), program_id, packed(MintState {
+                is_initialized: true,
+                mint,
+                sale_count: 0,
+            })),
+            TestAccount::new(pda(&[b"Platform", b"FeeSchedule"]), program_id, vec![]),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+            TestAccount::new(pda(&[lister.as_ref(), b"Trader", b"State"]), program_id, vec![]),
+            TestAccount::new(pda(&[mint.as_ref(), b"OrderBook", b"State"]), program_id, packed(order_book)),
+        ];
+        Match { program_id, accounts }
+    }
+
+    fn process(&mut self) -> ProgramResult {
+        let mut data = vec![35];
+        data.extend_from_slice(&0u64.to_be_bytes());
+        data.extend_from_slice(&0u64.to_be_bytes());
+        process(&self.program_id, &mut self.accounts, &data)
+    }
+
+    fn total_lamports(&self) -> u64 {
+        self.accounts.iter().map(|account| account.lamports).sum()
+    }
+}
+
+fn crossing_bid_fills_at_listing_price(program_id: Pubkey) {
+    let bid_amount = PRICE + PRICE / 2;
+    let mut orders = Match::new(program_id, PRICE, bid_amount, 0);
+    let escrowed = orders.accounts[BID_STATE].lamports + orders.accounts[BID_VAULT].lamports;
+    let total = orders.total_lamports();
+    orders.process().unwrap();
+
+    let fee = state::calculate_fee(PRICE, PLATFORM_FEE);
+    let keeper_reward = state::calculate_fee(fee, state::KEEPER_REWARD);
+    assert!(keeper_reward > 0);
+    assert_eq!(orders.accounts[KEEPER].lamports, keeper_reward);
+    assert_eq!(orders.accounts[VAULT].lamports, fee - keeper_reward);
+    assert_eq!(orders.accounts[LISTER].lamports, PRICE - fee);
+    assert_eq!(orders.accounts[BIDDER].lamports, escrowed - PRICE);
+    assert_eq!(orders.accounts[BID_STATE].lamports + orders.accounts[BID_VAULT].lamports, 0);
+    assert_eq!(orders.total_lamports(), total);
+
+    let list_state = ListEscrowState::unpack(&orders.accounts[LIST_STATE].data).unwrap();
+    assert!(list_state.success);
+    assert_eq!(list_state.amount, PRICE);
+    assert_eq!(list_state.successful_buyer, orders.accounts[BIDDER].key);
+    let order_book = OrderBookState::unpack(&orders.accounts[ORDER_BOOK].data).unwrap();
+    assert!(!order_book.has_listing());
+    assert!(order_book.bids().is_empty());
+}
+
+fn rejects_bid_below_listing(program_id: Pubkey) {
+    let mut orders = Match::new(program_id, PRICE, PRICE - 1, 0);
+    assert_eq!(orders.process(), Err(NFTError::OrdersDoNotCross.into()));
+}
+
+fn rejects_expired_bid(program_id: Pubkey) {
+    let mut orders = Match::new(program_id, PRICE, PRICE, NOW);
+    assert_eq!(orders.process(), Err(NFTError::BidExpired.into()));
+}
+
+fn rejects_filled_listing(program_id: Pubkey) {
+    let mut orders = Match::new(program_id, PRICE, PRICE, 0);
+    orders.process().unwrap();
+    assert_eq!(orders.process(), Err(NFTError::ListingAlreadyFullfilled.into()));
+}
+
+fn requires_keeper_signature(program_id: Pubkey) {
+    let mut orders = Match::new(program_id, PRICE, PRICE, 0);
+    orders.accounts[KEEPER].is_signer = false;
+    assert_eq!(orders.process(), Err(ProgramError::MissingRequiredSignature));
+}
+
+fn rejects_listing_reserved_for_another_buyer(program_id: Pubkey) {
+    let mut orders = Match::new(program_id, PRICE, PRICE, 0);
+    let mut list_state = ListEscrowState::unpack(&orders.accounts[LIST_STATE].data).unwrap();
+    list_state.reserved_buyer = Pubkey::new_unique();
+    orders.accounts[LIST_STATE].data = packed(list_state);
+    let total_lamports = orders.total_lamports();
+    assert_eq!(orders.process(), Err(NFTError::ListingReserved.into()));
+    assert_eq!(orders.total_lamports(), total_lamports);
+}
+
+fn caps_buyer_fee_at_escrow(program_id: Pubkey) {
+    // The bid escrowed a quarter of the fee where the buyer's half is due
+    let fee = state::calculate_fee(PRICE, PLATFORM_FEE);
+    let mut orders = Match::new(program_id, PRICE, PRICE, 0);
+    orders.accounts[PLATFORM_STATE].data = packed(PlatformState {
+        is_initialized: true,
+        platform_fee: PLATFORM_FEE,
+        fee_bearer: FeeBearer::Split,
+        ..PlatformState::default()
+    });
+    orders.accounts[BID_VAULT].lamports += fee / 4;
+    let escrowed = orders.accounts[BID_STATE].lamports + orders.accounts[BID_VAULT].lamports;
+    orders.process().unwrap();
+
+    let charged = fee / 2 + fee / 4;
+    let keeper_reward = state::calculate_fee(charged, state::KEEPER_REWARD);
+    assert_eq!(orders.accounts[LISTER].lamports, PRICE - fee / 2);
+    assert_eq!(orders.accounts[VAULT].lamports, charged - keeper_reward);
+    assert_eq!(orders.accounts[BIDDER].lamports, escrowed - PRICE - fee / 4);
+}
+
+fn main() {
+    let program_id = Pubkey::new_unique();
+    program_stubs::set_syscall_stubs(Box::new(SysvarMock));
+
+    run_tests!("match_orders", [
+        crossing_bid_fills_at_listing_price(program_id),
+        rejects_bid_below_listing(program_id),
+        rejects_expired_bid(program_id),
+        rejects_filled_listing(program_id),
+        requires_keeper_signature(program_id),
+        rejects_listing_reserved_for_another_buyer(program_id),
+        caps_buyer_fee_at_escrow(program_id),
+    ]);
+}
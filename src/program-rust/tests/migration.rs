@@ -0,0 +1,120 @@
+#[macro_use]
+mod common;
+
+use common::{packed, TestAccount};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::{MAX_PERMITTED_DATA_INCREASE, SUCCESS},
+    program_error::ProgramError,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+use test::{
+    processor::Processor,
+    state::{self, FeeBearer, MintState, PlatformState},
+};
+
+struct RentStub;
+
+impl SyscallStubs for RentStub {
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Rent) = Rent::default();
+        }
+        SUCCESS
+    }
+}
+
+fn migrate_state(program_id: &Pubkey, mut account: TestAccount) -> (Result<(), ProgramError>, TestAccount) {
+    // The runtime serializes an account's length before its data and leaves room after it to grow into
+    let len = account.data.len();
+    let mut buffer = vec![0; 8 + len + MAX_PERMITTED_DATA_INCREASE];
+    buffer[..8].copy_from_slice(&(len as u64).to_le_bytes());
+    buffer[8..8 + len].copy_from_slice(&account.data);
+
+    let mut signer = TestAccount::wallet(Pubkey::new_unique(), 1_000_000_000, true);
+    let mut system_program = TestAccount::new(system_program::id(), Pubkey::default(), vec![]);
+    let state = AccountInfo::new(&account.key, false, true, &mut account.lamports, &mut buffer[8..8 + len], &account.owner, false, 0);
+    let account_infos = [signer.info(), state, system_program.info()];
+    let result = Processor::process(program_id, &account_infos, &[40]);
+    let len = account_infos[1].data_len();
+    drop(account_infos);
+    account.data = buffer[8..8 + len].to_vec();
+    (result, account)
+}
+
+// Initialized flag, authority and platform fee, the platform state of the original program
+fn baseline_platform_state(authority: Pubkey) -> Vec<u8> {
+    let mut legacy = vec![1];
+    legacy.extend_from_slice(authority.as_ref());
+    legacy.extend_from_slice(&20_000_000u64.to_be_bytes());
+    legacy
+}
+
+fn migrates_baseline_platform_state() {
+    let authority = Pubkey::new_unique();
+    let data = state::migrate_platform_state(&baseline_platform_state(authority)).unwrap().unwrap();
+    assert_eq!(PlatformState::unpack(&data), Ok(PlatformState {
+        is_initialized: true,
+        authority,
+        platform_fee: 20_000_000,
+        fee_bearer: FeeBearer::Seller,
+        ..PlatformState::default()
+    }));
+}
+
+fn leaves_current_platform_state_alone() {
+    let platform_state = PlatformState { is_initialized: true, authority: Pubkey::new_unique(), ..PlatformState::default() };
+    assert_eq!(state::migrate_platform_state(&packed(platform_state)), Ok(None));
+}
+
+fn rejects_corrupt_platform_state() {
+    // Longer than the current layout, so it cannot be legacy
+    assert_eq!(state::migrate_platform_state(&[1; 80]), Err(ProgramError::InvalidAccountData));
+    // Flagged as another account type
+    assert_eq!(state::migrate_platform_state(&[7; 41]), Err(ProgramError::InvalidAccountData));
+}
+
+fn migrate_state_upgrades_in_place() {
+    let program_id = Pubkey::new_unique();
+    program_stubs::set_syscall_stubs(Box::new(RentStub));
+
+    let (key, _) = Pubkey::find_program_address(&[b"Platform", b"State"], &program_id);
+    let authority = Pubkey::new_unique();
+    let account = TestAccount::new(key, program_id, baseline_platform_state(authority)).with_lamports(1_000_000_000);
+    let (result, account) = migrate_state(&program_id, account);
+    assert_eq!(result, Ok(()));
+    let platform_state = PlatformState::unpack(&account.data).unwrap();
+    assert_eq!((platform_state.authority, platform_state.platform_fee), (authority, 20_000_000));
+
+    // Running it again changes nothing
+    let (result, account) = migrate_state(&program_id, account);
+    assert_eq!(result, Ok(()));
+    assert_eq!(PlatformState::unpack(&account.data), Ok(platform_state));
+
+    let foreign = TestAccount::new(key, Pubkey::new_unique(), vec![1; 41]);
+    let (result, foreign) = migrate_state(&program_id, foreign);
+    assert_eq!(result, Err(ProgramError::IllegalOwner));
+    assert_eq!(foreign.data, vec![1; 41]);
+}
+
+fn migrate_state_only_takes_platform_state(program_id: Pubkey) {
+    let mint_state = MintState { is_initialized: true, mint: Pubkey::new_unique(), sale_count: 4 };
+    let account = TestAccount::new(Pubkey::new_unique(), program_id, packed(mint_state)).with_lamports(1_000_000_000);
+    let (result, account) = migrate_state(&program_id, account);
+    assert_eq!(result, Err(ProgramError::InvalidAccountData));
+    assert_eq!(MintState::unpack(&account.data), Ok(mint_state));
+}
+
+fn main() {
+    run_tests!("migration", [
+        migrates_baseline_platform_state(),
+        leaves_current_platform_state_alone(),
+        rejects_corrupt_platform_state(),
+        migrate_state_upgrades_in_place(),
+        migrate_state_only_takes_platform_state(Pubkey::new_unique()),
+    ]);
+}
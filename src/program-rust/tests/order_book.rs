@@ -0,0 +1,371 @@
+#[macro_use]
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{bincode_rent, move_system_lamports, packed, process, TestAccount};
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::{ProgramResult, SUCCESS},
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+use test::{
+    error::NFTError,
+    state::{self, AccountType, BidEscrowState, OrderBookBid, OrderBookState, PlatformState, MAX_ORDER_BOOK_BIDS},
+};
+
+const NOW: i64 = 1_700_000_000;
+const BID: u64 = 1_000_000_000;
+
+/// System program calls only move lamports, the tests create program accounts at their final size up front. The
+/// clock always reads `NOW` and the program's logs are kept
+struct SystemMock {
+    logs: Arc<Mutex<Vec<String>>>,
+}
+
+impl SyscallStubs for SystemMock {
+    fn sol_log(&self, message: &str) {
+        self.logs.lock().unwrap().push(message.to_string());
+    }
+
+    fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], _signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        assert_eq!(instruction.program_id, system_program::id());
+        move_system_lamports(instruction, account_infos);
+        Ok(())
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Clock) = Clock { unix_timestamp: NOW, ..Clock::default() };
+        }
+        SUCCESS
+    }
+}
+
+// The rent sysvar as the runtime lays it out: lamports per byte-year, exemption threshold and burn percent
+fn bid(amount: u64, expiry: i64) -> OrderBookBid {
+    OrderBookBid { bidder: Pubkey::new_unique(), bid_id: 0, amount, expiry }
+}
+
+fn amounts(order_book: &OrderBookState) -> Vec<u64> {
+    order_book.bids().iter().map(|bid| bid.amount).collect()
+}
+
+fn book_sorts_bids_and_evicts_lowest() {
+    let mut order_book = OrderBookState { is_initialized: true, ..OrderBookState::default() };
+    let first = bid(5, 0);
+    assert!(order_book.insert_bid(first, NOW));
+    assert!(order_book.insert_bid(bid(7, 0), NOW));
+    let second = bid(5, 0);
+    assert!(order_book.insert_bid(second, NOW));
+    assert!(order_book.insert_bid(bid(3, 0), NOW));
+    assert_eq!(amounts(&order_book), vec![7, 5, 5, 3]);
+    // Equal amounts keep their arrival order
+    assert_eq!(order_book.bids()[1], first);
+    assert_eq!(order_book.bids()[2], second);
+
+    for _ in 4..MAX_ORDER_BOOK_BIDS {
+        assert!(order_book.insert_bid(bid(4, 0), NOW));
+    }
+    assert_eq!(order_book.bids().len(), MAX_ORDER_BOOK_BIDS);
+    assert!(!order_book.insert_bid(bid(3, 0), NOW));
+    assert!(order_book.insert_bid(bid(6, 0), NOW));
+    assert_eq!(order_book.bids().len(), MAX_ORDER_BOOK_BIDS);
+    assert_eq!(amounts(&order_book)[..3], [7, 6, 5]);
+    assert_eq!(*amounts(&order_book).last().unwrap(), 4);
+
+    order_book.remove_bid(&first.bidder, first.bid_id);
+    assert_eq!(amounts(&order_book)[..3], [7, 6, 5]);
+    assert_eq!(order_book.bids()[2], second);
+
+    let mut data = vec![0; state::ORDERBOOKSTATE];
+    OrderBookState::pack(order_book, &mut data).unwrap();
+    assert_eq!(OrderBookState::unpack(&data), Ok(order_book));
+}
+
+fn book_skips_and_prunes_expired_bids() {
+    let mut order_book = OrderBookState { is_initialized: true, ..OrderBookState::default() };
+    order_book.insert_bid(bid(9, NOW - 10), NOW - 20);
+    order_book.insert_bid(bid(8, NOW + 10), NOW - 20);
+    assert_eq!(order_book.best_bid(NOW).unwrap().amount, 8);
+    assert_eq!(order_book.best_bid(NOW + 10), None);
+
+    order_book.insert_bid(bid(1, 0), NOW);
+    assert_eq!(amounts(&order_book), vec![8, 1]);
+}
+
+fn book_clears_only_its_listing() {
+    let lister = Pubkey::new_unique();
+    let mut order_book = OrderBookState { is_initialized: true, ..OrderBookState::default() };
+    order_book.set_listing(&lister, 1, BID);
+    order_book.remove_listing(&lister, 2);
+    order_book.remove_listing(&Pubkey::new_unique(), 1);
+    assert!(order_book.has_listing());
+    order_book.remove_listing(&lister, 1);
+    assert!(!order_book.has_listing());
+}
+
+struct Market {
+    program_id: Pubkey,
+    mint: Pubkey,
+    order_book: TestAccount,
+}
+
+impl Market {
+    fn new(program_id: Pubkey) -> Self {
+        let mint = Pubkey::new_unique();
+        let (key, _) = Pubkey::find_program_address(&[mint.as_ref(), b"OrderBook", b"State"], &program_id);
+        let order_book = TestAccount::new(key, program_id, packed(OrderBookState {
+            is_initialized: true,
+            mint,
+            ..OrderBookState::default()
+        }));
+        Market { program_id, mint, order_book }
+    }
+
+    fn pda(&self, seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &self.program_id).0
+    }
+
+    fn order_book(&self) -> OrderBookState {
+        OrderBookState::unpack(&self.order_book.data).unwrap()
+    }
+
+    fn bid(&mut self, bidder: Pubkey, amount: u64, expiry: i64) -> ProgramResult {
+        let state = self.pda(&[self.mint.as_ref(), bidder.as_ref(), &0u64.to_be_bytes(), b"Bid", b"State"]);
+        let vault = self.pda(&[self.mint.as_ref(), bidder.as_ref(), &0u64.to_be_bytes(), b"Bid", b"Vault"]);
+        let mut accounts = vec![
+            TestAccount::wallet(bidder, 10 * BID, true),
+            TestAccount::new(self.mint, spl_token::id(), vec![]),
+            TestAccount::new(state, self.program_id, vec![0; state::BIDESCROWSTATE]),
+            TestAccount::new(vault, self.program_id, vec![]),
+            TestAccount::new(self.program_id, Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            TestAccount::new(sysvar::rent::id(), sysvar::id(), bincode_rent()),
+            TestAccount::new(self.pda(&[b"Platform", b"State"]), self.program_id, packed(PlatformState {
+                is_initialized: true,
+                ..PlatformState::default()
+            })),
+            std::mem::replace(&mut self.order_book, TestAccount::new(Pubkey::default(), Pubkey::default(), vec![])),
+        ];
+        let mut data = vec![5];
+        data.extend_from_slice(&amount.to_be_bytes());
+        data.extend_from_slice(&0u64.to_be_bytes());
+        data.extend_from_slice(&(expiry as u64).to_be_bytes());
+        let result = process(&self.program_id, &mut accounts, &data);
+        if result.is_ok() {
+            assert_eq!(BidEscrowState::unpack(&accounts[2].data).unwrap().expiry, expiry);
+        }
+        self.order_book = accounts.pop().unwrap();
+        result
+    }
+
+    fn withdraw_bid(&mut self, bidder: Pubkey, amount: u64) -> ProgramResult {
+        let state = self.pda(&[self.mint.as_ref(), bidder.as_ref(), &0u64.to_be_bytes(), b"Bid", b"State"]);
+        let vault = self.pda(&[self.mint.as_ref(), bidder.as_ref(), &0u64.to_be_bytes(), b"Bid", b"Vault"]);
+        let mut accounts = vec![
+            TestAccount::signer(bidder),
+            TestAccount::new(self.mint, spl_token::id(), vec![]),
+            TestAccount::new(state, self.program_id, packed(BidEscrowState {
+                is_initialized: true,
+                bidder,
+                mint: self.mint,
+                amount,
+                ..BidEscrowState::default()
+            })),
+            TestAccount::new(vault, self.program_id, vec![]),
+            TestAccount::new(self.program_id, Pubkey::default(), vec![]),
+            std::mem::replace(&mut self.order_book, TestAccount::new(Pubkey::default(), Pubkey::default(), vec![])),
+        ];
+        let mut data = vec![6];
+        data.extend_from_slice(&0u64.to_be_bytes());
+        let result = process(&self.program_id, &mut accounts, &data);
+        self.order_book = accounts.pop().unwrap();
+        result
+    }
+
+    /// The accounts of `bidder`'s bid escrow, as `RefundUser`, `SlashBid` and `BulkRefund` take them
+    fn bid_escrow(&self, bidder: Pubkey, amount: u64) -> [TestAccount; 2] {
+        let state = self.pda(&[self.mint.as_ref(), bidder.as_ref(), &0u64.to_be_bytes(), b"Bid", b"State"]);
+        let vault = self.pda(&[self.mint.as_ref(), bidder.as_ref(), &0u64.to_be_bytes(), b"Bid", b"Vault"]);
+        [
+            TestAccount::new(state, self.program_id, packed(BidEscrowState {
+                is_initialized: true,
+                bidder,
+                mint: self.mint,
+                amount,
+                ..BidEscrowState::default()
+            })),
+            TestAccount::new(vault, self.program_id, vec![]).with_lamports(amount),
+        ]
+    }
+
+    /// Closes `bidder`'s bid as the platform authority with `RefundUser`, `SlashBid` or `BulkRefund`
+    fn refund_bid(&mut self, tag: u8, bidder: Pubkey, amount: u64) -> ProgramResult {
+        let mut accounts = self.refund_accounts(tag, bidder, amount);
+        self.process_refund(tag, &mut accounts)
+    }
+
+    /// Accounts of the `tag` refund of `bidder`'s bid, the order book last
+    fn refund_accounts(&mut self, tag: u8, bidder: Pubkey, amount: u64) -> Vec<TestAccount> {
+        let authority = Pubkey::new_unique();
+        let authority_account = TestAccount::signer(authority);
+        let platform_state = TestAccount::new(self.pda(&[b"Platform", b"State"]), self.program_id, packed(PlatformState {
+            is_initialized: true,
+            authority,
+            ..PlatformState::default()
+        }));
+        let [escrow_state, escrow_vault] = self.bid_escrow(bidder, amount);
+        let bidder_account = TestAccount::new(bidder, system_program::id(), vec![]);
+        let mut accounts = match tag {
+            12 => vec![
+                authority_account,
+                platform_state,
+                TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+                escrow_state,
+                escrow_vault,
+                bidder_account,
+            ],
+            _ => {
+                let mut accounts = vec![
+                    authority_account,
+                    TestAccount::new(self.mint, spl_token::id(), vec![]),
+                    bidder_account,
+                    platform_state,
+                ];
+                if tag == 28 {
+                    accounts.push(TestAccount::new(self.pda(&[b"Platform", b"Vault"]), self.program_id, vec![]));
+                }
+                accounts.extend([escrow_state, escrow_vault]);
+                accounts
+            }
+        };
+        accounts.push(std::mem::replace(&mut self.order_book, TestAccount::new(Pubkey::default(), Pubkey::default(), vec![])));
+        accounts
+    }
+
+    /// Runs a refund on `accounts` and puts the order book back into the market
+    fn process_refund(&mut self, tag: u8, accounts: &mut Vec<TestAccount>) -> ProgramResult {
+        let mut data = vec![tag];
+        data.extend_from_slice(&0u64.to_be_bytes());
+        let result = process(&self.program_id, accounts, &data);
+        self.order_book = accounts.pop().unwrap();
+        result
+    }
+}
+
+fn bids_are_indexed_until_withdrawn(program_id: Pubkey) {
+    let mut market = Market::new(program_id);
+    let (low, high) = (Pubkey::new_unique(), Pubkey::new_unique());
+    market.bid(low, BID, 0).unwrap();
+    market.bid(high, 2 * BID, NOW + 60).unwrap();
+
+    let order_book = market.order_book();
+    let expected = [
+        OrderBookBid { bidder: high, bid_id: 0, amount: 2 * BID, expiry: NOW + 60 },
+        OrderBookBid { bidder: low, bid_id: 0, amount: BID, expiry: 0 },
+    ];
+    assert_eq!(order_book.bids(), &expected[..]);
+    assert_eq!(order_book.best_bid(NOW), Some(&expected[0]));
+
+    market.withdraw_bid(high, 2 * BID).unwrap();
+    assert_eq!(market.order_book().bids(), &expected[1..]);
+}
+
+fn refunds_drop_bids_from_book(program_id: Pubkey) {
+    let mut market = Market::new(program_id);
+    let bidders = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    for bidder in bidders.iter() {
+        market.bid(*bidder, BID, 0).unwrap();
+    }
+    assert_eq!(market.order_book().bids().len(), 3);
+
+    market.refund_bid(10, bidders[0], BID).unwrap();
+    market.refund_bid(28, bidders[1], BID).unwrap();
+    market.refund_bid(12, bidders[2], BID).unwrap();
+    assert!(market.order_book().bids().is_empty());
+}
+
+fn bulk_refund_rejects_other_account_types(program_id: Pubkey) {
+    let mut market = Market::new(program_id);
+    let bidder = Pubkey::new_unique();
+    market.bid(bidder, BID, 0).unwrap();
+
+    // Sized like a bid escrow, but typed as another program account
+    let mut accounts = market.refund_accounts(12, bidder, BID);
+    accounts[3].data[0] = AccountType::Mint as u8;
+    assert_eq!(market.process_refund(12, &mut accounts), Err(ProgramError::InvalidAccountData));
+    assert_eq!(market.order_book().bids().len(), 1);
+}
+
+fn bulk_refund_stops_before_budget_runs_out(logs: &Mutex<Vec<String>>, program_id: Pubkey) {
+    let mut market = Market::new(program_id);
+    let bidders = [Pubkey::new_unique(), Pubkey::new_unique()];
+    for bidder in bidders.iter() {
+        market.bid(*bidder, BID, 0).unwrap();
+    }
+
+    // Both bids of the book, with only enough budget to refund the first
+    let mut accounts = market.refund_accounts(12, bidders[0], BID);
+    let order_book = &accounts[accounts.len() - 1];
+    let order_book = TestAccount::new(order_book.key, program_id, order_book.data.clone());
+    let [escrow_state, escrow_vault] = market.bid_escrow(bidders[1], BID);
+    accounts.extend([escrow_state, escrow_vault, TestAccount::new(bidders[1], system_program::id(), vec![]), order_book]);
+    let mut data = vec![12];
+    data.extend_from_slice(&(state::BULK_REFUND_BASE_COMPUTE + state::BID_REFUND_COMPUTE).to_be_bytes());
+    logs.lock().unwrap().clear();
+    process(&program_id, &mut accounts, &data).unwrap();
+
+    assert_eq!(logs.lock().unwrap().last().unwrap(), "{action: \"BulkRefund\", processed: 1, next: 4, total: 8}");
+    assert_eq!((accounts[5].lamports, accounts[9].lamports), (BID, 0));
+    assert_eq!(accounts[8].lamports, BID);
+    market.order_book = accounts.remove(6);
+    assert_eq!(market.order_book().bids().len(), 1);
+    assert_eq!(market.order_book().bids()[0].bidder, bidders[1]);
+}
+
+fn slash_checks_escrow_before_taking_bond(program_id: Pubkey) {
+    let mut market = Market::new(program_id);
+    let bidder = Pubkey::new_unique();
+    market.bid(bidder, BID, 0).unwrap();
+
+    let mut accounts = market.refund_accounts(28, bidder, BID);
+    let bid_state = BidEscrowState::unpack(&accounts[5].data).unwrap();
+    accounts[5].data = packed(BidEscrowState { bond: BID / 10, ..bid_state });
+    accounts[6].key = Pubkey::new_unique();
+    assert_eq!(market.process_refund(28, &mut accounts), Err(ProgramError::InvalidAccountData));
+    assert_eq!(accounts[4].lamports, 0);
+    assert_eq!(accounts[6].lamports, BID);
+    assert_eq!(market.order_book().bids().len(), 1);
+}
+
+fn bid_rejects_past_expiry(program_id: Pubkey) {
+    let mut market = Market::new(program_id);
+    assert_eq!(market.bid(Pubkey::new_unique(), BID, NOW), Err(NFTError::BidExpired.into()));
+    assert!(market.order_book().bids().is_empty());
+}
+
+fn main() {
+    let program_id = Pubkey::new_unique();
+    let logs = Arc::new(Mutex::new(Vec::new()));
+    program_stubs::set_syscall_stubs(Box::new(SystemMock { logs: logs.clone() }));
+
+    run_tests!("order_book", [
+        book_sorts_bids_and_evicts_lowest(),
+        book_skips_and_prunes_expired_bids(),
+        book_clears_only_its_listing(),
+        bids_are_indexed_until_withdrawn(program_id),
+        refunds_drop_bids_from_book(program_id),
+        bulk_refund_rejects_other_account_types(program_id),
+        bulk_refund_stops_before_budget_runs_out(&logs, program_id),
+        slash_checks_escrow_before_taking_bond(program_id),
+        bid_rejects_past_expiry(program_id),
+    ]);
+}
@@ -0,0 +1,562 @@
+#[macro_use]
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{bincode_rent, move_system_lamports, nft_mint, packed, process, token_account, TestAccount};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+    sysvar,
+};
+use test::{
+    metadata::{self, TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE},
+    state::{self, ListEscrowState, MintState, OrderBookState, PlatformState},
+};
+
+const LIST_ID: u64 = 0;
+const PRICE: u64 = 1_000_000_000;
+// 2% platform fee, all of it paid by the seller, and a tenth of it to the referrer
+const PLATFORM_FEE: u64 = 20_000_000;
+const REFERRAL_FEE: u64 = 100_000_000;
+const TOKEN_STANDARD_NON_FUNGIBLE: u8 = 0;
+
+/// A cross-program invocation seen by the stub: the instruction and the program derived addresses that signed it
+struct Invocation {
+    instruction: Instruction,
+    signers: Vec<Pubkey>,
+}
+
+/// Stands in for the metadata program. Like the runtime it requires every account of the instruction to be passed
+/// along, then records the call instead of moving the NFT. System program calls are recorded too and only move
+/// lamports
+struct MetadataProgramStub {
+    program_id: Pubkey,
+    invocations: Arc<Mutex<Vec<Invocation>>>,
+}
+
+impl SyscallStubs for MetadataProgramStub {
+    fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        if instruction.program_id.eq(&system_program::id()) {
+            move_system_lamports(instruction, account_infos);
+        } else {
+            assert_eq!(instruction.program_id, metadata::id());
+            assert!(account_infos.iter().any(|account_info| account_info.key.eq(&metadata::id())));
+        }
+        for account_meta in instruction.accounts.iter() {
+            assert!(
+                account_infos.iter().any(|account_info| account_info.key.eq(&account_meta.pubkey)),
+                "account {} was not passed to the invoked program",
+                account_meta.pubkey
+            );
+        }
+        let signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &self.program_id).unwrap())
+            .collect();
+        self.invocations.lock().unwrap().push(Invocation {
+            instruction: instruction.clone(),
+            signers,
+        });
+        Ok(())
+    }
+}
+
+// Borsh layout of a metadata account up to the collection, with empty strings and no creators
+fn metadata_account(mint: Pubkey, token_standard: u8) -> Vec<u8> {
+    let mut data = vec![4];
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    data.extend_from_slice(mint.as_ref());
+    data.extend_from_slice(&[0; 12]);
+    data.extend_from_slice(&[0, 0, 0, 1, 1, 0]);
+    data.extend_from_slice(&[1, token_standard, 0]);
+    data
+}
+
+struct Listing {
+    program_id: Pubkey,
+    mint: Pubkey,
+    lister: Pubkey,
+    buyer: Pubkey,
+    authority: Pubkey,
+    state: Pubkey,
+    vault: Pubkey,
+}
+
+impl Listing {
+    fn new(program_id: Pubkey) -> Self {
+        let mint = Pubkey::new_unique();
+        let lister = Pubkey::new_unique();
+        let (state, _) = Pubkey::find_program_address(
+            &[mint.as_ref(), lister.as_ref(), &LIST_ID.to_be_bytes(), b"List", b"State"],
+            &program_id,
+        );
+        Listing {
+            program_id,
+            mint,
+            lister,
+            buyer: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            state,
+            vault: metadata::find_programmable_vault_address(&state, &mint),
+        }
+    }
+
+    fn state_account(&self, successful_buyer: Option<Pubkey>) -> TestAccount {
+        TestAccount::new(self.state, self.program_id, packed(ListEscrowState {
+            is_initialized: true,
+            lister: self.lister,
+            mint: self.mint,
+            amount: PRICE,
+            success: successful_buyer.is_some(),
+            successful_buyer: successful_buyer.unwrap_or_default(),
+            list_id: LIST_ID,
+            ..ListEscrowState::default()
+        }))
+        .with_lamports(1_000_000)
+    }
+
+    // A mint listed before it had an order book
+    fn order_book_account(&self) -> TestAccount {
+        let (key, _) = Pubkey::find_program_address(&[self.mint.as_ref(), b"OrderBook", b"State"], &self.program_id);
+        TestAccount::new(key, self.program_id, vec![])
+    }
+
+    fn pda(&self, seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &self.program_id).0
+    }
+
+    fn platform_state(&self) -> TestAccount {
+        TestAccount::new(self.pda(&[b"Platform", b"State"]), self.program_id, packed(PlatformState {
+            is_initialized: true,
+            authority: self.authority,
+            platform_fee: PLATFORM_FEE,
+            referral_fee: REFERRAL_FEE,
+            ..PlatformState::default()
+        }))
+    }
+
+    // A mint that was listed and sold before
+    fn mint_state_account(&self) -> TestAccount {
+        TestAccount::new(self.pda(&[self.mint.as_ref(), b"Mint", b"State"]), self.program_id, packed(MintState {
+            is_initialized: true,
+            mint: self.mint,
+            sale_count: 1,
+        }))
+    }
+
+    fn programmable_accounts(&self, source: Pubkey, destination: Pubkey, token_standard: u8) -> Vec<TestAccount> {
+        vec![
+            TestAccount::new(metadata::find_metadata_address(&self.mint).0, metadata::id(), metadata_account(self.mint, token_standard)),
+            TestAccount::new(metadata::find_master_edition_address(&self.mint).0, metadata::id(), vec![]),
+            TestAccount::new(metadata::find_token_record_address(&self.mint, &source).0, metadata::id(), vec![]),
+            TestAccount::new(metadata::find_token_record_address(&self.mint, &destination).0, system_program::id(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            TestAccount::new(sysvar::instructions::id(), Pubkey::default(), vec![]),
+            TestAccount::new(spl_associated_token_account::id(), Pubkey::default(), vec![]),
+            TestAccount::new(metadata::id(), Pubkey::default(), vec![]),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+        ]
+    }
+
+    fn list_accounts(&self) -> Vec<TestAccount> {
+        let lister_token = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::wallet(self.lister, 10 * PRICE, true),
+            TestAccount::new(lister_token, spl_token::id(), token_account(self.mint, self.lister, 1)),
+            TestAccount::new(self.mint, spl_token::id(), nft_mint()),
+            TestAccount::new(self.state, self.program_id, vec![0; state::LISTESCROWSTATE]),
+            // Left to the metadata program, which creates it as the escrow state's associated token account
+            TestAccount::new(self.vault, system_program::id(), vec![]),
+            TestAccount::new(self.program_id, Pubkey::default(), vec![]),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            TestAccount::new(sysvar::rent::id(), sysvar::id(), bincode_rent()),
+            self.mint_state_account(),
+            self.platform_state(),
+            TestAccount::new(self.order_book_account().key, self.program_id, packed(OrderBookState {
+                is_initialized: true,
+                mint: self.mint,
+                ..OrderBookState::default()
+            })),
+        ];
+        accounts.extend(self.programmable_accounts(lister_token, self.vault, TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE));
+        accounts
+    }
+
+    fn accept_listing_accounts(&self, referrer: Option<Pubkey>) -> Vec<TestAccount> {
+        let buyer_token = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::wallet(self.buyer, 10 * PRICE, true),
+            TestAccount::new(buyer_token, spl_token::id(), token_account(self.mint, self.buyer, 0)),
+            TestAccount::new(self.mint, spl_token::id(), nft_mint()),
+            TestAccount::wallet(self.lister, 0, false),
+            self.platform_state(),
+            TestAccount::new(self.pda(&[b"Platform", b"Vault"]), self.program_id, vec![]),
+            self.state_account(None),
+            TestAccount::new(self.vault, spl_token::id(), token_account(self.mint, self.state, 1)),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            self.mint_state_account(),
+            TestAccount::new(self.pda(&[b"Platform", b"FeeSchedule"]), self.program_id, vec![]),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+            TestAccount::new(self.pda(&[self.lister.as_ref(), b"Trader", b"State"]), self.program_id, vec![]),
+            self.order_book_account(),
+        ];
+        accounts.extend(self.programmable_accounts(self.vault, buyer_token, TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE));
+        if let Some(referrer) = referrer {
+            accounts.push(TestAccount::wallet(referrer, 0, false));
+        }
+        accounts
+    }
+
+    /// One listing of `SweepListings`, the programmable NFT accounts after the listing's own
+    fn sweep_accounts(&self) -> Vec<TestAccount> {
+        let buyer_token = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::wallet(self.buyer, 10 * PRICE, true),
+            self.platform_state(),
+            TestAccount::new(self.pda(&[b"Platform", b"Vault"]), self.program_id, vec![]),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            TestAccount::new(self.pda(&[b"Platform", b"FeeSchedule"]), self.program_id, vec![]),
+            TestAccount::new(buyer_token, spl_token::id(), token_account(self.mint, self.buyer, 0)),
+            TestAccount::new(self.mint, spl_token::id(), nft_mint()),
+            TestAccount::wallet(self.lister, 0, false),
+            self.state_account(None),
+            TestAccount::new(self.vault, spl_token::id(), token_account(self.mint, self.state, 1)),
+            self.mint_state_account(),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+            TestAccount::new(self.pda(&[self.lister.as_ref(), b"Trader", b"State"]), self.program_id, vec![]),
+            self.order_book_account(),
+        ];
+        accounts.extend(self.programmable_accounts(self.vault, buyer_token, TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE));
+        accounts
+    }
+
+    fn delist_accounts(&self, token_standard: u8) -> Vec<TestAccount> {
+        let lister_token = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(self.lister),
+            TestAccount::new(lister_token, spl_token::id(), token_account(self.mint, self.lister, 0)),
+            TestAccount::new(self.mint, spl_token::id(), vec![]),
+            self.state_account(None),
+            TestAccount::new(self.vault, spl_token::id(), token_account(self.mint, self.state, 1)),
+            TestAccount::new(self.program_id, Pubkey::default(), vec![]),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            self.order_book_account(),
+        ];
+        accounts.extend(self.programmable_accounts(self.vault, lister_token, token_standard));
+        accounts
+    }
+
+    /// The listing's escrow as `BulkRefund` takes it, the programmable NFT accounts last
+    fn refund_escrow_accounts(&self) -> Vec<TestAccount> {
+        let lister_token = Pubkey::new_unique();
+        let mut accounts = vec![
+            self.state_account(None),
+            TestAccount::new(self.vault, spl_token::id(), token_account(self.mint, self.state, 1)),
+            TestAccount::wallet(self.lister, 0, false),
+            TestAccount::new(lister_token, spl_token::id(), token_account(self.mint, self.lister, 0)),
+            TestAccount::new(self.mint, spl_token::id(), vec![]),
+            self.order_book_account(),
+        ];
+        accounts.extend(self.programmable_accounts(self.vault, lister_token, TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE));
+        accounts
+    }
+
+    fn refund_listing_accounts(&self) -> Vec<TestAccount> {
+        let lister_token = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(self.authority),
+            TestAccount::new(lister_token, spl_token::id(), token_account(self.mint, self.lister, 0)),
+            TestAccount::new(self.mint, spl_token::id(), vec![]),
+            TestAccount::wallet(self.lister, 0, false),
+            self.platform_state(),
+            self.state_account(None),
+            TestAccount::new(self.vault, spl_token::id(), token_account(self.mint, self.state, 1)),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            self.order_book_account(),
+        ];
+        accounts.extend(self.programmable_accounts(self.vault, lister_token, TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE));
+        accounts
+    }
+
+    fn withdraw_accounts(&self) -> Vec<TestAccount> {
+        let buyer_token = Pubkey::new_unique();
+        let mut accounts = vec![
+            TestAccount::signer(self.buyer),
+            TestAccount::new(buyer_token, spl_token::id(), token_account(self.mint, self.buyer, 0)),
+            TestAccount::new(self.mint, spl_token::id(), vec![]),
+            TestAccount::new(self.lister, system_program::id(), vec![]),
+            self.state_account(Some(self.buyer)),
+            TestAccount::new(self.vault, spl_token::id(), token_account(self.mint, self.state, 1)),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+        ];
+        accounts.extend(self.programmable_accounts(self.vault, buyer_token, TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE));
+        accounts
+    }
+}
+
+fn instruction_data(tag: u8) -> Vec<u8> {
+    let mut data = vec![tag];
+    data.extend_from_slice(&LIST_ID.to_be_bytes());
+    data
+}
+
+// The programmable NFT accounts, which come last unless a referrer follows them
+fn programmable(accounts: &[TestAccount], referrer: bool) -> &[TestAccount] {
+    let end = accounts.len() - referrer as usize;
+    &accounts[end - metadata::PROGRAMMABLE_ACCOUNTS..end]
+}
+
+// Checks a recorded `Transfer` of one NFT from `source`, owned by and authorized by `owner`, to `destination`
+fn assert_transfer(
+    invocation: &Invocation,
+    programmable: &[TestAccount],
+    (source, owner): (Pubkey, Pubkey),
+    (destination, destination_owner): (Pubkey, Pubkey),
+    mint: Pubkey,
+    payer: Pubkey,
+) {
+    let instruction = &invocation.instruction;
+    assert_eq!(instruction.program_id, metadata::id());
+    assert_eq!(instruction.data, vec![49, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+    let expected = [
+        (source, true, false),
+        (owner, false, false),
+        (destination, true, false),
+        (destination_owner, false, false),
+        (mint, false, false),
+        (programmable[0].key, true, false),
+        (programmable[1].key, false, false),
+        (programmable[2].key, true, false),
+        (programmable[3].key, true, false),
+        (owner, false, true),
+        (payer, true, true),
+        (system_program::id(), false, false),
+        (sysvar::instructions::id(), false, false),
+        (spl_token::id(), false, false),
+        (spl_associated_token_account::id(), false, false),
+        (programmable[8].key, false, false),
+        (programmable[9].key, false, false),
+    ];
+    let metas: Vec<(Pubkey, bool, bool)> = instruction
+        .accounts
+        .iter()
+        .map(|account_meta| (account_meta.pubkey, account_meta.is_writable, account_meta.is_signer))
+        .collect();
+    assert_eq!(metas, expected.to_vec());
+}
+
+// Checks a recorded `Transfer` of one NFT out of the listing's vault, signed by the escrow state
+fn assert_release(invocation: &Invocation, listing: &Listing, accounts: &[TestAccount], destination_owner: Pubkey, payer: Pubkey, referrer: bool) {
+    let destination = accounts[1].key;
+    let programmable = programmable(accounts, referrer);
+    assert_transfer(invocation, programmable, (listing.vault, listing.state), (destination, destination_owner), listing.mint, payer);
+    assert_eq!(invocation.signers, vec![listing.state]);
+}
+
+fn delist_transfers_through_metadata_program(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let mut accounts = listing.delist_accounts(TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE);
+    process(&program_id, &mut accounts, &instruction_data(4)).unwrap();
+
+    let invocations = invocations.lock().unwrap();
+    assert_eq!(invocations.len(), 1);
+    assert_release(&invocations[0], &listing, &accounts, listing.lister, listing.lister, false);
+    assert_eq!(accounts[3].lamports, 0);
+}
+
+fn withdraw_transfers_through_metadata_program(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let mut accounts = listing.withdraw_accounts();
+    process(&program_id, &mut accounts, &instruction_data(8)).unwrap();
+
+    let invocations = invocations.lock().unwrap();
+    assert_eq!(invocations.len(), 1);
+    assert_release(&invocations[0], &listing, &accounts, listing.buyer, listing.buyer, false);
+}
+
+fn list_escrows_through_metadata_program(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let mut accounts = listing.list_accounts();
+    let mut data = vec![3];
+    data.extend_from_slice(&PRICE.to_be_bytes());
+    data.extend_from_slice(&LIST_ID.to_be_bytes());
+    data.extend_from_slice(&0u64.to_be_bytes());
+    process(&program_id, &mut accounts, &data).unwrap();
+
+    // Only the escrow state is created here, the vault is left to the transfer
+    let invocations = invocations.lock().unwrap();
+    assert_eq!(invocations.len(), 2);
+    assert_eq!(invocations[0].instruction.program_id, system_program::id());
+    assert_eq!(invocations[0].instruction.accounts[1].pubkey, listing.state);
+    assert_eq!(invocations[0].signers, vec![listing.state]);
+    let lister_token = accounts[1].key;
+    let programmable = programmable(&accounts, false);
+    assert_transfer(&invocations[1], programmable, (lister_token, listing.lister), (listing.vault, listing.state), listing.mint, listing.lister);
+    assert!(invocations[1].signers.is_empty());
+
+    let list_state = ListEscrowState::unpack(&accounts[3].data).unwrap();
+    assert_eq!((list_state.lister, list_state.mint, list_state.amount), (listing.lister, listing.mint, PRICE));
+    assert_eq!(accounts[3].lamports, Rent::default().minimum_balance(state::LISTESCROWSTATE));
+    assert!(accounts[4].data.is_empty());
+}
+
+fn accept_listing_transfers_through_metadata_program(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let mut accounts = listing.accept_listing_accounts(None);
+    process(&program_id, &mut accounts, &instruction_data(9)).unwrap();
+
+    let invocations = invocations.lock().unwrap();
+    assert_eq!(invocations.len(), 3);
+    assert_release(&invocations[0], &listing, &accounts, listing.buyer, listing.buyer, false);
+    let fee = state::calculate_fee(PRICE, PLATFORM_FEE);
+    assert_eq!(accounts[0].lamports, 10 * PRICE - PRICE);
+    assert_eq!(accounts[5].lamports, fee);
+    assert!(ListEscrowState::unpack(&accounts[6].data).unwrap().success);
+}
+
+fn accept_listing_pays_referrer_after_programmable_accounts(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let referrer = Pubkey::new_unique();
+    let mut accounts = listing.accept_listing_accounts(Some(referrer));
+    process(&program_id, &mut accounts, &instruction_data(9)).unwrap();
+
+    let invocations = invocations.lock().unwrap();
+    assert_eq!(invocations.len(), 4);
+    assert_release(&invocations[0], &listing, &accounts, listing.buyer, listing.buyer, true);
+    let fee = state::calculate_fee(PRICE, PLATFORM_FEE);
+    let referral_fee = state::calculate_fee(fee, REFERRAL_FEE);
+    assert_eq!(accounts[5].lamports, fee - referral_fee);
+    assert_eq!(accounts[accounts.len() - 1].lamports, referral_fee);
+}
+
+fn sweep_takes_programmable_accounts_after_listing(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let mut accounts = listing.sweep_accounts();
+    let mut data = vec![36];
+    data.extend_from_slice(&(2 * PRICE).to_be_bytes());
+    data.push(0);
+    process(&program_id, &mut accounts, &data).unwrap();
+
+    let invocations = invocations.lock().unwrap();
+    assert_eq!(invocations.len(), 3);
+    let buyer_token = accounts[6].key;
+    assert_transfer(&invocations[0], programmable(&accounts, false), (listing.vault, listing.state), (buyer_token, listing.buyer), listing.mint, listing.buyer);
+    assert_eq!(invocations[0].signers, vec![listing.state]);
+    assert_eq!(accounts[0].lamports, 10 * PRICE - PRICE);
+    assert!(ListEscrowState::unpack(&accounts[9].data).unwrap().success);
+}
+
+fn sweep_requires_programmable_accounts(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let mut accounts = listing.sweep_accounts();
+    accounts.pop();
+    let mut data = vec![36];
+    data.extend_from_slice(&(2 * PRICE).to_be_bytes());
+    // A missing account group is malformed input rather than an unavailable listing
+    data.push(1);
+    assert_eq!(process(&program_id, &mut accounts, &data), Err(ProgramError::NotEnoughAccountKeys));
+    assert!(invocations.lock().unwrap().is_empty());
+}
+
+fn refund_listing_transfers_through_metadata_program(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let mut accounts = listing.refund_listing_accounts();
+    process(&program_id, &mut accounts, &instruction_data(11)).unwrap();
+
+    // The authority pays for the lister's token record
+    let invocations = invocations.lock().unwrap();
+    assert_eq!(invocations.len(), 1);
+    assert_release(&invocations[0], &listing, &accounts, listing.lister, listing.authority, false);
+    assert_eq!((accounts[3].lamports, accounts[5].lamports), (1_000_000, 0));
+}
+
+fn bulk_refund_takes_programmable_accounts_after_listing(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let mut accounts = vec![
+        TestAccount::signer(listing.authority),
+        listing.platform_state(),
+        TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+    ];
+    accounts.extend(listing.refund_escrow_accounts());
+    let mut data = vec![12];
+    data.extend_from_slice(&0u64.to_be_bytes());
+    process(&program_id, &mut accounts, &data).unwrap();
+
+    let invocations = invocations.lock().unwrap();
+    assert_eq!(invocations.len(), 1);
+    let lister_token = accounts[6].key;
+    let programmable = programmable(&accounts, false);
+    assert_transfer(&invocations[0], programmable, (listing.vault, listing.state), (lister_token, listing.lister), listing.mint, listing.authority);
+    assert_eq!(invocations[0].signers, vec![listing.state]);
+    assert_eq!((accounts[5].lamports, accounts[3].lamports), (1_000_000, 0));
+}
+
+fn bulk_refund_requires_programmable_accounts(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let mut accounts = vec![
+        TestAccount::signer(listing.authority),
+        listing.platform_state(),
+        TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+    ];
+    accounts.extend(listing.refund_escrow_accounts());
+    accounts.pop();
+    let mut data = vec![12];
+    data.extend_from_slice(&0u64.to_be_bytes());
+    assert_eq!(process(&program_id, &mut accounts, &data), Err(ProgramError::NotEnoughAccountKeys));
+    assert!(invocations.lock().unwrap().is_empty());
+}
+
+fn rejects_mint_that_is_not_programmable(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let mut accounts = listing.delist_accounts(TOKEN_STANDARD_NON_FUNGIBLE);
+    assert_eq!(process(&program_id, &mut accounts, &instruction_data(4)), Err(ProgramError::InvalidAccountData));
+    assert!(invocations.lock().unwrap().is_empty());
+}
+
+fn rejects_wrong_token_record(invocations: &Mutex<Vec<Invocation>>, program_id: Pubkey) {
+    let listing = Listing::new(program_id);
+    let mut accounts = listing.delist_accounts(TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE);
+    let source_token_record = accounts.len() - metadata::PROGRAMMABLE_ACCOUNTS + 2;
+    accounts[source_token_record].key = Pubkey::new_unique();
+    assert_eq!(process(&program_id, &mut accounts, &instruction_data(4)), Err(ProgramError::InvalidAccountData));
+    assert!(invocations.lock().unwrap().is_empty());
+}
+
+fn main() {
+    let program_id = Pubkey::new_unique();
+    let invocations = Arc::new(Mutex::new(Vec::new()));
+    program_stubs::set_syscall_stubs(Box::new(MetadataProgramStub {
+        program_id,
+        invocations: invocations.clone(),
+    }));
+
+    run_tests!("programmable_nft", [
+        delist_transfers_through_metadata_program(&invocations, program_id),
+        withdraw_transfers_through_metadata_program(&invocations, program_id),
+        list_escrows_through_metadata_program(&invocations, program_id),
+        accept_listing_transfers_through_metadata_program(&invocations, program_id),
+        accept_listing_pays_referrer_after_programmable_accounts(&invocations, program_id),
+        sweep_takes_programmable_accounts_after_listing(&invocations, program_id),
+        sweep_requires_programmable_accounts(&invocations, program_id),
+        refund_listing_transfers_through_metadata_program(&invocations, program_id),
+        bulk_refund_takes_programmable_accounts_after_listing(&invocations, program_id),
+        bulk_refund_requires_programmable_accounts(&invocations, program_id),
+        rejects_mint_that_is_not_programmable(&invocations, program_id),
+        rejects_wrong_token_record(&invocations, program_id),
+    ], {
+        invocations.lock().unwrap().clear();
+    });
+}
@@ -0,0 +1,371 @@
+#[macro_use]
+mod common;
+
+use std::convert::TryInto;
+use std::sync::Mutex;
+
+use common::{nft_mint, packed, process, token_account, TestAccount};
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    ed25519_program,
+    entrypoint::{ProgramResult, SUCCESS},
+    instruction::Instruction,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+    sysvar,
+};
+use spl_token::state::Account;
+use test::{
+    error::NFTError,
+    order::{self, SignedOrder},
+    state::{self, MintState, OrderNonceState, PlatformState},
+};
+
+const NOW: i64 = 1_700_000_000;
+const SOL: u64 = 1_000_000_000;
+const PRICE: u64 = 2 * SOL;
+// 2% platform fee, all of it paid by the seller
+const PLATFORM_FEE: u64 = 20_000_000;
+
+// Owners the system program assigned during the running instruction
+static ASSIGNED: Mutex<Vec<(Pubkey, Pubkey)>> = Mutex::new(Vec::new());
+
+/// Moves the NFT on the token account data, only letting the order delegate sign for it. System program calls only
+/// move lamports. The clock always reads `NOW` and rent is the default schedule
+struct TokenMock {
+    program_id: Pubkey,
+}
+
+impl TokenMock {
+    fn find<'a, 'b>(account_infos: &'a [AccountInfo<'b>], key: &Pubkey) -> &'a AccountInfo<'b> {
+        account_infos.iter().find(|account_info| account_info.key.eq(key)).unwrap()
+    }
+
+    fn update(account_info: &AccountInfo, update: impl FnOnce(&mut Account)) -> ProgramResult {
+        let mut account = Account::unpack(&account_info.data.borrow())?;
+        update(&mut account);
+        Account::pack(account, &mut account_info.data.borrow_mut())
+    }
+}
+
+impl SyscallStubs for TokenMock {
+    fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|account_meta| account_meta.pubkey).collect();
+        if instruction.program_id.eq(&system_program::id()) {
+            let account_info = Self::find(account_infos, &keys[0]);
+            match u32::from_le_bytes(instruction.data[..4].try_into().unwrap()) {
+                // Assign, applied to the test accounts once the instruction returns
+                1 => ASSIGNED.lock().unwrap().push((keys[0], instruction.data[4..36].try_into().unwrap())),
+                // Transfer
+                2 => {
+                    let lamports = u64::from_le_bytes(instruction.data[4..12].try_into().unwrap());
+                    **account_info.lamports.borrow_mut() -= lamports;
+                    **Self::find(account_infos, &keys[1]).lamports.borrow_mut() += lamports;
+                }
+                // Allocate, the nonce only ever takes no space
+                8 => assert_eq!(instruction.data[4..12], 0u64.to_le_bytes()),
+                tag => panic!("unexpected system instruction {}", tag),
+            }
+            return Ok(());
+        }
+
+        assert_eq!(instruction.program_id, spl_token::id());
+        // TransferChecked
+        assert_eq!(instruction.data[0], 12);
+        let delegate = Pubkey::create_program_address(signers_seeds[0], &self.program_id).unwrap();
+        assert_eq!(keys[3], delegate);
+        Self::update(Self::find(account_infos, &keys[0]), |account| {
+            assert_eq!(account.delegate, COption::Some(delegate));
+            account.amount -= 1;
+            account.delegated_amount -= 1;
+        })?;
+        Self::update(Self::find(account_infos, &keys[2]), |account| account.amount += 1)
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Clock) = Clock { unix_timestamp: NOW, ..Clock::default() };
+        }
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Rent) = Rent::default();
+        }
+        SUCCESS
+    }
+}
+
+// A token account holding `amount` of `mint`, all of it delegated to `delegate`
+fn delegated_token_account(mint: Pubkey, owner: Pubkey, amount: u64, delegate: COption<Pubkey>) -> Vec<u8> {
+    let account = Account::unpack(&token_account(mint, owner, amount)).unwrap();
+    packed(Account { delegate, delegated_amount: amount, ..account })
+}
+
+// An ed25519 program instruction over `message` the way clients build it: the offsets, then the public key,
+// signature and message. The runtime checks the signature itself, so any 64 bytes stand in for it here
+fn ed25519_instruction(signer: &Pubkey, message: &[u8]) -> Instruction {
+    let (public_key_offset, signature_offset, message_offset) = (16u16, 48u16, 112u16);
+    let mut data = vec![1, 0];
+    for offset in [signature_offset, u16::MAX, public_key_offset, u16::MAX, message_offset, message.len() as u16, u16::MAX] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(&[7; 64]);
+    data.extend_from_slice(message);
+    Instruction { program_id: ed25519_program::id(), accounts: vec![], data }
+}
+
+const BUYER: usize = 0;
+const BUYER_TOKEN: usize = 1;
+const SELLER: usize = 3;
+const SELLER_TOKEN: usize = 4;
+const NONCE: usize = 6;
+const VAULT: usize = 8;
+const INSTRUCTIONS: usize = 11;
+const ORDER_NONCE: usize = 16;
+
+struct Fill {
+    program_id: Pubkey,
+    order: SignedOrder,
+    accounts: Vec<TestAccount>,
+}
+
+impl Fill {
+    fn new(program_id: Pubkey, expiry: i64) -> Self {
+        let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &program_id).0;
+        let mint = Pubkey::new_unique();
+        let seller = Pubkey::new_unique();
+        let buyer = Pubkey::new_unique();
+        let order = SignedOrder { seller, mint, price: PRICE, expiry, nonce: 7 };
+        let delegate = order::find_delegate_address(&program_id).0;
+
+        let accounts = vec![
+            TestAccount::wallet(buyer, 10 * SOL, true),
+            TestAccount::new(Pubkey::new_unique(), spl_token::id(), token_account(mint, buyer, 0)),
+            TestAccount::new(mint, spl_token::id(), nft_mint()),
+            TestAccount::new(seller, system_program::id(), vec![]),
+            TestAccount::new(Pubkey::new_unique(), spl_token::id(), delegated_token_account(mint, seller, 1, COption::Some(delegate))),
+            TestAccount::new(delegate, system_program::id(), vec![]),
+            TestAccount::new(pda(&[seller.as_ref(), &order.nonce.to_be_bytes(), b"Order", b"Nonce"]), system_program::id(), vec![]),
+            TestAccount::new(pda(&[b"Platform", b"State"]), program_id, packed(PlatformState {
+                is_initialized: true,
+                platform_fee: PLATFORM_FEE,
+                ..PlatformState::default()
+            })),
+            TestAccount::new(pda(&[b"Platform", b"Vault"]), program_id, vec![]),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            TestAccount::new(sysvar::instructions::id(), sysvar::id(), vec![]),
+            TestAccount::new(pda(&[mint.as_ref(), b"Mint", b"State"]), program_id, packed(MintState {
+                is_initialized: true,
+                mint,
+                sale_count: 0,
+            })),
+            TestAccount::new(pda(&[b"Platform", b"FeeSchedule"]), program_id, vec![]),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+            TestAccount::new(pda(&[seller.as_ref(), b"Trader", b"State"]), program_id, vec![]),
+            TestAccount::new(pda(&[seller.as_ref(), b"OrderNonce", b"State"]), program_id, packed(OrderNonceState {
+                is_initialized: true,
+                owner: seller,
+                ..OrderNonceState::default()
+            })),
+        ];
+        let mut fill = Fill { program_id, order, accounts };
+        fill.sign(&seller, &order.message(&program_id));
+        fill
+    }
+
+    // Lays out the transaction's instructions the way the runtime does: the seller's ed25519 instruction, then the
+    // fill, with the index of the running instruction last
+    fn sign(&mut self, signer: &Pubkey, message: &[u8]) {
+        self.set_instructions(vec![ed25519_instruction(signer, message), Instruction {
+            program_id: self.program_id,
+            accounts: vec![],
+            data: vec![],
+        }]);
+    }
+
+    // The instructions sysvar layout: the instruction count and each instruction's offset, then every instruction
+    // as its accounts, program id and data, and last the index of the running instruction
+    fn set_instructions(&mut self, instructions: Vec<Instruction>) {
+        let mut data = (instructions.len() as u16).to_le_bytes().to_vec();
+        let mut serialized = vec![];
+        for instruction in instructions.iter() {
+            let offset = 2 + 2 * instructions.len() + serialized.len();
+            data.extend_from_slice(&(offset as u16).to_le_bytes());
+            serialized.extend_from_slice(&(instruction.accounts.len() as u16).to_le_bytes());
+            for account_meta in instruction.accounts.iter() {
+                serialized.push(account_meta.is_signer as u8 | (account_meta.is_writable as u8) << 1);
+                serialized.extend_from_slice(account_meta.pubkey.as_ref());
+            }
+            serialized.extend_from_slice(instruction.program_id.as_ref());
+            serialized.extend_from_slice(&(instruction.data.len() as u16).to_le_bytes());
+            serialized.extend_from_slice(&instruction.data);
+        }
+        data.extend_from_slice(&serialized);
+        data.extend_from_slice(&(instructions.len() as u16 - 1).to_le_bytes());
+        self.accounts[INSTRUCTIONS].data = data;
+    }
+
+    fn process(&mut self, price: u64) -> ProgramResult {
+        let mut data = vec![37];
+        data.extend_from_slice(&price.to_be_bytes());
+        data.extend_from_slice(&self.order.expiry.to_be_bytes());
+        data.extend_from_slice(&self.order.nonce.to_be_bytes());
+        let result = process(&self.program_id, &mut self.accounts, &data);
+        for (key, owner) in ASSIGNED.lock().unwrap().drain(..) {
+            if result.is_ok() {
+                self.accounts.iter_mut().find(|account| account.key.eq(&key)).unwrap().owner = owner;
+            }
+        }
+        result
+    }
+
+    // Sends IncrementNonce or CancelOrder as the seller
+    fn cancel(&mut self, data: &[u8]) -> ProgramResult {
+        let order_nonce = std::mem::replace(&mut self.accounts[ORDER_NONCE], TestAccount::new(Pubkey::default(), Pubkey::default(), vec![]));
+        let mut accounts = vec![
+            TestAccount::signer(self.order.seller),
+            order_nonce,
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+        ];
+        let result = process(&self.program_id, &mut accounts, data);
+        self.accounts[ORDER_NONCE] = accounts.swap_remove(1);
+        result
+    }
+
+    fn nft(&self, index: usize) -> u64 {
+        Account::unpack(&self.accounts[index].data).unwrap().amount
+    }
+}
+
+fn fills_signed_order(program_id: Pubkey) {
+    let mut fill = Fill::new(program_id, NOW + 60);
+    fill.process(PRICE).unwrap();
+
+    let fee = state::calculate_fee(PRICE, PLATFORM_FEE);
+    let nonce_rent = Rent::default().minimum_balance(0);
+    assert_eq!(fill.nft(SELLER_TOKEN), 0);
+    assert_eq!(fill.nft(BUYER_TOKEN), 1);
+    assert_eq!(fill.accounts[SELLER].lamports, PRICE - fee);
+    assert_eq!(fill.accounts[VAULT].lamports, fee);
+    assert_eq!(fill.accounts[NONCE].lamports, nonce_rent);
+    assert_eq!(fill.accounts[BUYER].lamports, 10 * SOL - PRICE - nonce_rent);
+}
+
+fn rejects_reused_nonce(program_id: Pubkey) {
+    let mut fill = Fill::new(program_id, 0);
+    fill.process(PRICE).unwrap();
+    assert_eq!(fill.process(PRICE), Err(NFTError::NonceAlreadyUsed.into()));
+}
+
+fn fills_despite_prefunded_nonce(program_id: Pubkey) {
+    // Anyone can send lamports to the nonce's PDA, which must not block the order
+    let mut fill = Fill::new(program_id, 0);
+    fill.accounts[NONCE].lamports = 1;
+    fill.process(PRICE).unwrap();
+
+    let nonce_rent = Rent::default().minimum_balance(0);
+    assert_eq!(fill.nft(BUYER_TOKEN), 1);
+    assert_eq!(fill.accounts[NONCE].lamports, nonce_rent);
+    assert_eq!(fill.accounts[NONCE].owner, program_id);
+    assert_eq!(fill.accounts[BUYER].lamports, 10 * SOL - PRICE - (nonce_rent - 1));
+    assert_eq!(fill.process(PRICE), Err(NFTError::NonceAlreadyUsed.into()));
+}
+
+fn rejects_terms_the_seller_did_not_sign(program_id: Pubkey) {
+    let mut fill = Fill::new(program_id, 0);
+    assert_eq!(fill.process(PRICE - 1), Err(NFTError::InvalidOrderSignature.into()));
+
+    let message = fill.order.message(&program_id);
+    fill.sign(&Pubkey::new_unique(), &message);
+    assert_eq!(fill.process(PRICE), Err(NFTError::InvalidOrderSignature.into()));
+
+    // Without an ed25519 instruction in front there is no signature at all
+    fill.set_instructions(vec![Instruction { program_id, accounts: vec![], data: vec![] }]);
+    assert_eq!(fill.process(PRICE), Err(NFTError::InvalidOrderSignature.into()));
+    assert_eq!(fill.nft(SELLER_TOKEN), 1);
+}
+
+fn rejects_expired_order(program_id: Pubkey) {
+    let mut fill = Fill::new(program_id, NOW);
+    assert_eq!(fill.process(PRICE), Err(NFTError::OrderExpired.into()));
+}
+
+fn rejects_revoked_delegation(program_id: Pubkey) {
+    let mut fill = Fill::new(program_id, 0);
+    let mint = fill.order.mint;
+    let seller = fill.order.seller;
+    fill.accounts[SELLER_TOKEN].data = delegated_token_account(mint, seller, 1, COption::None);
+    assert_eq!(fill.process(PRICE), Err(NFTError::StaleDelegation.into()));
+}
+
+fn cancelled_order_cannot_fill(program_id: Pubkey) {
+    let mut fill = Fill::new(program_id, 0);
+    let mut data = vec![39];
+    data.extend_from_slice(fill.order.hash(&program_id).as_ref());
+    fill.cancel(&data).unwrap();
+    assert_eq!(fill.process(PRICE), Err(NFTError::OrderCancelled.into()));
+    assert_eq!(fill.nft(SELLER_TOKEN), 1);
+
+    // Another order of the same seller is still live
+    let other = SignedOrder { nonce: fill.order.nonce + 1, ..fill.order };
+    let order_nonce_state = OrderNonceState::unpack(&fill.accounts[ORDER_NONCE].data).unwrap();
+    assert!(!order_nonce_state.is_cancelled(other.nonce, &other.hash(&program_id)));
+}
+
+fn increment_nonce_cancels_lower_nonces(program_id: Pubkey) {
+    let mut fill = Fill::new(program_id, 0);
+    let increment = |nonce: u64| {
+        let mut data = vec![38];
+        data.extend_from_slice(&nonce.to_be_bytes());
+        data
+    };
+    fill.cancel(&increment(fill.order.nonce)).unwrap();
+    fill.process(PRICE).unwrap();
+
+    let mut fill = Fill::new(program_id, 0);
+    fill.cancel(&increment(fill.order.nonce + 1)).unwrap();
+    assert_eq!(fill.process(PRICE), Err(NFTError::OrderCancelled.into()));
+    assert_eq!(fill.cancel(&increment(fill.order.nonce + 1)), Err(NFTError::NonceNotIncreased.into()));
+    assert_eq!(OrderNonceState::unpack(&fill.accounts[ORDER_NONCE].data).unwrap().min_nonce, fill.order.nonce + 1);
+}
+
+fn rejects_party_as_referrer(program_id: Pubkey) {
+    for party in [BUYER, SELLER] {
+        let mut fill = Fill::new(program_id, 0);
+        let referrer = fill.accounts[party].key;
+        fill.accounts.push(TestAccount::new(referrer, system_program::id(), vec![]));
+        assert_eq!(fill.process(PRICE), Err(NFTError::InvalidReferrer.into()));
+        assert_eq!(fill.nft(SELLER_TOKEN), 1);
+    }
+
+    let mut fill = Fill::new(program_id, 0);
+    fill.accounts.push(TestAccount::new(Pubkey::new_unique(), system_program::id(), vec![]));
+    fill.process(PRICE).unwrap();
+    assert_eq!(fill.nft(BUYER_TOKEN), 1);
+}
+
+fn main() {
+    let program_id = Pubkey::new_unique();
+    program_stubs::set_syscall_stubs(Box::new(TokenMock { program_id }));
+
+    run_tests!("signed_order", [
+        fills_signed_order(program_id),
+        rejects_reused_nonce(program_id),
+        fills_despite_prefunded_nonce(program_id),
+        rejects_terms_the_seller_did_not_sign(program_id),
+        rejects_expired_order(program_id),
+        rejects_revoked_delegation(program_id),
+        cancelled_order_cannot_fill(program_id),
+        increment_nonce_cancels_lower_nonces(program_id),
+        rejects_party_as_referrer(program_id),
+    ]);
+}
@@ -0,0 +1,245 @@
+#[macro_use]
+mod common;
+
+use common::{packed, process, token_account, TestAccount};
+use solana_program::{
+    clock::Clock,
+    entrypoint::{ProgramResult, SUCCESS},
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+use test::{
+    error::NFTError,
+    instruction::{AcceptBid, AcceptListing, NFTInstruction},
+    metadata,
+    state::{self, BidEscrowState, FeeBearer, ListEscrowState, MintState, PlatformState},
+};
+
+const PRICE: u64 = 1_000_000_000;
+// 2% platform fee
+const PLATFORM_FEE: u64 = 20_000_000;
+
+/// The clock reads zero and rent is the default schedule. Every trade here is rejected or settles without a CPI
+struct SysvarMock;
+
+impl SyscallStubs for SysvarMock {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Clock) = Clock::default();
+        }
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Rent) = Rent::default();
+        }
+        SUCCESS
+    }
+}
+
+fn with_slippage(mut data: Vec<u8>, slippage: Option<(u64, u64)>) -> Vec<u8> {
+    if let Some((expected_price, max_fee)) = slippage {
+        data.extend_from_slice(&expected_price.to_be_bytes());
+        data.extend_from_slice(&max_fee.to_be_bytes());
+    }
+    data
+}
+
+struct Trade {
+    program_id: Pubkey,
+    mint: Pubkey,
+    lister: Pubkey,
+    bidder: Pubkey,
+    reserved_buyer: Pubkey,
+    programmable: bool,
+}
+
+impl Trade {
+    fn new(program_id: Pubkey) -> Self {
+        Trade {
+            program_id,
+            mint: Pubkey::new_unique(),
+            lister: Pubkey::new_unique(),
+            bidder: Pubkey::new_unique(),
+            reserved_buyer: Pubkey::default(),
+            programmable: false,
+        }
+    }
+
+    fn pda(&self, seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &self.program_id).0
+    }
+
+    fn list_pda(&self, kind: &[u8]) -> Pubkey {
+        self.pda(&[self.mint.as_ref(), self.lister.as_ref(), &0u64.to_be_bytes(), b"List", kind])
+    }
+
+    /// A programmable NFT is escrowed in the listing state's associated token account
+    fn list_vault(&self) -> Pubkey {
+        match self.programmable {
+            true => metadata::find_programmable_vault_address(&self.list_pda(b"State"), &self.mint),
+            false => self.list_pda(b"Vault"),
+        }
+    }
+
+    fn bid_pda(&self, kind: &[u8]) -> Pubkey {
+        self.pda(&[self.mint.as_ref(), self.bidder.as_ref(), &0u64.to_be_bytes(), b"Bid", kind])
+    }
+
+    fn platform_state(&self, fee_bearer: FeeBearer) -> TestAccount {
+        TestAccount::new(self.pda(&[b"Platform", b"State"]), self.program_id, packed(PlatformState {
+            is_initialized: true,
+            platform_fee: PLATFORM_FEE,
+            fee_bearer,
+            ..PlatformState::default()
+        }))
+    }
+
+    fn list_state(&self) -> TestAccount {
+        TestAccount::new(self.list_pda(b"State"), self.program_id, packed(ListEscrowState {
+            is_initialized: true,
+            lister: self.lister,
+            mint: self.mint,
+            amount: PRICE,
+            reserved_buyer: self.reserved_buyer,
+            ..ListEscrowState::default()
+        }))
+    }
+
+    fn shared_accounts(&self) -> Vec<TestAccount> {
+        vec![
+            TestAccount::new(self.pda(&[self.mint.as_ref(), b"Mint", b"State"]), self.program_id, packed(MintState {
+                is_initialized: true,
+                mint: self.mint,
+                sale_count: 0,
+            })),
+            TestAccount::new(self.pda(&[b"Platform", b"FeeSchedule"]), self.program_id, vec![]),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+            TestAccount::new(self.pda(&[self.lister.as_ref(), b"Trader", b"State"]), self.program_id, vec![]),
+            TestAccount::new(self.pda(&[self.mint.as_ref(), b"OrderBook", b"State"]), self.program_id, vec![]),
+        ]
+    }
+
+    fn accept_listing(&self, fee_bearer: FeeBearer, slippage: Option<(u64, u64)>) -> ProgramResult {
+        let mut accounts = vec![
+            TestAccount::signer(self.bidder),
+            TestAccount::new(Pubkey::new_unique(), spl_token::id(), token_account(self.mint, self.bidder, 0)),
+            TestAccount::new(self.mint, spl_token::id(), vec![]),
+            TestAccount::new(self.lister, system_program::id(), vec![]),
+            self.platform_state(fee_bearer),
+            TestAccount::new(self.pda(&[b"Platform", b"Vault"]), self.program_id, vec![]),
+            self.list_state(),
+            TestAccount::new(self.list_vault(), spl_token::id(), vec![]),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+        ];
+        accounts.extend(self.shared_accounts());
+        let mut data = vec![9];
+        data.extend_from_slice(&0u64.to_be_bytes());
+        process(&self.program_id, &mut accounts, &with_slippage(data, slippage))
+    }
+
+    fn accept_bid(&self, fee_bearer: FeeBearer, slippage: Option<(u64, u64)>) -> ProgramResult {
+        let mut accounts = vec![
+            TestAccount::signer(self.lister),
+            TestAccount::new(self.mint, spl_token::id(), vec![]),
+            TestAccount::new(self.bidder, system_program::id(), vec![]),
+            self.platform_state(fee_bearer),
+            TestAccount::new(self.pda(&[b"Platform", b"Vault"]), self.program_id, vec![]),
+            TestAccount::new(self.bid_pda(b"State"), self.program_id, packed(BidEscrowState {
+                is_initialized: true,
+                bidder: self.bidder,
+                mint: self.mint,
+                amount: PRICE,
+                ..BidEscrowState::default()
+            })),
+            TestAccount::new(self.bid_pda(b"Vault"), self.program_id, vec![]).with_lamports(Rent::default().minimum_balance(0) + PRICE),
+            self.list_state(),
+            TestAccount::new(self.list_vault(), spl_token::id(), vec![]),
+        ];
+        accounts.extend(self.shared_accounts());
+        let mut data = vec![7];
+        data.extend_from_slice(&0u64.to_be_bytes());
+        data.extend_from_slice(&0u64.to_be_bytes());
+        process(&self.program_id, &mut accounts, &with_slippage(data, slippage))
+    }
+}
+
+fn unpacks_optional_slippage() {
+    let data = with_slippage(vec![9, 0, 0, 0, 0, 0, 0, 0, 1], None);
+    assert_eq!(NFTInstruction::unpack(&data), Ok(NFTInstruction::AcceptListing(AcceptListing {
+        list_id: 1,
+        slippage: None,
+    })));
+    let data = with_slippage(vec![7, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2], Some((PRICE, PLATFORM_FEE)));
+    assert_eq!(NFTInstruction::unpack(&data), Ok(NFTInstruction::AcceptBid(AcceptBid {
+        bid_id: 1,
+        list_id: 2,
+        slippage: Some((PRICE, PLATFORM_FEE)),
+    })));
+    let mut data = with_slippage(vec![9, 0, 0, 0, 0, 0, 0, 0, 1], Some((PRICE, PLATFORM_FEE)));
+    data.pop();
+    assert_eq!(NFTInstruction::unpack(&data), Err(NFTError::InvalidInstructionData.into()));
+}
+
+fn accept_listing_rejects_moved_terms(program_id: Pubkey) {
+    let trade = Trade::new(program_id);
+    let fee = state::calculate_fee(PRICE, PLATFORM_FEE);
+    assert_eq!(trade.accept_listing(FeeBearer::Buyer, Some((PRICE - 1, fee))), Err(NFTError::PriceMismatch.into()));
+    assert_eq!(trade.accept_listing(FeeBearer::Buyer, Some((PRICE, fee - 1))), Err(NFTError::FeeAboveMaximum.into()));
+    // The buyer agreed to pay nothing on top of the price, and the fee moved onto them
+    assert_eq!(trade.accept_listing(FeeBearer::Split, Some((PRICE, 0))), Err(NFTError::FeeAboveMaximum.into()));
+}
+
+fn accept_bid_rejects_moved_terms(program_id: Pubkey) {
+    let trade = Trade::new(program_id);
+    let fee = state::calculate_fee(PRICE, PLATFORM_FEE);
+    assert_eq!(trade.accept_bid(FeeBearer::Seller, Some((PRICE + 1, fee))), Err(NFTError::PriceMismatch.into()));
+    assert_eq!(trade.accept_bid(FeeBearer::Seller, Some((PRICE, fee - 1))), Err(NFTError::FeeAboveMaximum.into()));
+    assert_eq!(trade.accept_bid(FeeBearer::Split, Some((PRICE, fee / 2 - 1))), Err(NFTError::FeeAboveMaximum.into()));
+}
+
+fn accept_bid_settles_agreed_terms(program_id: Pubkey) {
+    let fee = state::calculate_fee(PRICE, PLATFORM_FEE);
+    assert_eq!(Trade::new(program_id).accept_bid(FeeBearer::Seller, Some((PRICE, fee))), Ok(()));
+    // The seller's share of a fee the buyer bears is nothing
+    assert_eq!(Trade::new(program_id).accept_bid(FeeBearer::Buyer, Some((PRICE, 0))), Ok(()));
+    assert_eq!(Trade::new(program_id).accept_bid(FeeBearer::Seller, None), Ok(()));
+}
+
+fn reserved_listing_rejects_other_buyers(program_id: Pubkey) {
+    let mut trade = Trade::new(program_id);
+    trade.reserved_buyer = Pubkey::new_unique();
+    assert_eq!(trade.accept_listing(FeeBearer::Seller, None), Err(NFTError::ListingReserved.into()));
+    assert_eq!(trade.accept_bid(FeeBearer::Seller, None), Err(NFTError::ListingReserved.into()));
+}
+
+fn reserved_listing_accepts_reserved_buyer_bid(program_id: Pubkey) {
+    let mut trade = Trade::new(program_id);
+    trade.reserved_buyer = trade.bidder;
+    assert_eq!(trade.accept_bid(FeeBearer::Seller, None), Ok(()));
+}
+
+fn accept_bid_takes_programmable_list_vault(program_id: Pubkey) {
+    let mut trade = Trade::new(program_id);
+    trade.programmable = true;
+    assert_eq!(trade.accept_bid(FeeBearer::Seller, None), Ok(()));
+}
+
+fn main() {
+    let program_id = Pubkey::new_unique();
+    program_stubs::set_syscall_stubs(Box::new(SysvarMock));
+
+    run_tests!("slippage", [
+        unpacks_optional_slippage(),
+        accept_listing_rejects_moved_terms(program_id),
+        accept_bid_rejects_moved_terms(program_id),
+        accept_bid_settles_agreed_terms(program_id),
+        reserved_listing_rejects_other_buyers(program_id),
+        reserved_listing_accepts_reserved_buyer_bid(program_id),
+        accept_bid_takes_programmable_list_vault(program_id),
+    ]);
+}
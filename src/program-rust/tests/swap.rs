@@ -0,0 +1,250 @@
+#[macro_use]
+mod common;
+
+use common::{bincode_rent, nft_mint, process, token_account, TestAccount, TokenMock};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::Pack,
+    program_stubs,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+    sysvar,
+};
+use spl_token::state::Account;
+use test::state::{self, SwapEscrowState};
+
+const SWAP_ID: u64 = 0;
+const SOL: u64 = 1_000_000_000;
+const OFFERED_LAMPORTS: u64 = SOL;
+const REQUESTED_LAMPORTS: u64 = SOL / 2;
+
+const OFFERER: usize = 0;
+const ESCROW_STATE: usize = 1;
+// Accounts of `CreateSwap` before the offered NFTs
+const CREATE_ACCOUNTS: usize = 6;
+// Accounts of `AcceptSwap` before the offered NFTs
+const TAKER: usize = 0;
+const ACCEPT_OFFERER: usize = 1;
+const SWAP_STATE: usize = 2;
+const ACCEPT_ACCOUNTS: usize = 5;
+
+struct Swap {
+    program_id: Pubkey,
+    offerer: Pubkey,
+    taker: Pubkey,
+    offered: Vec<Pubkey>,
+    requested: Vec<Pubkey>,
+    state: Pubkey,
+}
+
+impl Swap {
+    fn new(program_id: Pubkey, offered: usize, requested: usize) -> Self {
+        let offerer = Pubkey::new_unique();
+        let (state, _) = Pubkey::find_program_address(&[offerer.as_ref(), &SWAP_ID.to_be_bytes(), b"Swap", b"State"], &program_id);
+        Swap {
+            program_id,
+            offerer,
+            taker: Pubkey::new_unique(),
+            offered: (0..offered).map(|_| Pubkey::new_unique()).collect(),
+            requested: (0..requested).map(|_| Pubkey::new_unique()).collect(),
+            state,
+        }
+    }
+
+    fn vault(&self, mint: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[self.state.as_ref(), mint.as_ref(), b"Swap", b"Vault"], &self.program_id).0
+    }
+
+    fn create_accounts(&self) -> Vec<TestAccount> {
+        let mut accounts = vec![
+            TestAccount::wallet(self.offerer, 10 * SOL, true),
+            TestAccount::new(self.state, self.program_id, vec![0; state::SWAPESCROWSTATE]),
+            TestAccount::new(self.program_id, Pubkey::default(), vec![]),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            TestAccount::new(sysvar::rent::id(), sysvar::id(), bincode_rent()),
+        ];
+        for mint in self.offered.iter() {
+            accounts.extend([
+                TestAccount::new(Pubkey::new_unique(), spl_token::id(), token_account(*mint, self.offerer, 1)),
+                TestAccount::new(*mint, spl_token::id(), nft_mint()),
+                TestAccount::new(self.vault(mint), spl_token::id(), vec![0; Account::LEN]),
+            ]);
+        }
+        accounts
+    }
+
+    fn create_data(&self) -> Vec<u8> {
+        let mut data = vec![13];
+        data.extend_from_slice(&SWAP_ID.to_be_bytes());
+        data.extend_from_slice(&OFFERED_LAMPORTS.to_be_bytes());
+        data.extend_from_slice(&REQUESTED_LAMPORTS.to_be_bytes());
+        data.push(self.requested.len() as u8);
+        for mint in self.requested.iter() {
+            data.extend_from_slice(mint.as_ref());
+        }
+        data
+    }
+
+    /// The accounts of a swap `CreateSwap` went through
+    fn created(&self) -> Vec<TestAccount> {
+        let mut accounts = self.create_accounts();
+        process(&self.program_id, &mut accounts, &self.create_data()).unwrap();
+        accounts
+    }
+
+    fn escrow_state(&self, created: &[TestAccount]) -> TestAccount {
+        TestAccount::new(self.state, self.program_id, created[ESCROW_STATE].data.clone()).with_lamports(created[ESCROW_STATE].lamports)
+    }
+
+    /// Each offered NFT's vault, mint and a token account of `owner` to release it to, in the order they were offered
+    fn release_accounts(&self, created: &[TestAccount], owner: Pubkey) -> Vec<TestAccount> {
+        let mut accounts = vec![];
+        for (index, mint) in self.offered.iter().enumerate() {
+            let vault = &created[CREATE_ACCOUNTS + 3 * index + 2];
+            accounts.extend([
+                TestAccount::new(vault.key, spl_token::id(), vault.data.clone()).with_lamports(vault.lamports),
+                TestAccount::new(*mint, spl_token::id(), nft_mint()),
+                TestAccount::new(Pubkey::new_unique(), spl_token::id(), token_account(*mint, owner, 0)),
+            ]);
+        }
+        accounts
+    }
+
+    fn accept_accounts(&self, created: &[TestAccount]) -> Vec<TestAccount> {
+        let mut accounts = vec![
+            TestAccount::wallet(self.taker, 10 * SOL, true),
+            TestAccount::wallet(self.offerer, 0, false),
+            self.escrow_state(created),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+        ];
+        accounts.extend(self.release_accounts(created, self.taker));
+        for mint in self.requested.iter() {
+            accounts.extend([
+                TestAccount::new(Pubkey::new_unique(), spl_token::id(), token_account(*mint, self.taker, 1)),
+                TestAccount::new(*mint, spl_token::id(), nft_mint()),
+                TestAccount::new(Pubkey::new_unique(), spl_token::id(), token_account(*mint, self.offerer, 0)),
+            ]);
+        }
+        accounts
+    }
+
+    fn cancel_accounts(&self, created: &[TestAccount]) -> Vec<TestAccount> {
+        let mut accounts = vec![
+            TestAccount::signer(self.offerer),
+            self.escrow_state(created),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+        ];
+        accounts.extend(self.release_accounts(created, self.offerer));
+        accounts
+    }
+}
+
+fn swap_data(tag: u8) -> Vec<u8> {
+    let mut data = vec![tag];
+    data.extend_from_slice(&SWAP_ID.to_be_bytes());
+    data
+}
+
+fn amount(account: &TestAccount) -> u64 {
+    Account::unpack(&account.data).unwrap().amount
+}
+
+fn create_escrows_offered_nfts_and_lamports(program_id: Pubkey) {
+    let swap = Swap::new(program_id, 2, 1);
+    let accounts = swap.created();
+
+    let swap_state = SwapEscrowState::unpack(&accounts[ESCROW_STATE].data).unwrap();
+    assert_eq!(swap_state.offerer, swap.offerer);
+    assert_eq!(swap_state.offered(), &swap.offered[..]);
+    assert_eq!(swap_state.requested(), &swap.requested[..]);
+    assert_eq!((swap_state.offered_lamports, swap_state.requested_lamports), (OFFERED_LAMPORTS, REQUESTED_LAMPORTS));
+    for index in 0..swap.offered.len() {
+        assert_eq!(amount(&accounts[CREATE_ACCOUNTS + 3 * index]), 0);
+        let vault = Account::unpack(&accounts[CREATE_ACCOUNTS + 3 * index + 2].data).unwrap();
+        assert_eq!((vault.mint, vault.owner, vault.amount), (swap.offered[index], swap.state, 1));
+    }
+    // The offered lamports are held on top of the state's rent
+    assert_eq!(accounts[ESCROW_STATE].lamports, Rent::default().minimum_balance(state::SWAPESCROWSTATE) + OFFERED_LAMPORTS);
+}
+
+fn create_rejects_incomplete_nft_accounts(program_id: Pubkey) {
+    let swap = Swap::new(program_id, 2, 1);
+    let mut accounts = swap.create_accounts();
+    accounts.pop();
+    assert_eq!(process(&program_id, &mut accounts, &swap.create_data()), Err(ProgramError::NotEnoughAccountKeys));
+}
+
+fn accept_exchanges_nfts_and_lamports(program_id: Pubkey) {
+    let swap = Swap::new(program_id, 2, 1);
+    let created = swap.created();
+    let mut accounts = swap.accept_accounts(&created);
+    let escrowed: u64 = accounts[SWAP_STATE].lamports + (0..2).map(|index| accounts[ACCEPT_ACCOUNTS + 3 * index].lamports).sum::<u64>();
+    process(&program_id, &mut accounts, &swap_data(14)).unwrap();
+
+    for index in 0..2 {
+        assert_eq!(amount(&accounts[ACCEPT_ACCOUNTS + 3 * index + 2]), 1);
+    }
+    let requested = ACCEPT_ACCOUNTS + 6;
+    assert_eq!((amount(&accounts[requested]), amount(&accounts[requested + 2])), (0, 1));
+    assert_eq!(accounts[TAKER].lamports, 10 * SOL - REQUESTED_LAMPORTS + OFFERED_LAMPORTS);
+    // The state's rent and the closed vaults go back to the offerer
+    assert_eq!(accounts[ACCEPT_OFFERER].lamports, REQUESTED_LAMPORTS + escrowed - OFFERED_LAMPORTS);
+    assert_eq!(accounts[SWAP_STATE].lamports, 0);
+}
+
+fn accept_rejects_other_requested_mint(program_id: Pubkey) {
+    let swap = Swap::new(program_id, 1, 1);
+    let created = swap.created();
+    let mut accounts = swap.accept_accounts(&created);
+    let requested_mint = ACCEPT_ACCOUNTS + 3 + 1;
+    accounts[requested_mint] = TestAccount::new(Pubkey::new_unique(), spl_token::id(), nft_mint());
+    assert_eq!(process(&program_id, &mut accounts, &swap_data(14)), Err(ProgramError::InvalidAccountData));
+}
+
+fn accept_rejects_missing_requested_nft(program_id: Pubkey) {
+    let swap = Swap::new(program_id, 1, 2);
+    let created = swap.created();
+    let mut accounts = swap.accept_accounts(&created);
+    accounts.truncate(accounts.len() - 3);
+    assert_eq!(process(&program_id, &mut accounts, &swap_data(14)), Err(ProgramError::NotEnoughAccountKeys));
+}
+
+fn cancel_returns_offer(program_id: Pubkey) {
+    let swap = Swap::new(program_id, 2, 1);
+    let created = swap.created();
+    let mut accounts = swap.cancel_accounts(&created);
+    let escrowed: u64 = accounts[ESCROW_STATE].lamports + (0..2).map(|index| accounts[3 + 3 * index].lamports).sum::<u64>();
+    process(&program_id, &mut accounts, &swap_data(15)).unwrap();
+
+    for index in 0..2 {
+        assert_eq!(amount(&accounts[3 + 3 * index + 2]), 1);
+    }
+    assert_eq!(accounts[OFFERER].lamports, escrowed);
+    assert_eq!(accounts[ESCROW_STATE].lamports, 0);
+}
+
+fn cancel_requires_offerer(program_id: Pubkey) {
+    let swap = Swap::new(program_id, 1, 1);
+    let created = swap.created();
+    let mut accounts = swap.cancel_accounts(&created);
+    accounts[OFFERER] = TestAccount::signer(swap.taker);
+    assert_eq!(process(&program_id, &mut accounts, &swap_data(15)), Err(ProgramError::InvalidAccountData));
+}
+
+fn main() {
+    let program_id = Pubkey::new_unique();
+    program_stubs::set_syscall_stubs(Box::new(TokenMock));
+
+    run_tests!("swap", [
+        create_escrows_offered_nfts_and_lamports(program_id),
+        create_rejects_incomplete_nft_accounts(program_id),
+        accept_exchanges_nfts_and_lamports(program_id),
+        accept_rejects_other_requested_mint(program_id),
+        accept_rejects_missing_requested_nft(program_id),
+        cancel_returns_offer(program_id),
+        cancel_requires_offerer(program_id),
+    ]);
+}
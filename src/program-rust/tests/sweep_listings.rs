@@ -0,0 +1,174 @@
+#[macro_use]
+mod common;
+
+use common::{packed, process, token_account, TestAccount, TokenMock};
+use solana_program::{
+    entrypoint::ProgramResult,
+    program_pack::Pack,
+    program_stubs,
+    pubkey::Pubkey,
+    system_program,
+};
+use spl_token::state::Account;
+use test::{
+    error::NFTError,
+    state::{self, ListEscrowState, MintState, PlatformState},
+};
+
+const SOL: u64 = 1_000_000_000;
+// 2% platform fee, all of it paid by the seller
+const PLATFORM_FEE: u64 = 20_000_000;
+
+const BUYER: usize = 0;
+const VAULT: usize = 2;
+const FIXED_ACCOUNTS: usize = 6;
+const LISTING_ACCOUNTS: usize = 9;
+// Offsets into the accounts of one listing
+const TOKEN_ACCOUNT: usize = 0;
+const LISTER: usize = 2;
+const LIST_STATE: usize = 3;
+const LIST_VAULT: usize = 4;
+
+struct Sweep {
+    program_id: Pubkey,
+    accounts: Vec<TestAccount>,
+    prices: Vec<u64>,
+    sold_before: Vec<usize>,
+}
+
+impl Sweep {
+    fn new(program_id: Pubkey, prices: &[u64]) -> Self {
+        let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &program_id).0;
+        let mut accounts = vec![
+            TestAccount::wallet(Pubkey::new_unique(), 100 * SOL, true),
+            TestAccount::new(pda(&[b"Platform", b"State"]), program_id, packed(PlatformState {
+                is_initialized: true,
+                platform_fee: PLATFORM_FEE,
+                ..PlatformState::default()
+            })),
+            TestAccount::new(pda(&[b"Platform", b"Vault"]), program_id, vec![]),
+            TestAccount::new(spl_token::id(), Pubkey::default(), vec![]),
+            TestAccount::new(system_program::id(), Pubkey::default(), vec![]),
+            TestAccount::new(pda(&[b"Platform", b"FeeSchedule"]), program_id, vec![]),
+        ];
+
+        for (list_id, price) in prices.iter().enumerate() {
+            let list_id = list_id as u64;
+            let buyer = accounts[BUYER].key;
+            let mint = Pubkey::new_unique();
+            let lister = Pubkey::new_unique();
+            let list_seeds = |kind: &[u8]| pda(&[mint.as_ref(), lister.as_ref(), &list_id.to_be_bytes(), b"List", kind]);
+            let list_state = TestAccount::new(list_seeds(b"State"), program_id, packed(ListEscrowState {
+                is_initialized: true,
+                lister,
+                mint,
+                amount: *price,
+                list_id,
+                ..ListEscrowState::default()
+            }))
+            .with_lamports(1_000_000);
+            accounts.extend(vec![
+                TestAccount::new(Pubkey::new_unique(), spl_token::id(), token_account(mint, buyer, 0)),
+                TestAccount::new(mint, spl_token::id(), vec![]),
+                TestAccount::new(lister, system_program::id(), vec![]),
+                list_state,
+                TestAccount::new(list_seeds(b"Vault"), spl_token::id(), token_account(mint, list_seeds(b"State"), 1)),
+                TestAccount::new(pda(&[mint.as_ref(), b"Mint", b"State"]), program_id, packed(MintState {
+                    is_initialized: true,
+                    mint,
+                    sale_count: 0,
+                })),
+                TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+                TestAccount::new(pda(&[lister.as_ref(), b"Trader", b"State"]), program_id, vec![]),
+                TestAccount::new(pda(&[mint.as_ref(), b"OrderBook", b"State"]), program_id, vec![]),
+            ]);
+        }
+        Sweep { program_id, accounts, prices: prices.to_vec(), sold_before: vec![] }
+    }
+
+    fn listing(&mut self, index: usize, offset: usize) -> &mut TestAccount {
+        &mut self.accounts[FIXED_ACCOUNTS + index * LISTING_ACCOUNTS + offset]
+    }
+
+    fn mark_sold(&mut self, index: usize) {
+        let list_state = self.listing(index, LIST_STATE);
+        let mut state = ListEscrowState::unpack(&list_state.data).unwrap();
+        state.success = true;
+        ListEscrowState::pack(state, &mut list_state.data).unwrap();
+        self.sold_before.push(index);
+    }
+
+    fn process(&mut self, max_total: u64, skip_unavailable: bool) -> ProgramResult {
+        let mut data = vec![36];
+        data.extend_from_slice(&max_total.to_be_bytes());
+        data.push(skip_unavailable as u8);
+        process(&self.program_id, &mut self.accounts, &data)
+    }
+
+    fn assert_bought(&mut self, bought: &[usize]) {
+        let mut paid = 0;
+        for index in 0..self.prices.len() {
+            let price = self.prices[index];
+            let sold = bought.contains(&index);
+            let fee = state::calculate_fee(price, PLATFORM_FEE);
+            let nft = Account::unpack(&self.listing(index, TOKEN_ACCOUNT).data).unwrap().amount;
+            assert_eq!(nft, sold as u64);
+            let success = ListEscrowState::unpack(&self.listing(index, LIST_STATE).data).unwrap().success;
+            assert_eq!(success, sold || self.sold_before.contains(&index));
+            if sold {
+                assert_eq!(self.listing(index, LISTER).lamports, price - fee + 1_000_000);
+                paid += price;
+            } else {
+                assert_eq!(self.listing(index, LISTER).lamports, 0);
+            }
+        }
+        assert_eq!(self.accounts[BUYER].lamports, 100 * SOL - paid);
+        let fees: u64 = bought.iter().map(|index| state::calculate_fee(self.prices[*index], PLATFORM_FEE)).sum();
+        assert_eq!(self.accounts[VAULT].lamports, fees);
+    }
+}
+
+fn buys_every_listing_within_max_total(program_id: Pubkey) {
+    let mut sweep = Sweep::new(program_id, &[SOL, 2 * SOL, 3 * SOL]);
+    sweep.process(6 * SOL, false).unwrap();
+    sweep.assert_bought(&[0, 1, 2]);
+}
+
+fn atomic_sweep_fails_on_sold_listing(program_id: Pubkey) {
+    let mut sweep = Sweep::new(program_id, &[SOL, 2 * SOL, 3 * SOL]);
+    sweep.mark_sold(1);
+    assert_eq!(sweep.process(6 * SOL, false), Err(NFTError::ListingAlreadyFullfilled.into()));
+}
+
+fn skipping_sweep_buys_the_rest(program_id: Pubkey) {
+    let mut sweep = Sweep::new(program_id, &[SOL, 2 * SOL, 3 * SOL]);
+    sweep.mark_sold(1);
+    sweep.process(6 * SOL, true).unwrap();
+    sweep.assert_bought(&[0, 2]);
+    // The sold listing is left as it was, its NFT still in escrow for its buyer
+    assert_eq!(Account::unpack(&sweep.listing(1, LIST_VAULT).data).unwrap().amount, 1);
+}
+
+fn atomic_sweep_stops_at_max_total(program_id: Pubkey) {
+    let mut sweep = Sweep::new(program_id, &[SOL, 2 * SOL, 3 * SOL]);
+    assert_eq!(sweep.process(4 * SOL, false), Err(NFTError::SweepLimitExceeded.into()));
+}
+
+fn skipping_sweep_skips_over_max_total(program_id: Pubkey) {
+    let mut sweep = Sweep::new(program_id, &[SOL, 4 * SOL, 2 * SOL]);
+    sweep.process(3 * SOL, true).unwrap();
+    sweep.assert_bought(&[0, 2]);
+}
+
+fn main() {
+    let program_id = Pubkey::new_unique();
+    program_stubs::set_syscall_stubs(Box::new(TokenMock));
+
+    run_tests!("sweep_listings", [
+        buys_every_listing_within_max_total(program_id),
+        atomic_sweep_fails_on_sold_listing(program_id),
+        skipping_sweep_buys_the_rest(program_id),
+        atomic_sweep_stops_at_max_total(program_id),
+        skipping_sweep_skips_over_max_total(program_id),
+    ]);
+}
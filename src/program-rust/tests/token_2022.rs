@@ -0,0 +1,78 @@
+#[macro_use]
+mod common;
+
+use common::{nft_mint, TestAccount};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use test::{error::NFTError, token};
+
+const TRANSFER_FEE_CONFIG: u16 = 1;
+const MINT_CLOSE_AUTHORITY: u16 = 3;
+const DEFAULT_ACCOUNT_STATE: u16 = 6;
+const PERMANENT_DELEGATE: u16 = 12;
+const METADATA_POINTER: u16 = 18;
+
+// Lays a base mint out the way Token-2022 stores it once extensions are initialized
+fn extended_mint(extensions: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    let mut data = nft_mint();
+    data.resize(spl_token::state::Account::LEN, 0);
+    data.push(1);
+    for (extension_type, value) in extensions {
+        data.extend_from_slice(&extension_type.to_le_bytes());
+        data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        data.extend_from_slice(value);
+    }
+    data
+}
+
+fn check(owner: Pubkey, data: Vec<u8>) -> Result<(), ProgramError> {
+    let mut account = TestAccount::new(Pubkey::new_unique(), owner, data);
+    token::check_mint(&account.info())
+}
+
+fn accepts_plain_mints_of_both_programs() {
+    assert_eq!(check(spl_token::id(), nft_mint()), Ok(()));
+    assert_eq!(check(token::token_2022::id(), nft_mint()), Ok(()));
+}
+
+fn accepts_harmless_extensions() {
+    let data = extended_mint(&[
+        (MINT_CLOSE_AUTHORITY, vec![0; 32]),
+        (METADATA_POINTER, vec![0; 64]),
+        (DEFAULT_ACCOUNT_STATE, vec![1]),
+    ]);
+    assert_eq!(check(token::token_2022::id(), data), Ok(()));
+}
+
+fn rejects_transfer_fee_mint() {
+    let data = extended_mint(&[(TRANSFER_FEE_CONFIG, vec![0; 108])]);
+    assert_eq!(check(token::token_2022::id(), data), Err(NFTError::UnsupportedMintExtension.into()));
+}
+
+fn rejects_permanent_delegate_mint() {
+    let data = extended_mint(&[(METADATA_POINTER, vec![0; 64]), (PERMANENT_DELEGATE, vec![0; 32])]);
+    assert_eq!(check(token::token_2022::id(), data), Err(NFTError::UnsupportedMintExtension.into()));
+}
+
+fn rejects_frozen_default_account_state() {
+    let data = extended_mint(&[(DEFAULT_ACCOUNT_STATE, vec![2])]);
+    assert_eq!(check(token::token_2022::id(), data), Err(NFTError::UnsupportedMintExtension.into()));
+}
+
+fn rejects_mint_of_another_program() {
+    assert_eq!(check(Pubkey::new_unique(), nft_mint()), Err(ProgramError::InvalidAccountData));
+}
+
+fn main() {
+    run_tests!("token_2022", [
+        accepts_plain_mints_of_both_programs(),
+        accepts_harmless_extensions(),
+        rejects_transfer_fee_mint(),
+        rejects_permanent_delegate_mint(),
+        rejects_frozen_default_account_state(),
+        rejects_mint_of_another_program(),
+    ]);
+}
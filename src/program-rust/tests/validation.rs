@@ -0,0 +1,154 @@
+#[macro_use]
+mod common;
+
+use common::{packed, process, TestAccount};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use test::{
+    state::{AccountType, BidEscrowState, ListEscrowState, PlatformState},
+    validation,
+};
+
+fn list_state() -> ListEscrowState {
+    ListEscrowState {
+        is_initialized: true,
+        lister: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        amount: 1_000_000_000,
+        ..ListEscrowState::default()
+    }
+}
+
+fn loads_valid_list_state() {
+    let program_id = Pubkey::new_unique();
+    let state = list_state();
+    let mut account = TestAccount::new(Pubkey::new_unique(), program_id, packed(state));
+    assert_eq!(validation::load_list_state(&account.info(), &program_id), Ok(state));
+}
+
+fn rejects_account_owned_by_another_program() {
+    let program_id = Pubkey::new_unique();
+    let mut account = TestAccount::new(Pubkey::new_unique(), Pubkey::new_unique(), packed(list_state()));
+    assert_eq!(
+        validation::load_list_state(&account.info(), &program_id),
+        Err(ProgramError::IllegalOwner)
+    );
+}
+
+fn rejects_account_of_wrong_size() {
+    let program_id = Pubkey::new_unique();
+    let mut data = packed(list_state());
+    data.push(0);
+    let mut account = TestAccount::new(Pubkey::new_unique(), program_id, data);
+    assert_eq!(
+        validation::load_list_state(&account.info(), &program_id),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+fn rejects_account_of_another_type() {
+    let program_id = Pubkey::new_unique();
+    let mut data = packed(list_state());
+    data[0] = AccountType::BidEscrow as u8;
+    let mut account = TestAccount::new(Pubkey::new_unique(), program_id, data);
+    assert_eq!(
+        validation::load_list_state(&account.info(), &program_id),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+fn rejects_uninitialized_account() {
+    let program_id = Pubkey::new_unique();
+    let mut account = TestAccount::new(Pubkey::new_unique(), program_id, vec![0; ListEscrowState::LEN]);
+    assert_eq!(
+        validation::load_list_state(&account.info(), &program_id),
+        Err(ProgramError::UninitializedAccount)
+    );
+}
+
+fn rejects_platform_state_at_wrong_address() {
+    let program_id = Pubkey::new_unique();
+    let state = PlatformState {
+        is_initialized: true,
+        authority: Pubkey::new_unique(),
+        ..PlatformState::default()
+    };
+    let mut account = TestAccount::new(Pubkey::new_unique(), program_id, packed(state));
+    assert_eq!(
+        validation::load_platform_state(&account.info(), &program_id),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+fn change_fee_rejects_spoofed_platform_state() {
+    // An attacker-owned account at the platform state address naming the attacker as authority
+    let program_id = Pubkey::new_unique();
+    let attacker = Pubkey::new_unique();
+    let (state_account_pubkey, _) = Pubkey::find_program_address(&[b"Platform", b"State"], &program_id);
+    let state = PlatformState {
+        is_initialized: true,
+        authority: attacker,
+        ..PlatformState::default()
+    };
+    let mut accounts = vec![
+        TestAccount::signer(attacker),
+        TestAccount::new(state_account_pubkey, attacker, packed(state)),
+    ];
+
+    let mut instruction_data = vec![2];
+    instruction_data.extend_from_slice(&0u64.to_be_bytes());
+    assert_eq!(
+        process(&program_id, &mut accounts, &instruction_data),
+        Err(ProgramError::IllegalOwner)
+    );
+}
+
+fn withdraw_bid_rejects_spoofed_bid_state() {
+    // An attacker-owned account at the bid escrow address, with the escrowed lamports sitting in the vault
+    let program_id = Pubkey::new_unique();
+    let attacker = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let (state_account_pubkey, _) = Pubkey::find_program_address(&[mint.as_ref(), attacker.as_ref(), &0u64.to_be_bytes(), b"Bid", b"State"], &program_id);
+    let (vault_account_pubkey, _) = Pubkey::find_program_address(&[mint.as_ref(), attacker.as_ref(), &0u64.to_be_bytes(), b"Bid", b"Vault"], &program_id);
+    let state = BidEscrowState {
+        is_initialized: true,
+        bidder: attacker,
+        mint,
+        amount: 1_000_000_000,
+        ..BidEscrowState::default()
+    };
+    let mut accounts = vec![
+        TestAccount::signer(attacker),
+        TestAccount::new(mint, spl_token::id(), vec![]),
+        TestAccount::new(state_account_pubkey, attacker, packed(state)),
+        TestAccount::new(vault_account_pubkey, program_id, vec![]).with_lamports(1_000_000_000),
+        TestAccount::new(program_id, Pubkey::default(), vec![]),
+        TestAccount::new(Pubkey::new_unique(), program_id, vec![]),
+    ];
+
+    let mut instruction_data = vec![6];
+    instruction_data.extend_from_slice(&0u64.to_be_bytes());
+    let escrowed = accounts[3].lamports;
+    assert_eq!(
+        process(&program_id, &mut accounts, &instruction_data),
+        Err(ProgramError::IllegalOwner)
+    );
+    // Nothing moved before the state was rejected
+    assert_eq!((accounts[0].lamports, accounts[3].lamports), (0, escrowed));
+}
+
+fn main() {
+    run_tests!("validation", [
+        loads_valid_list_state(),
+        rejects_account_owned_by_another_program(),
+        rejects_account_of_wrong_size(),
+        rejects_account_of_another_type(),
+        rejects_uninitialized_account(),
+        rejects_platform_state_at_wrong_address(),
+        change_fee_rejects_spoofed_platform_state(),
+        withdraw_bid_rejects_spoofed_bid_state(),
+    ]);
+}